@@ -191,6 +191,10 @@ namespace Compiler
                 var generator = makeGenerator(schema);
                 generator.WriteAuxiliaryFiles(outputFile.DirectoryName ?? string.Empty);
                 var compiled = generator.Compile(langVersion);
+                if (schema.Warnings.Count > 0)
+                {
+                    await Log.WriteSpanWarnings(schema.Warnings);
+                }
                 await File.WriteAllTextAsync(outputFile.FullName, compiled);
                 return Ok;
             }
@@ -160,6 +160,10 @@ namespace Core.Exceptions
         public CyclicDefinitionsException(Definition definition)
             : base($"The schema contains an invalid cycle of definitions, involving '{definition.Name}'.", definition.Span, 116)
         { }
+
+        public CyclicDefinitionsException(Definition definition, IEnumerable<string> cycle)
+            : base($"The schema contains an invalid cycle of definitions, '{string.Join("' contains '", cycle)}' by value. One of the fields in the cycle needs to be an array, a map, or a message.", definition.Span, 116)
+        { }
     }
 
     [Serializable]
@@ -257,4 +261,36 @@ namespace Core.Exceptions
             : base($"Enums must have an integer underlying type, not {t}.", t.Span, 128)
         { }
     }
+
+    [Serializable]
+    public class BlockedConversionWarning : SpanException
+    {
+        public BlockedConversionWarning(Definition from, Definition to, Field field)
+            : base($"'{from.Name}' is not converted to '{to.Name}' since the type of '{field.Name}' does not convert.", field.Span, 129)
+        { }
+    }
+
+    [Serializable]
+    class InvalidDeriveAttributeUsageException : SpanException
+    {
+        public InvalidDeriveAttributeUsageException(Definition definition)
+            : base($"The definition '{definition.Name}' cannot be marked with a derive attribute as it is not a struct, message, or union", definition.Span, 130)
+        { }
+    }
+
+    [Serializable]
+    class InvalidDeriveAttributeValueException : SpanException
+    {
+        public InvalidDeriveAttributeValueException(Definition definition, string reason)
+            : base($"The definition '{definition.Name}' was marked with a derive attribute containing an invalid value: {reason}", definition.Span, 131)
+        { }
+    }
+
+    [Serializable]
+    public class IgnoredDeriveWarning : SpanException
+    {
+        public IgnoredDeriveWarning(Definition definition, string trait)
+            : base($"'{trait}' in the derive attribute of '{definition.Name}' is ignored, only Eq, Hash, PartialOrd, and Ord can be added or removed.", definition.Span, 132)
+        { }
+    }
 }
@@ -48,6 +48,36 @@ namespace Core.Generators.Rust
         private static readonly HashSet<string> _reservedWords = RustGenerator._reservedWordsArray.ToHashSet();
         private Dictionary<string, bool> _needsLifetime = new Dictionary<string, bool>();
 
+        /// <summary>
+        /// Definitions `NeedsLifetime` is currently checking, a definition can contain itself through an array or map.
+        /// </summary>
+        private readonly HashSet<string> _checkingLifetime = new HashSet<string>();
+
+        /// <summary>
+        /// Ownership type definitions are currently being written with.
+        /// </summary>
+        private OwnershipType _ownership = OwnershipType.Borrowed;
+
+        /// <summary>
+        /// The type of enum fields, declared in each module by <c>WriteTypeAliases</c>.
+        /// </summary>
+        private const string EnumAlias = "BebopEnum";
+
+        /// <summary>
+        /// The type of arrays which are not borrowed from the buffer, declared in each module by <c>WriteArrayAlias</c>.
+        /// </summary>
+        private const string ArrayAlias = "BebopArray";
+
+        /// <summary>
+        /// The comparison traits beyond <c>PartialEq</c> in the order they are derived, see <c>ComparisonDerives</c>.
+        /// </summary>
+        private static readonly string[] ComparisonTraits = { "Eq", "Hash", "PartialOrd", "Ord" };
+
+        /// <summary>
+        /// Message fields which lead back to the message they are in, found by <c>FindBoxedFields</c>.
+        /// </summary>
+        private readonly HashSet<Field> _boxedFields = new HashSet<Field>();
+
         #endregion
 
         #region entrypoints
@@ -57,20 +87,32 @@ namespace Core.Generators.Rust
         public override string Compile(Version? languageVersion)
         {
             var builder = new IndentedStringBuilder()
-                .AppendLine(GeneratorUtils.GetMarkdownAutoGeneratedNotice())
+                .AppendLine(GeneratorUtils.GetMarkdownAutoGeneratedNotice());
+            WriteConversionsDocumentation(builder);
+            builder
                 .AppendLine()
                 .AppendLine("#![allow(warnings)]")
                 .AppendLine()
-                .AppendLine("use ::std::io::Write as _;")
+                .AppendLine("use ::bebop::prelude::*;")
+                .AppendLine("use ::bebop::Write as _;")
                 .AppendLine("use ::core::convert::TryInto as _;")
-                .AppendLine("use ::bebop::FixedSized as _;")
                 .AppendLine();
+            WriteTypeAliases(builder);
+
+            FindBoxedFields();
+            CheckForInfiniteSize();
+            CheckDeriveAttributes();
 
             // TODO: do we need to do something with the namespace? Probably not since the file is itself a module.
 
             foreach (var definition in Schema.Definitions.Values)
             {
-                WriteDocumentation(builder, definition.Documentation);
+                // doc comments on a macro invocation are discarded so bitflags write their own
+                if (definition is not EnumDefinition { IsBitFlags: true })
+                {
+                    WriteDocumentation(builder, definition.Documentation);
+                }
+
                 switch (definition)
                 {
                     case ConstDefinition cd:
@@ -78,17 +120,31 @@ namespace Core.Generators.Rust
                         break;
                     case EnumDefinition ed:
                         WriteEnumDefinition(builder, ed);
+                        WriteDebugJson(builder, ed);
+                        WriteArbitrary(builder, ed);
                         break;
                     case MessageDefinition md:
                         if (md.Parent is UnionDefinition) continue;
                         WriteMessageDefinition(builder, md);
+                        WriteDebugJson(builder, md);
+                        WriteArbitrary(builder, md);
+                        WriteWasmFunctions(builder, md);
+                        WriteWireHash(builder, md);
                         break;
                     case StructDefinition sd:
                         if (sd.Parent is UnionDefinition) continue;
                         WriteStructDefinition(builder, sd);
+                        WriteDebugJson(builder, sd);
+                        WriteArbitrary(builder, sd);
+                        WriteWasmFunctions(builder, sd);
+                        WriteWireHash(builder, sd);
                         break;
                     case UnionDefinition ud:
                         WriteUnionDefinition(builder, ud);
+                        WriteDebugJson(builder, ud);
+                        WriteArbitrary(builder, ud);
+                        WriteWasmFunctions(builder, ud);
+                        WriteWireHash(builder, ud);
                         break;
                     default:
                         throw new InvalidOperationException($"unsupported definition {definition.GetType()}");
@@ -97,6 +153,9 @@ namespace Core.Generators.Rust
                 builder.AppendLine();
             }
 
+            WriteConversions(builder);
+            WriteOwnedModule(builder);
+
             return builder.ToString();
         }
 
@@ -109,6 +168,65 @@ namespace Core.Generators.Rust
 
         #region definition_writers
 
+        /// <summary>
+        /// Write the `owned` module which mirrors the top level definitions without borrowing from the
+        /// input buffer. Definitions which never borrow are re-exported so they remain the same type.
+        /// </summary>
+        private void WriteOwnedModule(IndentedStringBuilder builder)
+        {
+            builder
+                .AppendLine("/// Definitions which own all of their data and can outlive the buffer they were read from.")
+                .AppendLine("#[cfg(feature = \"owned\")]")
+                .CodeBlock("pub mod owned", _tab, () =>
+                {
+                    builder
+                        .AppendLine("use ::bebop::prelude::*;")
+                        .AppendLine("use ::bebop::Write as _;")
+                        .AppendLine("use ::core::convert::TryInto as _;")
+                        .AppendLine();
+                    WriteTypeAliases(builder);
+
+                    _ownership = OwnershipType.Owned;
+                    foreach (var definition in Schema.Definitions.Values)
+                    {
+                        if (definition.Parent is UnionDefinition) continue;
+                        if (!NeedsLifetime(definition))
+                        {
+                            var ident = definition is ConstDefinition
+                                ? MakeConstIdent(definition.Name)
+                                : MakeDefIdent(definition.Name);
+                            builder.AppendLine($"pub use super::{ident};");
+                            continue;
+                        }
+
+                        builder.AppendLine();
+                        WriteDocumentation(builder, definition.Documentation);
+                        switch (definition)
+                        {
+                            case MessageDefinition md:
+                                WriteMessageDefinition(builder, md);
+                                break;
+                            case StructDefinition sd:
+                                WriteStructDefinition(builder, sd);
+                                break;
+                            case UnionDefinition ud:
+                                WriteUnionDefinition(builder, ud);
+                                break;
+                            default:
+                                throw new InvalidOperationException($"unsupported definition {definition.GetType()}");
+                        }
+
+                        WriteOwnedConversions(builder, definition);
+                        WriteDebugJson(builder, definition);
+                        WriteArbitrary(builder, definition);
+                    }
+
+                    builder.AppendLine();
+                    WriteConversions(builder);
+                    _ownership = OwnershipType.Borrowed;
+                }).AppendLine();
+        }
+
         private void WriteConstDefinition(IndentedStringBuilder builder, ConstDefinition d)
         {
             builder.AppendLine(
@@ -125,6 +243,8 @@ namespace Core.Generators.Rust
             {
                 builder.CodeBlock("::bebop::bitflags!", _tab, () =>
                 {
+                    WriteDocumentation(builder, d.Documentation);
+                    WriteSerdeDerive(builder, true);
                     builder.CodeBlock($"pub struct {name}: {type}", _tab, () =>
                     {
                         foreach (var m in d.Members)
@@ -138,18 +258,22 @@ namespace Core.Generators.Rust
             }
             else
             {
+                // the repr and explicit discriminants make the layout match the C# and C++ outputs and casting a
+                // member with `as` give its wire value, `lenient_enums` only changes the type of fields
                 builder
                     .AppendLine($"#[repr({type})]")
-                    .AppendLine("#[derive(Copy, Clone, Debug, Eq, PartialEq)]")
-                    .CodeBlock($"pub enum {name}", _tab, () =>
+                    .AppendLine("#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]");
+                WriteSerdeDerive(builder, true);
+                WriteNonExhaustive(builder);
+                builder.CodeBlock($"pub enum {name}", _tab, () =>
+                {
+                    foreach (var m in d.Members)
                     {
-                        foreach (var m in d.Members)
-                        {
-                            WriteDocumentation(builder, m.Documentation);
-                            WriteDeprecation(builder, m.DeprecatedAttribute);
-                            builder.AppendLine($"{MakeEnumVariantIdent(m.Name)} = {m.ConstantValue},");
-                        }
-                    }).AppendLine();
+                        WriteDocumentation(builder, m.Documentation);
+                        WriteDeprecation(builder, m.DeprecatedAttribute);
+                        builder.AppendLine($"{MakeEnumVariantIdent(m.Name)} = {m.ConstantValue},");
+                    }
+                }).AppendLine();
 
                 // conversion from int
                 builder.CodeBlock($"impl ::core::convert::TryFrom<{type}> for {name}", _tab, () =>
@@ -165,7 +289,8 @@ namespace Core.Generators.Rust
                                     builder.AppendLine($"{m.ConstantValue} => Ok({name}::{MakeEnumVariantIdent(m.Name)}),");
                                 }
 
-                                builder.AppendLine("d => Err(::bebop::DeserializeError::InvalidEnumDiscriminator(d.into())),");
+                                builder.AppendLine(
+                                    "d => Err(::bebop::DeserializeError::InvalidEnumDiscriminant { value: d.into() }),");
                             });
                         });
                 }).AppendLine();
@@ -184,21 +309,39 @@ namespace Core.Generators.Rust
                         });
                     });
                 }).AppendLine();
+
+                builder.CodeBlock($"impl ::bebop::Enum for {name}", _tab, () =>
+                {
+                    builder
+                        .AppendLine($"type Repr = {type};")
+                        .AppendLine()
+                        .AppendLine("#[inline]")
+                        .CodeBlock($"fn from_repr(value: {type}) -> ::core::option::Option<Self>", _tab, () =>
+                        {
+                            builder.AppendLine("::core::convert::TryFrom::try_from(value).ok()");
+                        })
+                        .AppendLine()
+                        .AppendLine("#[inline]")
+                        .CodeBlock($"fn to_repr(self) -> {type}", _tab, () =>
+                        {
+                            builder.AppendLine("self.into()");
+                        });
+                }).AppendLine();
             }
 
             // sub record
             builder.CodeBlock($"impl<'raw> ::bebop::SubRecord<'raw> for {name}", _tab, () =>
             {
                 builder
-                    .AppendLine($"const MIN_SERIALIZED_SIZE: usize = ::std::mem::size_of::<{type}>();")
-                    .AppendLine($"const EXACT_SERIALIZED_SIZE: Option<usize> = Some(::std::mem::size_of::<{type}>());")
+                    .AppendLine($"const MIN_SERIALIZED_SIZE: usize = ::core::mem::size_of::<{type}>();")
+                    .AppendLine($"const EXACT_SERIALIZED_SIZE: Option<usize> = Some(::core::mem::size_of::<{type}>());")
                     .AppendLine()
                     .AppendLine("#[inline]")
-                    .AppendLine($"fn serialized_size(&self) -> usize {{ ::std::mem::size_of::<{type}>() }}")
+                    .AppendLine($"fn serialized_size(&self) -> usize {{ ::core::mem::size_of::<{type}>() }}")
                     .AppendLine()
                     .AppendLine("#[inline]")
                     .CodeBlock(
-                        "fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
+                        "fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
                         _tab,
                         () =>
                         {
@@ -216,19 +359,26 @@ namespace Core.Generators.Rust
                         });
             }).AppendLine();
 
+            WriteAsyncSubRecord(builder, name);
+
             builder.CodeBlock($"impl ::bebop::FixedSized for {name}", _tab, () =>
             {
-                builder.AppendLine($"const SERIALIZED_SIZE: usize = ::std::mem::size_of::<{type}>();");
+                builder.AppendLine($"const SERIALIZED_SIZE: usize = ::core::mem::size_of::<{type}>();");
+                if (!d.IsBitFlags)
+                {
+                    // not every value is a member
+                    builder.AppendLine("const IS_WIRE_FORMAT: bool = false;");
+                }
             }).AppendLine();
         }
 
         private void WriteStructDefinition(IndentedStringBuilder builder, StructDefinition d)
         {
-            var needsLifetime = NeedsLifetime(d);
+            var needsLifetime = HasLifetime(d);
             var name = MakeDefIdent(d.Name) + (needsLifetime ? "<'raw>" : "");
             var isFixedSize = d.IsFixedSize(Schema);
 
-            builder.Append("#[derive(Clone, Debug, PartialEq");
+            builder.Append($"#[derive(Clone, Debug, PartialEq{ComparisonDerives(d)}");
             if (isFixedSize)
             {
                 // this will allow us access it in a raw buffer without copying it.
@@ -241,15 +391,29 @@ namespace Core.Generators.Rust
                 builder.AppendEnd(")]");
             }
 
+            WriteSerdeDerive(builder, !needsLifetime);
             builder
                 .CodeBlock($"pub struct {name}", _tab, () => WriteStructDefinitionAttrs(builder, d))
                 .AppendLine();
 
             if (isFixedSize)
             {
-                builder
-                    .AppendLine($"impl ::bebop::FixedSized for {name} {{}}")
-                    .AppendLine();
+                // arrays of this are checked item by item if any field has to be
+                var isWireFormat = d.Fields.Count == 0
+                    ? "true"
+                    : string.Join(" && ",
+                        d.Fields.Select((f) => $"<{TypeName(f.Type)} as ::bebop::FixedSized>::IS_WIRE_FORMAT"));
+                // not the size of the struct since lenient enum fields are bigger than they are on the wire
+                var serializedSize = d.Fields.Count == 0
+                    ? "0"
+                    : string.Join(" + ",
+                        d.Fields.Select((f) => $"<{TypeName(f.Type)} as ::bebop::FixedSized>::SERIALIZED_SIZE"));
+                builder.CodeBlock($"impl ::bebop::FixedSized for {name}", _tab, () =>
+                {
+                    builder
+                        .AppendLine($"const SERIALIZED_SIZE: usize = {serializedSize};")
+                        .AppendLine($"const IS_WIRE_FORMAT: bool = {isWireFormat};");
+                }).AppendLine();
             }
 
             builder
@@ -288,7 +452,7 @@ namespace Core.Generators.Rust
                     }).AppendLine();
 
                     builder.CodeBlock(
-                        "fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
+                        "fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
                         _tab, () =>
                         {
                             if (d.Fields.Count == 0)
@@ -307,15 +471,50 @@ namespace Core.Generators.Rust
                             }
                         }).AppendLine();
 
-                    builder.CodeBlock("fn _deserialize_chained(raw: &'raw [u8]) -> ::bebop::DeResult<(usize, Self)>",
+                    // fixed size structs have nothing to reuse and can't have their packed fields borrowed
+                    var reuse = !isFixedSize && d.Fields.Count > 0;
+                    WriteDefaultDepthDeserialization(builder, reuse);
+                    builder.CodeBlock(
+                        "fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> ::bebop::DeResult<(usize, Self)>",
                         _tab,
                         () =>
                         {
+                            builder.AppendLine("let depth = ::bebop::nested_depth(depth)?;");
                             WriteStructDeserialization(builder, d);
                         });
+
+                    if (reuse)
+                    {
+                        builder.AppendLine().CodeBlock(
+                            "fn _deserialize_chained_into_with_depth(&mut self, raw: &'raw [u8], depth: usize) -> ::bebop::DeResult<usize>",
+                            _tab,
+                            () =>
+                            {
+                                builder
+                                    .AppendLine("let depth = ::bebop::nested_depth(depth)?;")
+                                    .AppendLine("let mut i = 0;");
+                                foreach (var f in d.Fields)
+                                {
+                                    builder.AppendLine(
+                                        $"i += self.{MakeAttrIdent(f.Name)}._deserialize_chained_into_with_depth(&raw[i..], depth).map_err(|err| err.at_offset(i))?;");
+                                }
+
+                                builder.AppendLine("Ok(i)");
+                            });
+                    }
                 }).AppendLine();
 
+            // fixed size structs are small enough to serialize at once
+            WriteAsyncSubRecord(builder, name, isFixedSize ? null : () =>
+            {
+                builder.CodeBlock("Ok(", _tab, () =>
+                {
+                    builder.AppendLine(string.Join(" +\n",
+                        d.Fields.Select((f) => SerializeCall($"self.{MakeAttrIdent(f.Name)}", true))));
+                }, "", ")");
+            });
             WriteRecordImpl(builder, name, d);
+            WriteBuilder(builder, d, needsLifetime);
         }
 
         /// <summary>
@@ -327,6 +526,7 @@ namespace Core.Generators.Rust
             {
                 WriteDocumentation(builder, f.Documentation);
                 WriteDeprecation(builder, f.DeprecatedAttribute);
+                WriteSerdeFieldAttr(builder, f, false);
                 var pub = makePub ? "pub " : "";
                 builder.AppendLine($"{pub}{MakeAttrIdent(f.Name)}: {TypeName(f.Type)},");
             }
@@ -361,7 +561,7 @@ namespace Core.Generators.Rust
             {
                 builder
                     .AppendLine(
-                        $"let (read, v{j}) = <{TypeName(f.Type)}>::_deserialize_chained(&raw[i..])?;")
+                        $"let (read, v{j}) = <{TypeName(f.Type)}>::_deserialize_chained_with_depth(&raw[i..], depth).map_err(|err| err.at_offset(i))?;")
                     .AppendLine("i += read;");
                 vars.AddLast((MakeAttrIdent(f.Name), $"v{j++}"));
             }
@@ -377,11 +577,12 @@ namespace Core.Generators.Rust
 
         private void WriteMessageDefinition(IndentedStringBuilder builder, MessageDefinition d)
         {
-            var needsLifetime = NeedsLifetime(d);
+            var needsLifetime = HasLifetime(d);
             var name = MakeDefIdent(d.Name) + (needsLifetime ? "<'raw>" : "");
 
+            builder.AppendLine($"#[derive(Clone, Debug, PartialEq{ComparisonDerives(d)}, Default)]");
+            WriteSerdeDerive(builder, !needsLifetime);
             builder
-                .AppendLine("#[derive(Clone, Debug, PartialEq, Default)]")
                 .CodeBlock($"pub struct {name}", _tab, () => WriteMessageDefinitionAttrs(builder, d))
                 .AppendLine();
 
@@ -399,18 +600,35 @@ namespace Core.Generators.Rust
                         })
                         .AppendLine()
                         .CodeBlock(
-                            "fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
+                            "fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
                             _tab, () =>
                             {
                                 WriteMessageSerialization(builder, d);
                             }).AppendLine();
 
-                    builder.CodeBlock("fn _deserialize_chained(raw: &'raw [u8]) -> ::bebop::DeResult<(usize, Self)>",
+                    WriteDefaultDepthDeserialization(builder, true);
+                    builder.CodeBlock(
+                        "fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> ::bebop::DeResult<(usize, Self)>",
+                        _tab,
+                        () =>
+                        {
+                            builder.AppendLine("let depth = ::bebop::nested_depth(depth)?;");
+                            WriteMessageDeserialization(builder, d);
+                        }).AppendLine();
+
+                    builder.CodeBlock(
+                        "fn _deserialize_chained_into_with_depth(&mut self, raw: &'raw [u8], depth: usize) -> ::bebop::DeResult<usize>",
                         _tab,
-                        () => WriteMessageDeserialization(builder, d));
+                        () =>
+                        {
+                            builder.AppendLine("let depth = ::bebop::nested_depth(depth)?;");
+                            WriteMessageDeserialization(builder, d, reuse: true);
+                        });
                 }).AppendLine();
 
+            WriteAsyncSubRecord(builder, name, () => WriteMessageSerialization(builder, d, isAsync: true));
             WriteRecordImpl(builder, name, d);
+            WriteBuilder(builder, d, needsLifetime);
         }
 
         /// <summary>
@@ -424,8 +642,9 @@ namespace Core.Generators.Rust
                 WriteDocumentation(builder, f.Documentation);
                 WriteDocumentation(builder, $"Field {f.ConstantValue}");
                 WriteDeprecation(builder, f.DeprecatedAttribute);
+                WriteSerdeFieldAttr(builder, f, true);
                 var pub = makePub ? "pub " : "";
-                builder.AppendLine($"{pub}{MakeAttrIdent(f.Name)}: ::core::option::Option<{TypeName(f.Type)}>,");
+                builder.AppendLine($"{pub}{MakeAttrIdent(f.Name)}: ::core::option::Option<{MessageFieldTypeName(f)}>,");
             }
         }
 
@@ -441,7 +660,8 @@ namespace Core.Generators.Rust
                 )));
         }
 
-        private void WriteMessageSerialization(IndentedStringBuilder builder, MessageDefinition d, bool calcSize = true, string obj = "self")
+        private void WriteMessageSerialization(IndentedStringBuilder builder, MessageDefinition d, bool calcSize = true,
+            string obj = "self", bool isAsync = false)
         {
             obj = string.IsNullOrEmpty(obj) ? "_" : $"{obj}.";
             if (calcSize)
@@ -459,7 +679,7 @@ namespace Core.Generators.Rust
                 {
                     builder
                         .AppendLine($"{f.ConstantValue}u8._serialize_chained(dest)?;")
-                        .AppendLine("v._serialize_chained(dest)?;");
+                        .AppendLine($"{SerializeCall("v", isAsync)};");
                 });
             }
 
@@ -470,8 +690,13 @@ namespace Core.Generators.Rust
             }
         }
 
+        /// <summary>
+        /// Write the body of `_deserialize_chained_with_depth`, or of `_deserialize_chained_into_with_depth` if `reuse`
+        /// is set in which case the previous value of each field is decoded into if it appears again. Fields are decoded
+        /// with the `depth` variable.
+        /// </summary>
         private void WriteMessageDeserialization(IndentedStringBuilder builder, MessageDefinition d,
-            bool externalIter = false, string selfClass = "Self", bool directReturn = false)
+            bool externalIter = false, string selfClass = "Self", bool directReturn = false, bool reuse = false)
         {
             if (!externalIter)
             {
@@ -479,8 +704,9 @@ namespace Core.Generators.Rust
             }
 
             var plusI = externalIter ? " + i" : "";
+            // a length near `u32::MAX` overflows on 32 bit targets, it can never fit in the buffer either way
             builder
-                .AppendLine($"let len = ::bebop::read_len(&raw[i..])?{plusI} + ::bebop::LEN_SIZE;")
+                .AppendLine($"let len = ::bebop::read_len(&raw[i..])?.checked_add(::bebop::LEN_SIZE{plusI}).ok_or(::bebop::DeserializeError::CorruptFrame)?;")
                 .AppendLine($"i += ::bebop::LEN_SIZE;")
                 .AppendLine()
                 .AppendLine("#[cfg(not(feature = \"unchecked\"))]")
@@ -498,6 +724,10 @@ namespace Core.Generators.Rust
             foreach (var f in d.Fields.OrderBy((f) => f.ConstantValue))
             {
                 builder.AppendLine($"let mut _{MakeAttrIdent(f.Name)} = None;");
+                if (reuse)
+                {
+                    builder.AppendLine($"let mut old_{MakeAttrIdent(f.Name)} = self.{MakeAttrIdent(f.Name)}.take();");
+                }
             }
 
             builder
@@ -541,13 +771,16 @@ namespace Core.Generators.Rust
                                     "return Err(::bebop::DeserializeError::DuplicateMessageField);");
                             });
                             builder
-                                .AppendLine(
-                                    $"let (read, value) = <{TypeName(f.Type)}>::_deserialize_chained(&raw[i..])?;")
+                                .AppendLine(reuse
+                                    ? $"let (read, value) = ::bebop::deserialize_reusing(old_{fname}.take(), &raw[i..], depth).map_err(|err| err.at_offset(i))?;"
+                                    : $"let (read, value) = <{MessageFieldTypeName(f)}>::_deserialize_chained_with_depth(&raw[i..], depth).map_err(|err| err.at_offset(i))?;")
                                 .AppendLine("i += read;")
                                 .AppendLine($"_{fname} = Some(value)");
                         });
                     }
 
+                    // fields are written in order and don't have their own lengths, so an unknown field means the
+                    // rest of the message is from a newer schema and is skipped using the message length
                     builder.CodeBlock("_ =>", _tab, () =>
                     {
                         builder
@@ -556,13 +789,25 @@ namespace Core.Generators.Rust
                     });
                 });
             }).AppendLine();
+            // a field running past the end or a terminator before it are both corrupt
             builder.CodeBlock("if i != len", _tab, () =>
             {
-                builder
-                    .AppendLine("debug_assert!(i > len);")
-                    .AppendLine("return Err(::bebop::DeserializeError::CorruptFrame)");
+                builder.AppendLine("return Err(::bebop::DeserializeError::CorruptFrame)");
             });
 
+            if (reuse)
+            {
+                builder.AppendLine().CodeBlock("*self = Self", _tab, () =>
+                {
+                    foreach (var f in d.Fields.OrderBy((f) => f.ConstantValue))
+                    {
+                        var fieldName = MakeAttrIdent(f.Name);
+                        builder.AppendLine($"{fieldName}: _{fieldName},");
+                    }
+                }, "{", "};").AppendLine("Ok(i)");
+                return;
+            }
+
             builder.AppendLine().CodeBlock((directReturn ? "" : "Ok((i, ") + $"{selfClass}", _tab, () =>
             {
                 foreach (var f in d.Fields.OrderBy((f) => f.ConstantValue))
@@ -576,8 +821,10 @@ namespace Core.Generators.Rust
         private void WriteUnionDefinition(IndentedStringBuilder builder, UnionDefinition d)
         {
             var scopeName = MakeDefIdent(d.Name);
-            var name = scopeName + (NeedsLifetime(d) ? "<'raw>" : "");
-            builder.AppendLine("#[derive(Clone, Debug, PartialEq)]");
+            var name = scopeName + (HasLifetime(d) ? "<'raw>" : "");
+            builder.AppendLine($"#[derive(Clone, Debug, PartialEq{ComparisonDerives(d)})]");
+            WriteSerdeDerive(builder, !HasLifetime(d));
+            WriteNonExhaustive(builder);
             builder.CodeBlock($"pub enum {name}", _tab, () =>
             {
                 if (d.Branches.Any((b) => b.Definition.Name == "Unknown"))
@@ -586,8 +833,8 @@ namespace Core.Generators.Rust
                 }
 
                 WriteDocumentation(builder,
-                    "An unknown type which is likely defined in a newer version of the schema.");
-                builder.AppendLine("Unknown,");
+                    "An unknown type which is likely defined in a newer version of the schema, contains its discriminator.");
+                builder.AppendLine("Unknown(u8),");
 
                 foreach (var b in d.Branches.OrderBy((b) => b.Discriminator))
                 {
@@ -611,6 +858,8 @@ namespace Core.Generators.Rust
                 }
             }).AppendLine();
 
+            WriteUnionAccessors(builder, d, scopeName, name);
+
             builder.CodeBlock($"impl<'raw> ::bebop::SubRecord<'raw> for {name}", _tab, () =>
             {
                 builder
@@ -622,7 +871,7 @@ namespace Core.Generators.Rust
                     builder.AppendLine("::bebop::LEN_SIZE + 1 +");
                     builder.CodeBlock("match self", _tab, () =>
                     {
-                        builder.CodeBlock($"{scopeName}::Unknown =>", _tab, () =>
+                        builder.CodeBlock($"{scopeName}::Unknown(_) =>", _tab, () =>
                         {
                             builder.AppendLine("0");
                         });
@@ -678,76 +927,28 @@ namespace Core.Generators.Rust
                 }).AppendLine();
 
                 builder.CodeBlock(
-                    "fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
+                    "fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize>",
                     _tab, () =>
                     {
-                        builder.AppendLine("let size = self.serialized_size();")
-                            // the length and discriminators are not part of the body
-                            .AppendLine("::bebop::write_len(dest, size - ::bebop::LEN_SIZE - 1)?;");
-                        builder.CodeBlock("match self", _tab, () =>
-                        {
-                            builder.CodeBlock($"{scopeName}::Unknown =>", _tab, () =>
-                            {
-                                builder.AppendLine("return Err(::bebop::SerializeError::CannotSerializeUnknownUnion);");
-                            });
-                            // matching against each possible union type
-                            foreach (var b in d.Branches.OrderBy((b) => b.Discriminator))
-                            {
-                                var branchName = MakeEnumVariantIdent(b.Definition.Name);
-                                builder.CodeBlock($"{scopeName}::{branchName}", _tab, () =>
-                                {
-                                    // destructuring
-                                    if (b.Definition is FieldsDefinition fd)
-                                    {
-                                        foreach (var field in fd.Fields)
-                                        {
-                                            var fieldName = MakeAttrIdent(field.Name);
-                                            // alias to prevent name collisions
-                                            builder.AppendLine($"{fieldName}: ref _{fieldName},");
-                                        }
-                                    }
-                                    else
-                                    {
-                                        throw new ArgumentOutOfRangeException(b.Definition.ToString());
-                                    }
-                                });
-                                builder.CodeBlock("=>", _tab, () =>
-                                {
-                                    // serialization
-                                    builder.AppendLine($"{b.Discriminator}u8._serialize_chained(dest)?;");
-                                    switch (b.Definition)
-                                    {
-                                        case StructDefinition sd:
-                                            foreach (var sdField in sd.Fields)
-                                            {
-                                                builder.AppendLine(
-                                                    $"_{MakeAttrIdent(sdField.Name)}._serialize_chained(dest)?;");
-                                            }
-
-                                            break;
-                                        case MessageDefinition md:
-                                            // calculate size of message by taking union size and subbing union length and discriminator
-                                            builder.AppendLine("::bebop::write_len(dest, size - ::bebop::LEN_SIZE * 2 - 1)?;");
-                                            WriteMessageSerialization(builder, md, false, "");
-                                            break;
-                                        default:
-                                            throw new ArgumentOutOfRangeException(b.Definition.ToString());
-                                    }
-                                });
-                            }
-                        });
-
-                        builder
-                            .AppendLine("Ok(size)");
+                        WriteUnionSerialization(builder, d, scopeName, false);
                     }).AppendLine();
 
-                builder.CodeBlock("fn _deserialize_chained(raw: &'raw [u8]) -> ::bebop::DeResult<(usize, Self)>", _tab,
+                WriteDefaultDepthDeserialization(builder, false);
+                builder.CodeBlock(
+                    "fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> ::bebop::DeResult<(usize, Self)>",
+                    _tab,
                     () =>
                     {
                         builder
+                            .AppendLine("let depth = ::bebop::nested_depth(depth)?;")
                             // add 1 for discriminator
-                            .AppendLine("let len = ::bebop::read_len(&raw)? + ::bebop::LEN_SIZE + 1;")
+                            .AppendLine("let len = ::bebop::read_len(&raw)?.checked_add(::bebop::LEN_SIZE + 1).ok_or(::bebop::DeserializeError::CorruptFrame)?;")
                             .AppendLine("let mut i = ::bebop::LEN_SIZE + 1;");
+                        builder.CodeBlock("if raw.len() < len", _tab, () =>
+                        {
+                            builder.AppendLine(
+                                "return Err(::bebop::DeserializeError::MoreDataExpected(len - raw.len()));");
+                        });
 
                         builder.CodeBlock("let de = match raw[::bebop::LEN_SIZE]", _tab, () =>
                         {
@@ -773,19 +974,17 @@ namespace Core.Generators.Rust
                                 });
                             }
 
-                            builder.CodeBlock($"_ =>", _tab, () =>
+                            builder.CodeBlock($"d =>", _tab, () =>
                             {
                                 builder
                                     .AppendLine("i = len;")
-                                    .AppendLine($"{scopeName}::Unknown");
+                                    .AppendLine($"{scopeName}::Unknown(d)");
                             });
                         }, "{", "};");
                         builder.CodeBlock("if !cfg!(feature = \"unchecked\") && i != len", _tab,
                             () =>
                             {
-                                builder
-                                    .AppendLine("debug_assert!(i > len);")
-                                    .AppendLine("Err(::bebop::DeserializeError::CorruptFrame)");
+                                builder.AppendLine("Err(::bebop::DeserializeError::CorruptFrame)");
                             });
                         builder.CodeBlock("else", _tab, () =>
                         {
@@ -794,63 +993,1184 @@ namespace Core.Generators.Rust
                     }).AppendLine();
             }).AppendLine();
 
+            WriteAsyncSubRecord(builder, name, () => WriteUnionSerialization(builder, d, scopeName, true));
             WriteRecordImpl(builder, name, d);
         }
 
-        #endregion
-
-        #region component_writers
-
-        private static void WriteDocumentation(IndentedStringBuilder builder, string documentation)
+        /// <summary>
+        /// Write the body of `_serialize_chained`, or of the future returned by `_serialize_chained_async` if
+        /// `isAsync` is set.
+        /// </summary>
+        private void WriteUnionSerialization(IndentedStringBuilder builder, UnionDefinition d, string scopeName,
+            bool isAsync)
         {
-            if (string.IsNullOrEmpty(documentation)) { return; }
-
-            foreach (var line in documentation.GetLines())
+            builder.AppendLine("let size = self.serialized_size();")
+                // the length and discriminators are not part of the body
+                .AppendLine("::bebop::write_len(dest, size - ::bebop::LEN_SIZE - 1)?;");
+            builder.CodeBlock("match self", _tab, () =>
             {
-                // TODO: make the docs more friendly to rustdoc by formatting as markdown
-                builder.AppendLine($"/// {line}");
-            }
+                builder.CodeBlock($"{scopeName}::Unknown(_) =>", _tab, () =>
+                {
+                    builder.AppendLine("return Err(::bebop::SerializeError::CannotSerializeUnknownUnion);");
+                });
+                // matching against each possible union type
+                foreach (var b in d.Branches.OrderBy((b) => b.Discriminator))
+                {
+                    var branchName = MakeEnumVariantIdent(b.Definition.Name);
+                    builder.CodeBlock($"{scopeName}::{branchName}", _tab, () =>
+                    {
+                        // destructuring
+                        if (b.Definition is FieldsDefinition fd)
+                        {
+                            foreach (var field in fd.Fields)
+                            {
+                                var fieldName = MakeAttrIdent(field.Name);
+                                // alias to prevent name collisions
+                                builder.AppendLine($"{fieldName}: ref _{fieldName},");
+                            }
+                        }
+                        else
+                        {
+                            throw new ArgumentOutOfRangeException(b.Definition.ToString());
+                        }
+                    });
+                    builder.CodeBlock("=>", _tab, () =>
+                    {
+                        // serialization
+                        builder.AppendLine($"{b.Discriminator}u8._serialize_chained(dest)?;");
+                        switch (b.Definition)
+                        {
+                            case StructDefinition sd:
+                                foreach (var sdField in sd.Fields)
+                                {
+                                    builder.AppendLine($"{SerializeCall($"_{MakeAttrIdent(sdField.Name)}", isAsync)};");
+                                }
+
+                                break;
+                            case MessageDefinition md:
+                                // calculate size of message by taking union size and subbing union length and discriminator
+                                builder.AppendLine("::bebop::write_len(dest, size - ::bebop::LEN_SIZE * 2 - 1)?;");
+                                WriteMessageSerialization(builder, md, false, "", isAsync);
+                                break;
+                            default:
+                                throw new ArgumentOutOfRangeException(b.Definition.ToString());
+                        }
+                    });
+                }
+            });
+
+            builder
+                .AppendLine("Ok(size)");
         }
 
-        private static void WriteDeprecation(IndentedStringBuilder builder, BaseAttribute? attr)
+        /// <summary>
+        /// Write `_deserialize_chained`, and `_deserialize_chained_into` if `into` is set, for a record which implements
+        /// the `_with_depth` versions. Decoding starts with the default depth limit.
+        /// </summary>
+        private void WriteDefaultDepthDeserialization(IndentedStringBuilder builder, bool into)
         {
-            if (attr is null) { return; }
-
-            builder.Append("#[deprecated");
-            if (!string.IsNullOrEmpty(attr.Value))
+            builder
+                .AppendLine("#[inline]")
+                .CodeBlock("fn _deserialize_chained(raw: &'raw [u8]) -> ::bebop::DeResult<(usize, Self)>", _tab, () =>
+                {
+                    builder.AppendLine("Self::_deserialize_chained_with_depth(raw, ::bebop::DEFAULT_MAX_DEPTH)");
+                }).AppendLine();
+            if (into)
             {
-                builder.AppendMid($"(note = \"{attr.Value}\")");
+                builder
+                    .AppendLine("#[inline]")
+                    .CodeBlock("fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> ::bebop::DeResult<usize>",
+                        _tab, () =>
+                        {
+                            builder.AppendLine(
+                                "self._deserialize_chained_into_with_depth(raw, ::bebop::DEFAULT_MAX_DEPTH)");
+                        }).AppendLine();
             }
-
-            builder.AppendEnd("]");
         }
 
-        private static void WriteRecordImpl(IndentedStringBuilder builder, string name, TopLevelDefinition d)
+        /// <summary>
+        /// Write `is_*`, `as_*`, and `into_*` accessors for each union branch. Branches with multiple fields give a
+        /// tuple of them and branches without fields only get `is_*`.
+        /// </summary>
+        private void WriteUnionAccessors(IndentedStringBuilder builder, UnionDefinition d, string scopeName,
+            string name)
         {
-            if (d.OpcodeAttribute is { Value: not (null or "") })
+            builder.CodeBlock(HasLifetime(d) ? $"impl<'raw> {name}" : $"impl {name}", _tab, () =>
             {
-                builder.CodeBlock($"impl<'raw> ::bebop::Record<'raw> for {name}", _tab, () =>
+                builder.CodeBlock("pub fn is_unknown(&self) -> bool", _tab, () =>
                 {
-                    builder.AppendLine($"const OPCODE: ::core::option::Option<u32> = Some({d.OpcodeAttribute.Value});");
+                    builder.AppendLine($"matches!(self, {scopeName}::Unknown(_))");
                 });
-            }
-            else
-            {
-                builder.AppendLine($"impl<'raw> ::bebop::Record<'raw> for {name} {{}}");
-            }
-        }
-
-        #endregion
 
-        #region types_and_identifiers
+                builder.AppendLine();
+                WriteDocumentation(builder,
+                    "The discriminator this is written with, also see `::bebop::peek_union_discriminant`.");
+                builder.CodeBlock("pub fn discriminant(&self) -> u8", _tab, () =>
+                {
+                    builder.CodeBlock("match self", _tab, () =>
+                    {
+                        builder.AppendLine($"{scopeName}::Unknown(d) => *d,");
+                        foreach (var b in d.Branches.OrderBy((b) => b.Discriminator))
+                        {
+                            builder.AppendLine(
+                                $"{scopeName}::{MakeEnumVariantIdent(b.Definition.Name)} {{ .. }} => {b.Discriminator},");
+                        }
+                    });
+                });
 
-        private static string MakeConstIdent(string ident)
-        {
-            var reCased = ident.ToSnakeCase().ToUpper();
-            return _reservedWords.Contains(reCased)
-                ? $"_{reCased}"
-                : reCased;
-        }
+                foreach (var b in d.Branches.OrderBy((b) => b.Discriminator))
+                {
+                    var branchName = MakeEnumVariantIdent(b.Definition.Name);
+                    var methodName = b.Definition.Name.ToSnakeCase();
+                    var fields = b.Definition switch
+                    {
+                        StructDefinition sd => sd.Fields
+                            .Select((f) => (MakeAttrIdent(f.Name), TypeName(f.Type)))
+                            .ToList(),
+                        MessageDefinition md => md.Fields.OrderBy((f) => f.ConstantValue)
+                            .Select((f) => (MakeAttrIdent(f.Name), $"::core::option::Option<{MessageFieldTypeName(f)}>"))
+                            .ToList(),
+                        _ => throw new ArgumentOutOfRangeException(b.Definition.ToString())
+                    };
+
+                    builder.AppendLine().CodeBlock($"pub fn is_{methodName}(&self) -> bool", _tab, () =>
+                    {
+                        builder.AppendLine($"matches!(self, {scopeName}::{branchName} {{ .. }})");
+                    });
+                    if (fields.Count == 0) { continue; }
+
+                    var names = fields.Select((f) => f.Item1).ToList();
+                    var pattern = $"{scopeName}::{branchName} {{ {string.Join(", ", names)} }}";
+                    var values = names.Count == 1 ? names[0] : $"({string.Join(", ", names)})";
+                    string Types(string prefix) => fields.Count == 1
+                        ? prefix + fields[0].Item2
+                        : $"({string.Join(", ", fields.Select((f) => prefix + f.Item2))})";
+
+                    builder.AppendLine().CodeBlock(
+                        $"pub fn as_{methodName}(&self) -> ::core::option::Option<{Types("&")}>", _tab, () =>
+                        {
+                            builder.CodeBlock("match self", _tab, () =>
+                            {
+                                builder
+                                    .AppendLine($"{pattern} => Some({values}),")
+                                    .AppendLine("_ => None,");
+                            });
+                        });
+                    builder.AppendLine().CodeBlock(
+                        $"pub fn into_{methodName}(self) -> ::core::option::Option<{Types("")}>", _tab, () =>
+                        {
+                            builder.CodeBlock("match self", _tab, () =>
+                            {
+                                builder
+                                    .AppendLine($"{pattern} => Some({values}),")
+                                    .AppendLine("_ => None,");
+                            });
+                        });
+                }
+            }).AppendLine();
+        }
+
+        /// <summary>
+        /// Write `to_owned` on the borrowed version of a definition and `as_borrowed` on the owned version, for use
+        /// within the `owned` module.
+        /// </summary>
+        private void WriteOwnedConversions(IndentedStringBuilder builder, Definition d)
+        {
+            var ident = MakeDefIdent(d.Name);
+
+            // write the body of a conversion from `from` to `to` where `convert` converts one field value
+            void WriteBody(string from, string to, Func<string, TypeBase, int, string> convert)
+            {
+                string Field(Field f, string v, bool optional) => optional && TypeNeedsLifetime(f.Type)
+                    ? $"{v}.as_ref().map(|v0| {BoxExpr(f, convert(_boxedFields.Contains(f) ? "(**v0)" : "v0", f.Type, 1))})"
+                    : convert(v, f.Type, 0);
+
+                switch (d)
+                {
+                    case StructDefinition sd:
+                        builder.CodeBlock(to, _tab, () =>
+                        {
+                            foreach (var f in sd.Fields)
+                            {
+                                var fieldName = MakeAttrIdent(f.Name);
+                                builder.AppendLine($"{fieldName}: {Field(f, $"(&self.{fieldName})", false)},");
+                            }
+                        });
+                        break;
+                    case MessageDefinition md:
+                        builder.CodeBlock(to, _tab, () =>
+                        {
+                            foreach (var f in md.Fields.OrderBy((f) => f.ConstantValue))
+                            {
+                                var fieldName = MakeAttrIdent(f.Name);
+                                builder.AppendLine($"{fieldName}: {Field(f, $"self.{fieldName}", true)},");
+                            }
+                        });
+                        break;
+                    case UnionDefinition ud:
+                        builder.CodeBlock("match self", _tab, () =>
+                        {
+                            builder.AppendLine($"{from}::Unknown(d) => {to}::Unknown(*d),");
+                            foreach (var b in ud.Branches.OrderBy((b) => b.Discriminator))
+                            {
+                                var branchName = MakeEnumVariantIdent(b.Definition.Name);
+                                var fd = (FieldsDefinition)b.Definition;
+                                var fields = fd is MessageDefinition
+                                    ? fd.Fields.OrderBy((f) => f.ConstantValue).ToList()
+                                    : fd.Fields.ToList();
+                                var names = string.Join(", ", fields.Select((f) => MakeAttrIdent(f.Name)));
+                                builder.CodeBlock($"{from}::{branchName} {{ {names} }} => {to}::{branchName}", _tab,
+                                    () =>
+                                    {
+                                        foreach (var f in fields)
+                                        {
+                                            var fieldName = MakeAttrIdent(f.Name);
+                                            builder.AppendLine(
+                                                $"{fieldName}: {Field(f, fieldName, fd is MessageDefinition)},");
+                                        }
+                                    }, "{", "},");
+                            }
+                        });
+                        break;
+                    default:
+                        throw new InvalidOperationException($"unsupported definition {d.GetType()}");
+                }
+            }
+
+            builder
+                .AppendLine()
+                .CodeBlock($"impl<'raw> super::{ident}<'raw>", _tab, () =>
+                {
+                    builder
+                        .AppendLine("/// Copy all borrowed data so the result can outlive the buffer it was read from.")
+                        .CodeBlock($"pub fn to_owned(&self) -> {ident}", _tab,
+                            () => WriteBody($"super::{ident}", ident, ToOwnedExpr));
+                })
+                .AppendLine()
+                .CodeBlock($"impl {ident}", _tab, () =>
+                {
+                    builder
+                        .AppendLine("/// Borrow the data of this record, the inverse of `to_owned`.")
+                        .CodeBlock($"pub fn as_borrowed(&self) -> super::{ident}<'_>", _tab,
+                            () => WriteBody(ident, $"super::{ident}", AsBorrowedExpr));
+                });
+        }
+
+        /// <summary>
+        /// Expression converting a reference `v` to a borrowed value of the given type to an owned value.
+        /// </summary>
+        private string ToOwnedExpr(string v, TypeBase type, int depth)
+        {
+            var item = $"v{depth}";
+            return type switch
+            {
+                _ when !TypeNeedsLifetime(type) => $"{v}.clone()",
+                ScalarType { BaseType: BaseType.String } => $"::bebop::alloc::string::String::from(*{v})",
+                ArrayType at => CollectExpr($"{v}.iter().map(|{item}| {ToOwnedExpr(item, at.MemberType, depth + 1)})",
+                    at, OwnershipType.Owned),
+                MapType mt =>
+                    $"{v}.iter().map(|(k{depth}, {item})| ({ToOwnedExpr($"k{depth}", mt.KeyType, depth + 1)}, {ToOwnedExpr(item, mt.ValueType, depth + 1)})).collect()",
+                DefinedType => $"{v}.to_owned()",
+                _ => throw new ArgumentOutOfRangeException(type.ToString())
+            };
+        }
+
+        /// <summary>
+        /// Expression collecting the iterator `items` into the array type `at`, which goes through the runtime for
+        /// <c>BebopArray</c> since it may be in another allocator.
+        /// </summary>
+        private string CollectExpr(string items, ArrayType at, OwnershipType ot) =>
+            TypeName(at, ot).StartsWith(ArrayAlias)
+                ? $"::bebop::CollectArray::collect_array({items})"
+                : $"{items}.collect()";
+
+        /// <summary>
+        /// Expression converting a reference `v` to an owned value of the given type to a borrowed value.
+        /// </summary>
+        private string AsBorrowedExpr(string v, TypeBase type, int depth)
+        {
+            var item = $"v{depth}";
+            return type switch
+            {
+                _ when !TypeNeedsLifetime(type) => $"{v}.clone()",
+                ScalarType { BaseType: BaseType.String } => $"{v}.as_str()",
+                ArrayType at when TypeName(at, OwnershipType.Borrowed).StartsWith("::bebop::SliceWrapper") =>
+                    $"::bebop::SliceWrapper::Cooked({v}.as_slice())",
+                ArrayType at => CollectExpr(
+                    $"{v}.iter().map(|{item}| {AsBorrowedExpr(item, at.MemberType, depth + 1)})", at,
+                    OwnershipType.Borrowed),
+                MapType mt =>
+                    $"{v}.iter().map(|(k{depth}, {item})| ({AsBorrowedExpr($"k{depth}", mt.KeyType, depth + 1)}, {AsBorrowedExpr(item, mt.ValueType, depth + 1)})).collect()",
+                DefinedType => $"{v}.as_borrowed()",
+                _ => throw new ArgumentOutOfRangeException(type.ToString())
+            };
+        }
+
+        /// <summary>
+        /// Write `From` conversions between the top level structs and messages whose fields map one-to-one, which are
+        /// enabled by the `conversions` feature of the crate including the generated code. Structs convert when they
+        /// have the same field names and messages when every field of the source is in the target, so an older version
+        /// of a message converts to a newer one with the new fields unset. Field types must be the same or convert
+        /// the same way, arrays and maps are converted item by item. Pairs whose field names match but where a type
+        /// does not convert are warned about.
+        /// </summary>
+        private void WriteConversions(IndentedStringBuilder builder)
+        {
+            var definitions = ConversionDefinitions();
+            foreach (var from in definitions)
+            {
+                foreach (var to in definitions)
+                {
+                    if (from == to)
+                    {
+                        continue;
+                    }
+                    if (!CanConvert(from, to, new HashSet<(string, string)>()))
+                    {
+                        // anything blocked in the owned module is blocked here too, so each pair is warned about once
+                        if (_ownership == OwnershipType.Borrowed)
+                        {
+                            WarnBlockedConversion(from, to);
+                        }
+                        continue;
+                    }
+                    // the owned module re-exports definitions which never borrow along with their conversions
+                    if (_ownership == OwnershipType.Owned && !NeedsLifetime(from) && !NeedsLifetime(to))
+                    {
+                        continue;
+                    }
+
+                    var fromName = MakeDefIdent(from.Name) + (HasLifetime(from) ? "<'raw>" : "");
+                    var toName = MakeDefIdent(to.Name) + (HasLifetime(to) ? "<'raw>" : "");
+                    var lifetime = HasLifetime(from) || HasLifetime(to) ? "<'raw>" : "";
+                    var toFields = to is MessageDefinition
+                        ? to.Fields.OrderBy((f) => f.ConstantValue).ToList()
+                        : to.Fields.ToList();
+                    builder
+                        .AppendLine("#[cfg(feature = \"conversions\")]")
+                        .CodeBlock($"impl{lifetime} ::core::convert::From<{fromName}> for {toName}", _tab, () =>
+                        {
+                            builder.CodeBlock($"fn from(value: {fromName}) -> Self", _tab, () =>
+                            {
+                                builder.CodeBlock("Self", _tab, () =>
+                                {
+                                    foreach (var t in toFields)
+                                    {
+                                        var fieldName = MakeAttrIdent(t.Name);
+                                        var source = from.Fields.FirstOrDefault((f) => f.Name == t.Name);
+                                        var value = source is null
+                                            ? "::core::option::Option::None"
+                                            : to is MessageDefinition
+                                                ? ConvertMessageFieldExpr($"value.{fieldName}", source, t)
+                                                : ConvertExpr($"value.{fieldName}", source.Type, t.Type, 0);
+                                        builder.AppendLine($"{fieldName}: {value},");
+                                    }
+                                });
+                            });
+                        })
+                        .AppendLine();
+                }
+            }
+        }
+
+        /// <summary>
+        /// The definitions which can have conversions between them.
+        /// </summary>
+        private List<FieldsDefinition> ConversionDefinitions() => Schema.Definitions.Values
+            .Where((d) => d is StructDefinition or MessageDefinition && d.Parent is not UnionDefinition)
+            .Cast<FieldsDefinition>()
+            .ToList();
+
+        /// <summary>
+        /// Add to the module documentation when it has conversions, the compiler only sees the schemas it is given so
+        /// definitions in other schemas are not converted to unless they are imported.
+        /// </summary>
+        private void WriteConversionsDocumentation(IndentedStringBuilder builder)
+        {
+            var definitions = ConversionDefinitions();
+            if (!definitions.Any((from) => definitions.Any((to) =>
+                    from != to && CanConvert(from, to, new HashSet<(string, string)>()))))
+            {
+                return;
+            }
+            builder
+                .AppendLine("//!")
+                .AppendLine("//! With the `conversions` feature `From` is implemented between the definitions in this module whose")
+                .AppendLine("//! fields map one-to-one. Only definitions in the schemas compiled together are considered, so a")
+                .AppendLine("//! version of a definition in another schema is not converted to unless that schema is imported.");
+        }
+
+        /// <summary>
+        /// Warn when the fields of `from` match the fields of `to` by name, so they are likely versions of the same
+        /// definition, but the type of one of them does not convert.
+        /// </summary>
+        private void WarnBlockedConversion(FieldsDefinition from, FieldsDefinition to)
+        {
+            if (from.GetType() != to.GetType() || (from is StructDefinition && from.Fields.Count != to.Fields.Count) ||
+                !from.Fields.All((f) => to.Fields.Any((t) => t.Name == f.Name)))
+            {
+                return;
+            }
+            foreach (var field in from.Fields)
+            {
+                var target = to.Fields.First((t) => t.Name == field.Name);
+                // a field which blocks the conversion still fails on its own since fewer pairs are assumed to convert
+                if (!CanConvertType(field.Type, target.Type, new HashSet<(string, string)> { (from.Name, to.Name) }))
+                {
+                    Schema.Warnings.Add(new BlockedConversionWarning(from, to, field));
+                    return;
+                }
+            }
+        }
+
+        /// <summary>
+        /// Whether all fields of `from` map to fields of `to`, pairs in `assumed` are already being checked so they are
+        /// treated as convertible to allow recursive definitions.
+        /// </summary>
+        private bool CanConvert(FieldsDefinition from, FieldsDefinition to, HashSet<(string, string)> assumed)
+        {
+            if (from.GetType() != to.GetType() || (from is StructDefinition && from.Fields.Count != to.Fields.Count))
+            {
+                return false;
+            }
+            if (!assumed.Add((from.Name, to.Name)))
+            {
+                return true;
+            }
+            return from.Fields.All((f) =>
+                to.Fields.FirstOrDefault((t) => t.Name == f.Name) is { } t && CanConvertType(f.Type, t.Type, assumed));
+        }
+
+        private bool CanConvertType(TypeBase from, TypeBase to, HashSet<(string, string)> assumed)
+        {
+            if (TypeName(from) == TypeName(to))
+            {
+                return true;
+            }
+            return (from, to) switch
+            {
+                (DefinedType fd, DefinedType td) =>
+                    Schema.Definitions[fd.Name] is FieldsDefinition fdd and (StructDefinition or MessageDefinition) &&
+                    Schema.Definitions[td.Name] is FieldsDefinition tdd and (StructDefinition or MessageDefinition) &&
+                    fdd.Parent is not UnionDefinition && tdd.Parent is not UnionDefinition &&
+                    CanConvert(fdd, tdd, assumed),
+                // borrowed arrays of fixed size structs point into the buffer and can't be converted
+                (ArrayType fa, ArrayType ta) => !TypeName(ta).StartsWith("::bebop::SliceWrapper") &&
+                    CanConvertType(fa.MemberType, ta.MemberType, assumed),
+                (MapType fm, MapType tm) => TypeName(fm.KeyType) == TypeName(tm.KeyType) &&
+                    CanConvertType(fm.ValueType, tm.ValueType, assumed),
+                _ => false
+            };
+        }
+
+        /// <summary>
+        /// Expression converting the value `v` of the message field `from` to the message field `to`, either of which
+        /// may be boxed.
+        /// </summary>
+        private string ConvertMessageFieldExpr(string v, Field from, Field to)
+        {
+            var fromBoxed = _boxedFields.Contains(from);
+            if (TypeName(from.Type) == TypeName(to.Type) && fromBoxed == _boxedFields.Contains(to))
+            {
+                return v;
+            }
+            var item = TypeName(from.Type) == TypeName(to.Type)
+                ? fromBoxed ? "*v0" : "v0"
+                : ConvertExpr(fromBoxed ? "(*v0)" : "v0", from.Type, to.Type, 1);
+            return $"{v}.map(|v0| {BoxExpr(to, item)})";
+        }
+
+        /// <summary>
+        /// Box the expression `v` if it is the value of a boxed message field.
+        /// </summary>
+        private string BoxExpr(Field f, string v) =>
+            _boxedFields.Contains(f) ? $"::bebop::alloc::boxed::Box::new({v})" : v;
+
+        /// <summary>
+        /// Expression converting a value `v` of the type `from` to the type `to`, see `CanConvertType`.
+        /// </summary>
+        private string ConvertExpr(string v, TypeBase from, TypeBase to, int depth)
+        {
+            var item = $"v{depth}";
+            return (from, to) switch
+            {
+                _ when TypeName(from) == TypeName(to) => v,
+                (ArrayType fa, ArrayType ta) => CollectExpr(
+                    $"{v}.into_iter().map(|{item}| {ConvertExpr(item, fa.MemberType, ta.MemberType, depth + 1)})", ta,
+                    _ownership),
+                (MapType fm, MapType tm) =>
+                    $"{v}.into_iter().map(|(k{depth}, {item})| (k{depth}, {ConvertExpr(item, fm.ValueType, tm.ValueType, depth + 1)})).collect()",
+                (DefinedType, DefinedType) => $"{v}.into()",
+                _ => throw new ArgumentOutOfRangeException(from.ToString())
+            };
+        }
+
+        /// <summary>
+        /// Write a builder with chainable setters which is enabled by the `builder` feature of the crate including
+        /// the generated code. Every struct field is required so building a struct fails if any were not set, message
+        /// fields are all optional so building a message cannot fail.
+        /// </summary>
+        private void WriteBuilder(IndentedStringBuilder builder, FieldsDefinition d, bool needsLifetime)
+        {
+            var ident = MakeDefIdent(d.Name);
+            var lifetime = needsLifetime ? "<'raw>" : "";
+            var name = ident + lifetime;
+            var builderName = $"{ident}Builder{lifetime}";
+            var isMessage = d is MessageDefinition;
+            var fields = isMessage ? d.Fields.OrderBy((f) => f.ConstantValue).ToList() : d.Fields.ToList();
+
+            builder
+                .AppendLine($"/// Builder for [`{ident}`].")
+                .AppendLine("#[cfg(feature = \"builder\")]")
+                .AppendLine("#[derive(Clone, Debug, Default)]");
+            if (isMessage)
+            {
+                builder.AppendLine($"pub struct {builderName}({name});");
+            }
+            else
+            {
+                builder.CodeBlock($"pub struct {builderName}", _tab, () =>
+                {
+                    foreach (var f in fields)
+                    {
+                        builder.AppendLine($"{MakeAttrIdent(f.Name)}: ::core::option::Option<{TypeName(f.Type)}>,");
+                    }
+                });
+            }
+            builder.AppendLine();
+
+            builder
+                .AppendLine("#[cfg(feature = \"builder\")]")
+                .CodeBlock(needsLifetime ? $"impl<'raw> {builderName}" : $"impl {builderName}", _tab, () =>
+                {
+                    foreach (var f in fields)
+                    {
+                        var attr = MakeAttrIdent(f.Name);
+                        var target = isMessage ? $"self.0.{attr}" : $"self.{attr}";
+                        // enums are converted so setting a member works the same with `lenient_enums`
+                        var isEnum = f.Type is DefinedType dt &&
+                            Schema.Definitions[dt.Name] is EnumDefinition { IsBitFlags: false };
+                        var type = isEnum ? $"impl ::core::convert::Into<{TypeName(f.Type)}>" : TypeName(f.Type);
+                        var value = isEnum ? $"{attr}.into()" : isMessage ? BoxExpr(f, attr) : attr;
+                        WriteDocumentation(builder, f.Documentation);
+                        builder.CodeBlock($"pub fn {attr}(mut self, {attr}: {type}) -> Self", _tab,
+                            () =>
+                            {
+                                builder
+                                    .AppendLine($"{target} = Some({value});")
+                                    .AppendLine("self");
+                            }).AppendLine();
+                    }
+
+                    if (isMessage)
+                    {
+                        builder.CodeBlock($"pub fn build(self) -> {name}", _tab, () =>
+                        {
+                            builder.AppendLine("self.0");
+                        });
+                    }
+                    else
+                    {
+                        builder.CodeBlock(
+                            $"pub fn build(self) -> ::core::result::Result<{name}, ::bebop::MissingFieldError>", _tab,
+                            () =>
+                            {
+                                builder.CodeBlock($"Ok({ident}", _tab, () =>
+                                {
+                                    foreach (var f in fields)
+                                    {
+                                        var attr = MakeAttrIdent(f.Name);
+                                        builder.AppendLine(
+                                            $"{attr}: self.{attr}.ok_or(::bebop::MissingFieldError(\"{attr}\"))?,");
+                                    }
+                                }, "{", "})");
+                            });
+                    }
+                }).AppendLine();
+
+            builder
+                .AppendLine("#[cfg(feature = \"builder\")]")
+                .CodeBlock(needsLifetime ? $"impl<'raw> {name}" : $"impl {name}", _tab, () =>
+                {
+                    builder.CodeBlock($"pub fn builder() -> {builderName}", _tab, () =>
+                    {
+                        builder.AppendLine("::core::default::Default::default()");
+                    });
+                }).AppendLine();
+        }
+
+        /// <summary>
+        /// Write an implementation of `DebugJson` which renders a definition as JSON for debugging. This is enabled by
+        /// the `debug_json` feature of the crate including the generated code, which should also enable
+        /// `bebop/debug_json`.
+        /// </summary>
+        private void WriteDebugJson(IndentedStringBuilder builder, Definition d)
+        {
+            const string debugJson = "::bebop::debug_json";
+            var scopeName = MakeDefIdent(d.Name);
+            var name = scopeName + (HasLifetime(d) ? "<'raw>" : "");
+
+            builder
+                .AppendLine("#[cfg(feature = \"debug_json\")]")
+                .CodeBlock($"impl{(HasLifetime(d) ? "<'raw>" : "")} {debugJson}::DebugJson for {name}", _tab, () =>
+                {
+                    builder.CodeBlock($"fn to_debug_json(&self) -> {debugJson}::Value", _tab, () =>
+                    {
+                        switch (d)
+                        {
+                            case EnumDefinition { IsBitFlags: true }:
+                                builder.AppendLine($"{debugJson}::Value::from(self.bits())");
+                                break;
+                            case EnumDefinition ed:
+                                builder.CodeBlock("match self", _tab, () =>
+                                {
+                                    foreach (var m in ed.Members)
+                                    {
+                                        builder.AppendLine($"{scopeName}::{MakeEnumVariantIdent(m.Name)} => \"{m.Name}\".into(),");
+                                    }
+                                });
+                                break;
+                            case StructDefinition sd:
+                                // fields of packed structs can't be borrowed so copy them
+                                var copy = sd.IsFixedSize(Schema);
+                                WriteDebugJsonFields(builder, sd,
+                                    (f) => copy ? $"&{{ self.{MakeAttrIdent(f.Name)} }}" : $"&self.{MakeAttrIdent(f.Name)}");
+                                break;
+                            case MessageDefinition md:
+                                WriteDebugJsonFields(builder, md, (f) => $"&self.{MakeAttrIdent(f.Name)}");
+                                break;
+                            case UnionDefinition ud:
+                                builder.CodeBlock("match self", _tab, () =>
+                                {
+                                    builder.AppendLine(
+                                        $"{scopeName}::Unknown(d) => {debugJson}::union(*d, {debugJson}::Value::Null),");
+                                    foreach (var b in ud.Branches.OrderBy((b) => b.Discriminator))
+                                    {
+                                        var fd = (FieldsDefinition)b.Definition;
+                                        builder.CodeBlock($"{scopeName}::{MakeEnumVariantIdent(b.Definition.Name)}", _tab, () =>
+                                        {
+                                            foreach (var f in fd.Fields)
+                                            {
+                                                var fieldName = MakeAttrIdent(f.Name);
+                                                builder.AppendLine($"{fieldName}: ref _{fieldName},");
+                                            }
+                                        });
+                                        builder.CodeBlock("=>", _tab, () =>
+                                        {
+                                            builder.CodeBlock($"{debugJson}::union({b.Discriminator},", _tab, () =>
+                                            {
+                                                WriteDebugJsonFields(builder, fd, (f) => $"_{MakeAttrIdent(f.Name)}");
+                                            }, "{", "})");
+                                        });
+                                    }
+                                });
+                                break;
+                            default:
+                                throw new InvalidOperationException($"unsupported definition {d.GetType()}");
+                        }
+                    });
+                }).AppendLine();
+        }
+
+        /// <summary>
+        /// Write an expression which makes a JSON object of the fields of a definition keyed by their schema names.
+        /// Unset message fields are left out.
+        /// </summary>
+        private static void WriteDebugJsonFields(IndentedStringBuilder builder, FieldsDefinition d,
+            Func<Field, string> access)
+        {
+            const string debugJson = "::bebop::debug_json";
+            builder.AppendLine($"let mut map = {debugJson}::Map::new();");
+            foreach (var f in d.Fields)
+            {
+                var insert = $"map.insert(\"{f.Name}\".into(), {debugJson}::to_value(v));";
+                if (d is MessageDefinition)
+                {
+                    builder.AppendLine($"if let Some(v) = {access(f)} {{ {insert} }}");
+                }
+                else
+                {
+                    builder.AppendLine($"let v = {access(f)};").AppendLine(insert);
+                }
+            }
+
+            builder.AppendLine($"{debugJson}::Value::Object(map)");
+        }
+
+        /// <summary>
+        /// Write an implementation of `arbitrary::Arbitrary` so values can be made from fuzzer or property test input.
+        /// This is enabled by the `arbitrary` feature of the crate including the generated code, which should also
+        /// enable `bebop/arbitrary`. Only the enum members and union branches in the schema are made since unknown ones
+        /// could not be written.
+        /// </summary>
+        private void WriteArbitrary(IndentedStringBuilder builder, Definition d)
+        {
+            const string arbitrary = "::bebop::arbitrary";
+            var scopeName = MakeDefIdent(d.Name);
+            var name = scopeName + (HasLifetime(d) ? "<'raw>" : "");
+
+            builder
+                .AppendLine("#[cfg(feature = \"arbitrary\")]")
+                .CodeBlock($"impl<'raw> {arbitrary}::Arbitrary<'raw> for {name}", _tab, () =>
+                {
+                    builder.CodeBlock(
+                        $"fn arbitrary(u: &mut {arbitrary}::Unstructured<'raw>) -> {arbitrary}::Result<Self>", _tab,
+                        () =>
+                        {
+                            switch (d)
+                            {
+                                case EnumDefinition { IsBitFlags: true }:
+                                    builder.AppendLine("Ok(Self::from_bits_truncate(u.arbitrary()?))");
+                                    break;
+                                case EnumDefinition ed:
+                                    var members = string.Join(", ",
+                                        ed.Members.Select((m) => $"{scopeName}::{MakeEnumVariantIdent(m.Name)}"));
+                                    builder.AppendLine($"Ok(*u.choose(&[{members}])?)");
+                                    break;
+                                case StructDefinition or MessageDefinition:
+                                    builder.CodeBlock("Ok(Self", _tab,
+                                        () => WriteArbitraryFields(builder, (FieldsDefinition)d), "{", "})");
+                                    break;
+                                case UnionDefinition ud:
+                                    var branches = ud.Branches.OrderBy((b) => b.Discriminator).ToList();
+                                    builder.CodeBlock($"Ok(match u.choose_index({branches.Count})?", _tab, () =>
+                                    {
+                                        for (var i = 0; i < branches.Count; i++)
+                                        {
+                                            var branchName = MakeEnumVariantIdent(branches[i].Definition.Name);
+                                            builder.CodeBlock($"{i} => {scopeName}::{branchName}", _tab,
+                                                () => WriteArbitraryFields(builder,
+                                                    (FieldsDefinition)branches[i].Definition), "{", "},");
+                                        }
+
+                                        builder.AppendLine("_ => unreachable!(),");
+                                    }, "{", "})");
+                                    break;
+                                default:
+                                    throw new InvalidOperationException($"unsupported definition {d.GetType()}");
+                            }
+                        });
+                }).AppendLine();
+        }
+
+        private static void WriteArbitraryFields(IndentedStringBuilder builder, FieldsDefinition d)
+        {
+            foreach (var f in d.Fields)
+            {
+                builder.AppendLine($"{MakeAttrIdent(f.Name)}: u.arbitrary()?,");
+            }
+        }
+
+        /// <summary>
+        /// Write `#[wasm_bindgen]` functions which encode and decode the owned version of a record from a JS object.
+        /// These are enabled by the `wasm` feature of the crate including the generated code, which should also enable
+        /// `owned`, `serde`, and `bebop/wasm`.
+        /// </summary>
+        private static void WriteWasmFunctions(IndentedStringBuilder builder, TopLevelDefinition d)
+        {
+            const string wasmBindgen = "::bebop::wasm::wasm_bindgen";
+            var ident = MakeDefIdent(d.Name);
+            var fnName = d.Name.ToSnakeCase();
+            string Attr(string jsName) =>
+                $"#[{wasmBindgen}::prelude::wasm_bindgen(wasm_bindgen = {wasmBindgen}, js_name = \"{jsName}\")]";
+
+            builder
+                .AppendLine($"/// Encode a JS object as [`{ident}`].")
+                .AppendLine("#[cfg(feature = \"wasm\")]")
+                .AppendLine(Attr($"encode{ident}"))
+                .CodeBlock(
+                    $"pub fn encode_{fnName}(value: {wasmBindgen}::JsValue) -> ::core::result::Result<::bebop::alloc::boxed::Box<[u8]>, {wasmBindgen}::JsValue>",
+                    _tab, () =>
+                    {
+                        builder.AppendLine($"::bebop::wasm::encode::<owned::{ident}>(value)");
+                    })
+                .AppendLine()
+                .AppendLine($"/// Decode [`{ident}`] as a JS object.")
+                .AppendLine("#[cfg(feature = \"wasm\")]")
+                .AppendLine(Attr($"decode{ident}"))
+                .CodeBlock(
+                    $"pub fn decode_{fnName}(bytes: &[u8]) -> ::core::result::Result<{wasmBindgen}::JsValue, {wasmBindgen}::JsValue>",
+                    _tab, () =>
+                    {
+                        builder.AppendLine($"::bebop::wasm::decode::<owned::{ident}>(bytes)");
+                    })
+                .AppendLine();
+        }
+
+        /// <summary>
+        /// Write a constant with a hash of the wire layout of a record for use with `encode_checked` and
+        /// `decode_checked`. Only the types and order or indices of fields are included, so renaming does not change
+        /// the hash but any other change to the layout does, even compatible ones like adding a message field.
+        /// </summary>
+        private void WriteWireHash(IndentedStringBuilder builder, TopLevelDefinition d)
+        {
+            // FNV-1a since it is simple to reproduce and stable across runs unlike string.GetHashCode
+            var hash = 0xcbf29ce484222325UL;
+            foreach (var b in Encoding.UTF8.GetBytes(WireLayout(d, new HashSet<string>())))
+            {
+                hash = unchecked((hash ^ b) * 0x100000001b3UL);
+            }
+
+            builder
+                .AppendLine($"/// Hash of the wire layout of [`{MakeDefIdent(d.Name)}`].")
+                .AppendLine($"pub const {MakeConstIdent(d.Name)}_WIRE_HASH: u64 = 0x{hash:x16};")
+                .AppendLine();
+        }
+
+        /// <summary>
+        /// Describe the wire layout of a definition, `path` is the definitions it is nested within so recursive
+        /// definitions end.
+        /// </summary>
+        private string WireLayout(Definition d, HashSet<string> path)
+        {
+            if (!path.Add(d.Name)) { return $"@{d.Name}"; }
+
+            var layout = d switch
+            {
+                EnumDefinition ed => $"enum({WireLayout(ed.ScalarType, path)})",
+                StructDefinition sd => $"struct{{{string.Join(",", sd.Fields.Select((f) => WireLayout(f.Type, path)))}}}",
+                MessageDefinition md =>
+                    $"message{{{string.Join(",", md.Fields.OrderBy((f) => f.ConstantValue).Select((f) => $"{f.ConstantValue}:{WireLayout(f.Type, path)}"))}}}",
+                UnionDefinition ud =>
+                    $"union{{{string.Join(",", ud.Branches.OrderBy((b) => b.Discriminator).Select((b) => $"{b.Discriminator}:{WireLayout(b.Definition, path)}"))}}}",
+                _ => throw new ArgumentOutOfRangeException(d.Name)
+            };
+            path.Remove(d.Name);
+            return layout;
+        }
+
+        private string WireLayout(TypeBase type, HashSet<string> path) =>
+            type switch
+            {
+                ScalarType st => st.BaseType.ToString(),
+                ArrayType at => $"[{WireLayout(at.MemberType, path)}]",
+                MapType mt => $"map[{WireLayout(mt.KeyType, path)},{WireLayout(mt.ValueType, path)}]",
+                DefinedType dt => WireLayout(Schema.Definitions[dt.Name], path),
+                _ => throw new ArgumentOutOfRangeException(type.ToString())
+            };
+
+        #endregion
+
+        #region component_writers
+
+        /// <summary>
+        /// Declare the types which depend on features of the crate including the generated code.
+        ///
+        /// Arrays which are not borrowed are a <c>Vec</c> unless the <c>smallvec</c> feature is enabled, then items are
+        /// kept inline until there are more than <c>BEBOP_SMALL_ARRAY_CAPACITY</c> of them when the crate is built.
+        /// With the nightly only <c>allocator_api</c> feature they are a <c>Vec</c> in <c>crate::BebopAllocator</c>, which
+        /// the crate declares as an allocator implementing <c>Default</c> such as a handle to a per-request arena. The
+        /// serde and arbitrary impls only exist for the global allocator so those features can not be combined with it,
+        /// and owned strings are still a <c>String</c>.
+        ///
+        /// Enum fields fail to decode values which are not members unless the <c>lenient_enums</c> feature is enabled,
+        /// then they are a <c>MaybeUnknown</c> of the enum so newer schemas can add members without older readers
+        /// failing. The enums themselves are the same either way.
+        /// </summary>
+        private static void WriteTypeAliases(IndentedStringBuilder builder)
+        {
+            builder
+                .AppendLine("#[cfg(not(feature = \"lenient_enums\"))]")
+                .AppendLine($"type {EnumAlias}<E> = E;")
+                .AppendLine("#[cfg(feature = \"lenient_enums\")]")
+                .AppendLine($"type {EnumAlias}<E> = ::bebop::MaybeUnknown<E>;")
+                .AppendLine("#[cfg(not(any(feature = \"smallvec\", feature = \"allocator_api\")))]")
+                .AppendLine($"type {ArrayAlias}<T> = ::bebop::alloc::vec::Vec<T>;")
+                .AppendLine("#[cfg(feature = \"allocator_api\")]")
+                .AppendLine($"type {ArrayAlias}<T> = ::bebop::alloc::vec::Vec<T, crate::BebopAllocator>;")
+                .AppendLine("#[cfg(all(feature = \"smallvec\", not(feature = \"allocator_api\")))]")
+                .AppendLine($"type {ArrayAlias}<T> = ::bebop::smallvec::SmallVec<")
+                .AppendLine("    [T; ::bebop::small_array_capacity(::core::option_env!(\"BEBOP_SMALL_ARRAY_CAPACITY\"))],")
+                .AppendLine(">;")
+                .AppendLine("#[cfg(all(feature = \"allocator_api\", any(feature = \"smallvec\", feature = \"serde\", feature = \"arbitrary\")))]")
+                .AppendLine("compile_error!(\"`allocator_api` can not be combined with `smallvec`, `serde`, or `arbitrary`\");")
+                .AppendLine();
+        }
+
+        private static void WriteDocumentation(IndentedStringBuilder builder, string documentation)
+        {
+            if (string.IsNullOrEmpty(documentation)) { return; }
+
+            foreach (var line in documentation.GetLines())
+            {
+                // TODO: make the docs more friendly to rustdoc by formatting as markdown
+                builder.AppendLine(string.IsNullOrWhiteSpace(line) ? "///" : $"/// {line}");
+            }
+        }
+
+        private static void WriteDeprecation(IndentedStringBuilder builder, BaseAttribute? attr)
+        {
+            if (attr is null) { return; }
+
+            builder.Append("#[deprecated");
+            if (!string.IsNullOrEmpty(attr.Value))
+            {
+                builder.AppendMid($"(note = {MakeStringLiteral(attr.Value)})");
+            }
+
+            builder.AppendEnd("]");
+        }
+
+        /// <summary>
+        /// Write the serde derives which are enabled by the `serde` feature of the crate including the generated
+        /// code. Borrowed definitions can only be serialized since most formats cannot be read without copying.
+        /// </summary>
+        private static void WriteSerdeDerive(IndentedStringBuilder builder, bool deserialize)
+        {
+            var traits = deserialize
+                ? "::bebop::serde::Serialize, ::bebop::serde::Deserialize"
+                : "::bebop::serde::Serialize";
+            builder.AppendLine($"#[cfg_attr(feature = \"serde\", derive({traits}), serde(crate = \"::bebop::serde\"))]");
+        }
+
+        /// <summary>
+        /// Write the attribute which marks enums and unions as non-exhaustive when the `non_exhaustive` feature of
+        /// the crate including the generated code is enabled, so adding to the schema is not a breaking change for
+        /// crates which depend on it.
+        /// </summary>
+        private static void WriteNonExhaustive(IndentedStringBuilder builder)
+        {
+            builder.AppendLine("#[cfg_attr(feature = \"non_exhaustive\", non_exhaustive)]");
+        }
+
+        /// <summary>
+        /// Write the serde attribute which represents a byte array field as a base64 string.
+        /// </summary>
+        private void WriteSerdeFieldAttr(IndentedStringBuilder builder, Field f, bool optional)
+        {
+            if (f.Type is not ArrayType at || !at.IsBytes()) { return; }
+
+            var module = optional ? "::bebop::base64::option" : "::bebop::base64";
+            var attr = _ownership == OwnershipType.Borrowed
+                ? $"serialize_with = \"{module}::serialize\""
+                : (optional ? "default, " : "") + $"with = \"{module}\"";
+            builder.AppendLine($"#[cfg_attr(feature = \"serde\", serde({attr}))]");
+        }
+
+        /// <summary>
+        /// Find the message fields whose type contains the message they are in, through struct fields, message fields,
+        /// or union branches. These are boxed since the message would otherwise contain itself.
+        /// </summary>
+        private void FindBoxedFields()
+        {
+            IEnumerable<Definition> Contained(Definition definition) => definition switch
+            {
+                FieldsDefinition fd => fd.Fields
+                    .Select((f) => f.Type)
+                    .OfType<DefinedType>()
+                    .Select((dt) => Schema.Definitions[dt.Name]),
+                UnionDefinition ud => ud.Branches.Select((b) => (Definition)b.Definition),
+                _ => Enumerable.Empty<Definition>()
+            };
+
+            bool Reaches(Definition from, Definition target)
+            {
+                var seen = new HashSet<string>();
+                var pending = new Stack<Definition>();
+                pending.Push(from);
+                while (pending.Count > 0)
+                {
+                    var definition = pending.Pop();
+                    if (definition.Name == target.Name) { return true; }
+                    if (!seen.Add(definition.Name)) { continue; }
+                    foreach (var contained in Contained(definition)) { pending.Push(contained); }
+                }
+                return false;
+            }
+
+            foreach (var md in Schema.Definitions.Values.OfType<MessageDefinition>())
+            {
+                foreach (var f in md.Fields)
+                {
+                    if (f.Type is DefinedType dt && Reaches(Schema.Definitions[dt.Name], md)) { _boxedFields.Add(f); }
+                }
+            }
+        }
+
+        /// <summary>
+        /// Make sure no definition contains itself by value, rust would reject it as a type with infinite size. Arrays
+        /// and maps are allocated separately and message fields which lead back to their message are boxed, so only
+        /// cycles of struct fields and union branches are left.
+        /// </summary>
+        private void CheckForInfiniteSize()
+        {
+            var done = new HashSet<string>();
+            var path = new List<Definition>();
+
+            void VisitDefinition(Definition definition)
+            {
+                if (done.Contains(definition.Name)) { return; }
+
+                var start = path.FindIndex((d) => d.Name == definition.Name);
+                if (start >= 0)
+                {
+                    var cycle = path.Skip(start).Append(definition).Select((d) => d.Name);
+                    throw new CyclicDefinitionsException(path[start], cycle);
+                }
+
+                path.Add(definition);
+                switch (definition)
+                {
+                    case StructDefinition sd:
+                        foreach (var f in sd.Fields)
+                        {
+                            if (f.Type is DefinedType dt) { VisitDefinition(Schema.Definitions[dt.Name]); }
+                        }
+                        break;
+                    case UnionDefinition ud:
+                        foreach (var b in ud.Branches) { VisitDefinition(b.Definition); }
+                        break;
+                }
+                path.RemoveAt(path.Count - 1);
+                done.Add(definition.Name);
+            }
+
+            foreach (var definition in Schema.Definitions.Values) { VisitDefinition(definition); }
+        }
+
+        /// <summary>
+        /// The comparison traits beyond <c>PartialEq</c> which can be derived for a definition with the current
+        /// ownership type. Floats prevent deriving <c>Eq</c>, <c>Hash</c>, and <c>Ord</c> while maps and slice
+        /// wrappers also prevent deriving <c>PartialOrd</c>.
+        ///
+        /// A <c>derive</c> attribute adds or removes traits. The ones removed from a definition are not derived for
+        /// definitions containing it either unless they add them back, e.g. when the trait is implemented by hand.
+        /// <c>Ord</c> needs <c>Eq</c> and <c>PartialOrd</c> so adding it adds them and removing either removes it.
+        /// </summary>
+        private string ComparisonDerives(Definition d)
+        {
+            var visited = new HashSet<string>();
+            var excluded = new HashSet<string>();
+            var ordered = true;
+            var total = true;
+
+            void VisitDefinition(Definition definition)
+            {
+                if (!visited.Add(definition.Name)) { return; }
+                if (definition is TopLevelDefinition { DeriveAttribute: { } derive })
+                {
+                    excluded.UnionWith(derive.Excluded);
+                }
+
+                switch (definition)
+                {
+                    case FieldsDefinition fd:
+                        foreach (var f in fd.Fields) { VisitType(f.Type); }
+                        break;
+                    case UnionDefinition ud:
+                        foreach (var b in ud.Branches) { VisitDefinition(b.Definition); }
+                        break;
+                }
+            }
+
+            void VisitType(TypeBase type)
+            {
+                switch (type)
+                {
+                    case ScalarType { BaseType: BaseType.Float32 or BaseType.Float64 }:
+                        total = false;
+                        break;
+                    case MapType:
+                        ordered = total = false;
+                        break;
+                    case ArrayType at when TypeName(at).StartsWith("::bebop::SliceWrapper"):
+                        ordered = total = false;
+                        break;
+                    case ArrayType at:
+                        VisitType(at.MemberType);
+                        break;
+                    case DefinedType dt:
+                        VisitDefinition(Schema.Definitions[dt.Name]);
+                        break;
+                }
+            }
+
+            VisitDefinition(d);
+            var derives = new HashSet<string>();
+            if (total) { derives.UnionWith(ComparisonTraits); }
+            else if (ordered) { derives.Add("PartialOrd"); }
+            derives.ExceptWith(excluded);
+            if (d is TopLevelDefinition { DeriveAttribute: { } own })
+            {
+                derives.UnionWith(own.Included);
+                if (own.Included.Contains("Ord")) { derives.UnionWith(new[] { "Eq", "PartialOrd" }); }
+                derives.ExceptWith(own.Excluded);
+            }
+            if (!derives.Contains("Eq") || !derives.Contains("PartialOrd")) { derives.Remove("Ord"); }
+            return string.Concat(ComparisonTraits.Where(derives.Contains).Select((t) => $", {t}"));
+        }
+
+        /// <summary>
+        /// Warn about traits in <c>derive</c> attributes which are not comparison traits, the others are always
+        /// derived or never derived since the generated code depends on it.
+        /// </summary>
+        private void CheckDeriveAttributes()
+        {
+            foreach (var td in Schema.Definitions.Values.OfType<TopLevelDefinition>())
+            {
+                if (td.DeriveAttribute is null) { continue; }
+                foreach (var trait in td.DeriveAttribute.Included.Concat(td.DeriveAttribute.Excluded))
+                {
+                    if (!ComparisonTraits.Contains(trait)) { Schema.Warnings.Add(new IgnoredDeriveWarning(td, trait)); }
+                }
+            }
+        }
+
+        /// <summary>
+        /// Write the `AsyncSubRecord` impl used to stream a definition to an async writer. Without `writeBody` the
+        /// default is used, which serializes the whole value at once.
+        /// </summary>
+        private static void WriteAsyncSubRecord(IndentedStringBuilder builder, string name, Action? writeBody = null)
+        {
+            builder.AppendLine("#[cfg(feature = \"tokio\")]");
+            if (writeBody is null)
+            {
+                builder.AppendLine($"impl<'raw> ::bebop::AsyncSubRecord<'raw> for {name} {{}}").AppendLine();
+                return;
+            }
+
+            builder.CodeBlock($"impl<'raw> ::bebop::AsyncSubRecord<'raw> for {name}", _tab, () =>
+            {
+                builder.CodeBlock(
+                    "fn _serialize_chained_async<'a, W: ::bebop::AsyncWrite + ?Sized>(&'a self, dest: &'a mut ::bebop::AsyncSink<'_, W>) -> ::bebop::SerializeFuture<'a>",
+                    _tab,
+                    () => builder.CodeBlock("::bebop::alloc::boxed::Box::pin(async move", _tab, writeBody, "{", "})"));
+            }).AppendLine();
+        }
+
+        /// <summary>
+        /// Serialize `value` to `dest`, awaiting it within `_serialize_chained_async`.
+        /// </summary>
+        private static string SerializeCall(string value, bool isAsync) => isAsync
+            ? $"{value}._serialize_chained_async(dest).await?"
+            : $"{value}._serialize_chained(dest)?";
+
+        private static void WriteRecordImpl(IndentedStringBuilder builder, string name, TopLevelDefinition d)
+        {
+            if (d.OpcodeAttribute is { Value: not (null or "") })
+            {
+                builder.CodeBlock($"impl<'raw> ::bebop::Record<'raw> for {name}", _tab, () =>
+                {
+                    builder.AppendLine($"const OPCODE: ::core::option::Option<u32> = Some({d.OpcodeAttribute.Value});");
+                });
+            }
+            else
+            {
+                builder.AppendLine($"impl<'raw> ::bebop::Record<'raw> for {name} {{}}");
+            }
+        }
+
+        #endregion
+
+        #region types_and_identifiers
+
+        private static string MakeConstIdent(string ident)
+        {
+            var reCased = ident.ToSnakeCase().ToUpper();
+            return _reservedWords.Contains(reCased)
+                ? $"_{reCased}"
+                : reCased;
+        }
 
         private string MakeEnumVariantIdent(string ident) => MakeDefIdent(ident);
 
@@ -870,14 +2190,23 @@ namespace Core.Generators.Rust
                 : reCased;
         }
 
+        /// <summary>
+        /// The type of a message field within its `Option`, boxed if it leads back to the message.
+        /// </summary>
+        private string MessageFieldTypeName(Field f) => _boxedFields.Contains(f)
+            ? $"::bebop::alloc::boxed::Box<{TypeName(f.Type)}>"
+            : TypeName(f.Type);
+
         /// <summary>
         /// Generate a Rust type name for the given <see cref="TypeBase"/>.
         /// </summary>
         /// <param name="type">The field type to generate code for.</param>
-        /// <param name="ot">Ownership type, e.g. <c>&'raw str</c> versus <c>String</c>.</param>
+        /// <param name="ownership">Ownership type, e.g. <c>&'raw str</c> versus <c>String</c>. Defaults to the
+        /// ownership definitions are currently being written with.</param>
         /// <returns>The Rust type name.</returns>
-        private string TypeName(in TypeBase type, OwnershipType ot = OwnershipType.Borrowed)
+        private string TypeName(in TypeBase type, OwnershipType? ownership = null)
         {
+            var ot = ownership ?? _ownership;
             switch (type)
             {
                 case ScalarType st:
@@ -897,7 +2226,7 @@ namespace Core.Generators.Rust
                         {
                             OwnershipType.Borrowed => "&'raw str",
                             OwnershipType.Constant => "&str",
-                            OwnershipType.Owned => "String",
+                            OwnershipType.Owned => "::bebop::alloc::string::String",
                             _ => throw new ArgumentOutOfRangeException(nameof(ot))
                         },
                         BaseType.Guid => "::bebop::Guid",
@@ -922,7 +2251,7 @@ namespace Core.Generators.Rust
                             BaseType.Int64 => wrappedSlice,
                             BaseType.Float32 => wrappedSlice,
                             BaseType.Float64 => wrappedSlice,
-                            BaseType.String => $"::std::vec::Vec<{TypeName(at.MemberType)}>",
+                            BaseType.String => $"{ArrayAlias}<{TypeName(at.MemberType, ot)}>",
                             // this one does not care what endian the system is
                             BaseType.Guid => wrappedSlice, //$"&{lifetime} [::bebop::Guid]",
                             BaseType.Date => wrappedSlice,
@@ -937,23 +2266,28 @@ namespace Core.Generators.Rust
                     {
                         // extra special case where we have an array of primitive-like structs
                         var lifetime = ot is OwnershipType.Borrowed ? "'raw" : "'static";
-                        return $"::bebop::SliceWrapper<{lifetime}, {TypeName(at.MemberType)}>";
+                        return $"::bebop::SliceWrapper<{lifetime}, {TypeName(at.MemberType, ot)}>";
+                    }
+                    else if (at.IsBytes())
+                    {
+                        // kept as a `Vec` for the base64 serde helpers, byte arrays are rarely small anyway
+                        return "::bebop::alloc::vec::Vec<u8>";
                     }
                     else
                     {
-                        return $"::std::vec::Vec<{TypeName(at.MemberType)}>";
+                        return $"{ArrayAlias}<{TypeName(at.MemberType, ot)}>";
                     }
                 case MapType mt:
-                    return $"::std::collections::HashMap<{TypeName(mt.KeyType)}, {TypeName(mt.ValueType)}>";
+                    return $"::bebop::HashMap<{TypeName(mt.KeyType, ot)}, {TypeName(mt.ValueType, ot)}>";
+                case DefinedType dt when Schema.Definitions[dt.Name] is EnumDefinition { IsBitFlags: false }:
+                    return $"{EnumAlias}<{dt.Name}>";
                 case DefinedType dt:
                     return ot switch
                     {
                         OwnershipType.Borrowed => NeedsLifetime(Schema.Definitions[dt.Name])
                             ? $"{dt.Name}<'raw>"
                             : dt.Name,
-                        OwnershipType.Owned => NeedsLifetime(Schema.Definitions[dt.Name])
-                            ? $"{dt.Name}<'raw>"
-                            : dt.Name,
+                        OwnershipType.Owned => dt.Name,
                         OwnershipType.Constant => throw new NotSupportedException("Cannot have a const defined type"),
                         _ => throw new ArgumentOutOfRangeException(nameof(ot), ot, null)
                     };
@@ -962,6 +2296,11 @@ namespace Core.Generators.Rust
             throw new InvalidOperationException($"GetTypeName: {type}");
         }
 
+        /// <summary>
+        /// Whether the definition takes a lifetime when written with the current ownership type.
+        /// </summary>
+        private bool HasLifetime(Definition d) => _ownership == OwnershipType.Borrowed && NeedsLifetime(d);
+
         private bool TypeNeedsLifetime(TypeBase type, OwnershipType ot = OwnershipType.Borrowed) =>
             type switch
             {
@@ -978,8 +2317,9 @@ namespace Core.Generators.Rust
                 return _needsLifetime[key];
             }
 
-            _needsLifetime[key] = TypeNeedsLifetime(f.Type, ot);
-            return _needsLifetime[key];
+            var needsLifetime = TypeNeedsLifetime(f.Type, ot);
+            CacheLifetime(key, needsLifetime);
+            return needsLifetime;
         }
 
         private bool NeedsLifetime(Definition d, OwnershipType ot = OwnershipType.Borrowed)
@@ -988,8 +2328,13 @@ namespace Core.Generators.Rust
             {
                 return _needsLifetime[d.Name];
             }
+            // a definition which contains itself only needs a lifetime if something else in it does
+            if (!_checkingLifetime.Add(d.Name))
+            {
+                return false;
+            }
 
-            _needsLifetime[d.Name] = d switch
+            var needsLifetime = d switch
             {
                 ConstDefinition cd => false,
                 EnumDefinition ed => false,
@@ -997,7 +2342,21 @@ namespace Core.Generators.Rust
                 UnionDefinition ud => ud.Branches.Any(b => NeedsLifetime(b.Definition)),
                 _ => throw new ArgumentOutOfRangeException(d.Name)
             };
-            return _needsLifetime[d.Name];
+            _checkingLifetime.Remove(d.Name);
+            CacheLifetime(d.Name, needsLifetime);
+            return needsLifetime;
+        }
+
+        /// <summary>
+        /// Remember whether a definition or field needs a lifetime. A `false` found while a definition it may contain
+        /// is still being checked is not final, the rest of that definition might still need one.
+        /// </summary>
+        private void CacheLifetime(string key, bool needsLifetime)
+        {
+            if (needsLifetime || _checkingLifetime.Count == 0)
+            {
+                _needsLifetime[key] = needsLifetime;
+            }
         }
 
         #endregion
@@ -1012,7 +2371,8 @@ namespace Core.Generators.Rust
                 FloatLiteral { Value: "inf" } => $"{TypeName(literal.Type)}::INFINITY",
                 FloatLiteral { Value: "-inf" } => $"{TypeName(literal.Type)}::NEG_INFINITY",
                 FloatLiteral { Value: "nan" } => $"{TypeName(literal.Type)}::NAN",
-                FloatLiteral fl => fl.Value.Contains('.') ? fl.Value : fl.Value + '.',
+                // `1e3` is already a float literal in rust but `1e3.` is not
+                FloatLiteral fl => fl.Value.Contains('.') || fl.Value.Contains('e') ? fl.Value : fl.Value + '.',
                 StringLiteral sl => MakeStringLiteral(sl.Value),
                 GuidLiteral gl => MakeGuidLiteral(gl.Value),
                 _ => throw new ArgumentOutOfRangeException(literal.ToString()),
@@ -1020,7 +2380,9 @@ namespace Core.Generators.Rust
 
         private static string MakeStringLiteral(string value) =>
             // rust accepts full UTF-8 strings in code AND even supports newlines
-            value.Contains("\"#") ? value.Replace("\\", "\\\\").Replace("\"", "\\\"") : $"r#\"{value}\"#";
+            value.Contains("\"#")
+                ? $"\"{value.Replace("\\", "\\\\").Replace("\"", "\\\"")}\""
+                : $"r#\"{value}\"#";
 
         private static string MakeGuidLiteral(Guid guid)
         {
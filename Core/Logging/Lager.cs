@@ -39,12 +39,16 @@ namespace Core.Logging
             await Console.Error.WriteLineAsync(message);
         }
 
-        private string FormatSpanError(SpanException ex)
+        private string FormatSpanError(SpanException ex) => FormatSpanException(ex, false);
+
+        private string FormatSpanWarning(SpanException ex) => FormatSpanException(ex, true);
+
+        private string FormatSpanException(SpanException ex, bool isWarning)
         {
             return Formatter switch
             {
-                LogFormatter.MSBuild => $"{ex.Span.FileName}({ex.Span.StartColonString(',')}) : error BOP{ex.ErrorCode}: {ex.Message}",
-                LogFormatter.Structured => $"[{DateTime.Now}][Compiler][Error] Issue located in '{ex.Span.FileName}' at {ex.Span.StartColonString()}: {ex.Message}",
+                LogFormatter.MSBuild => $"{ex.Span.FileName}({ex.Span.StartColonString(',')}) : {(isWarning ? "warning" : "error")} BOP{ex.ErrorCode}: {ex.Message}",
+                LogFormatter.Structured => $"[{DateTime.Now}][Compiler][{(isWarning ? "Warning" : "Error")}] Issue located in '{ex.Span.FileName}' at {ex.Span.StartColonString()}: {ex.Message}",
                 LogFormatter.JSON => $@"{{""Message"": ""{ex.Message.EscapeString()}"", ""Span"": {ex.Span.ToJson()}}}",
                 _ => throw new ArgumentOutOfRangeException()
             };
@@ -63,7 +67,19 @@ namespace Core.Logging
         /// </summary>
         public async Task WriteSpanErrors(List<SpanException> exs)
         {
-            var messages = exs.Select(FormatSpanError);
+            await WriteSpanExceptions(exs.Select(FormatSpanError));
+        }
+
+        /// <summary>
+        /// Format and write a list of <see cref="SpanException"/> which do not stop compilation
+        /// </summary>
+        public async Task WriteSpanWarnings(List<SpanException> exs)
+        {
+            await WriteSpanExceptions(exs.Select(FormatSpanWarning));
+        }
+
+        private async Task WriteSpanExceptions(IEnumerable<string> messages)
+        {
             string message;
             if (Formatter == LogFormatter.JSON)
             {
@@ -0,0 +1,49 @@
+﻿using System.Collections.Generic;
+using System.Linq;
+
+namespace Core.Meta.Attributes
+{
+    /// <summary>
+    /// An attribute that adds or removes traits derived for a definition, e.g. <c>[derive("Hash, !Ord")]</c> derives
+    /// <c>Hash</c> and never <c>Ord</c>. It is for the generators which derive traits, others ignore it.
+    /// </summary>
+    public sealed class DeriveAttribute : BaseAttribute
+    {
+        public DeriveAttribute(string value)
+        {
+            Value = value;
+        }
+
+        private IEnumerable<string> Traits => Value.Split(',').Select((t) => t.Trim());
+
+        /// <summary>
+        /// The traits to derive even if the generator would not.
+        /// </summary>
+        public IEnumerable<string> Included => Traits.Where((t) => !t.StartsWith("!"));
+
+        /// <summary>
+        /// The traits to never derive, written with a leading <c>!</c>.
+        /// </summary>
+        public IEnumerable<string> Excluded => Traits.Where((t) => t.StartsWith("!")).Select((t) => t.Substring(1));
+
+        /// <summary>
+        /// Validates that every trait is an identifier which is not both added and removed.
+        /// </summary>
+        public override bool TryValidate(out string message)
+        {
+            if (Traits.FirstOrDefault((t) => t.TrimStart('!').Length == 0 || t.LastIndexOf('!') > 0 ||
+                    !t.TrimStart('!').All((c) => char.IsLetterOrDigit(c) || c == '_')) is { } invalid)
+            {
+                message = $"\"{invalid}\" is not a trait name.";
+                return false;
+            }
+            if (Included.Intersect(Excluded).FirstOrDefault() is { } both)
+            {
+                message = $"\"{both}\" is both derived and not derived.";
+                return false;
+            }
+            message = string.Empty;
+            return true;
+        }
+    }
+}
@@ -161,6 +161,10 @@ namespace Core.Meta
                         errors.Add(new DuplicateOpcodeException(td));
                     }
                 }
+                if (definition is TopLevelDefinition { DeriveAttribute: { } derive } && !derive.TryValidate(out var deriveReason))
+                {
+                    errors.Add(new InvalidDeriveAttributeValueException(definition, deriveReason));
+                }
                 if (definition is FieldsDefinition fd) foreach (var field in fd.Fields)
                 {
                     if (ReservedWords.Identifiers.Contains(field.Name))
@@ -77,6 +77,11 @@ namespace Core.Meta
 
         public BaseAttribute? OpcodeAttribute { get; }
 
+        /// <summary>
+        /// The traits added to or removed from the ones generated code derives for this definition, if any.
+        /// </summary>
+        public DeriveAttribute? DeriveAttribute { get; set; }
+
         /// <summary>
         /// If this definition is part of a union branch, then this is its discriminator in the parent union.
         /// Otherwise, this property is null. (This feels a bit hacky, but oh well.)
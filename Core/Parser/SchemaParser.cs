@@ -312,6 +312,7 @@ namespace Core.Parser
             }
             BaseAttribute? opcodeAttribute = null;
             BaseAttribute? flagsAttribute = null;
+            DeriveAttribute? deriveAttribute = null;
 
             try
             {
@@ -326,6 +327,10 @@ namespace Core.Parser
                     {
                         flagsAttribute = attribute;
                     }
+                    else if (attribute is DeriveAttribute derive)
+                    {
+                        deriveAttribute = derive;
+                    }
                 }
             }
             catch (SpanException e)
@@ -342,9 +347,10 @@ namespace Core.Parser
                 // We probably want to start over if we hit the end of the file.
                 return null;
             }
+            Definition? definition;
             if (Eat(TokenKind.Union))
             {
-                return ParseUnionDefinition(CurrentToken, definitionDocumentation, opcodeAttribute);
+                definition = ParseUnionDefinition(CurrentToken, definitionDocumentation, opcodeAttribute);
             }
             else
             {
@@ -361,8 +367,18 @@ namespace Core.Parser
                     // Uh oh we skipped ahead due to a missing identifier, get outta there
                     return null;
                 }
-                return ParseNonUnionDefinition(CurrentToken, kind, isReadOnly, definitionDocumentation, opcodeAttribute, flagsAttribute);
+                definition = ParseNonUnionDefinition(CurrentToken, kind, isReadOnly, definitionDocumentation, opcodeAttribute, flagsAttribute);
+            }
+
+            if (deriveAttribute != null && definition is TopLevelDefinition td)
+            {
+                td.DeriveAttribute = deriveAttribute;
+            }
+            else if (deriveAttribute != null && definition is not null)
+            {
+                _errors.Add(new InvalidDeriveAttributeUsageException(definition));
             }
+            return definition;
         }
 
         private ConstDefinition ParseConstDefinition(string definitionDocumentation)
@@ -486,6 +502,7 @@ namespace Core.Parser
                     "deprecated" => new DeprecatedAttribute(value),
                     "opcode" => new OpcodeAttribute(value, isNumber),
                     "flags" => new FlagsAttribute(),
+                    "derive" => new DeriveAttribute(value),
                     _ => throw new UnknownAttributeException(kindToken),
                 };
             }
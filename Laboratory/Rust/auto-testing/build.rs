@@ -11,5 +11,5 @@ fn main() {
         bebop::COMPILER_PATH = Some(PathBuf::from(BEBOP_BIN));
         bebop::GENERATED_PREFIX = Some("_".into());
     }
-    bebop::build_schema_dir("../../Schemas", "src/generated");
+    bebop::build_schema_dir_flat("../../Schemas", "src/generated");
 }
@@ -18,7 +18,7 @@
 
 use bebop::FixedSized as _;
 use core::convert::TryInto as _;
-use std::io::Write as _;
+use bebop::Write as _;
 
 #[repr(u32)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -40,9 +40,7 @@ impl ::core::convert::TryFrom<u32> for Instrument {
             2 => Ok(Instrument::Clarinet),
             3 => Ok(Instrument::Piano),
             4 => Ok(Instrument::Cello),
-            d => Err(::bebop::DeserializeError::InvalidEnumDiscriminator(
-                d.into(),
-            )),
+            d => Err(::bebop::DeserializeError::InvalidEnumDiscriminant { value: d.into() }),
         }
     }
 }
@@ -69,7 +67,7 @@ impl<'raw> ::bebop::SubRecord<'raw> for Instrument {
     }
 
     #[inline]
-    fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
+    fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
         u32::from(*self)._serialize_chained(dest)
     }
 
@@ -99,7 +97,7 @@ impl<'raw> ::bebop::SubRecord<'raw> for Performer<'raw> {
         self.name.serialized_size() + self.plays.serialized_size()
     }
 
-    fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
+    fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
         Ok(self.name._serialize_chained(dest)? + self.plays._serialize_chained(dest)?)
     }
 
@@ -161,7 +159,7 @@ impl<'raw> ::bebop::SubRecord<'raw> for Song<'raw> {
                 .unwrap_or(0)
     }
 
-    fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
+    fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
         let size = self.serialized_size();
         ::bebop::write_len(dest, size - ::bebop::LEN_SIZE)?;
         if let Some(ref v) = self.title {
@@ -323,7 +321,7 @@ impl<'raw> ::bebop::SubRecord<'raw> for Album<'raw> {
             }
     }
 
-    fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
+    fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
         let size = self.serialized_size();
         ::bebop::write_len(dest, size - ::bebop::LEN_SIZE - 1)?;
         match self {
@@ -483,7 +481,7 @@ impl<'raw> ::bebop::SubRecord<'raw> for Library<'raw> {
         self.albums.serialized_size()
     }
 
-    fn _serialize_chained<W: ::std::io::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
+    fn _serialize_chained<W: ::bebop::Write>(&self, dest: &mut W) -> ::bebop::SeResult<usize> {
         Ok(self.albums._serialize_chained(dest)?)
     }
 
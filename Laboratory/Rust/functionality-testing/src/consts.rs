@@ -0,0 +1,49 @@
+//! The same values the other generators are tested against for `const.bop`.
+
+use crate::generated::consts::*;
+use bebop::Guid;
+
+#[test]
+fn integers() {
+    assert_eq!(PCASE, 0x12u8);
+    assert_eq!(EXAMPLE_CONST_INT32, -123i32);
+    assert_eq!(EXAMPLE_CONST_UINT64, 0x123ffffffffu64);
+    assert_eq!(EXAMPLE_CONST_INT64, i64::MIN);
+}
+
+#[test]
+fn floats() {
+    assert_eq!(EXAMPLE_CONST_FLOAT64, 123.45678e9f64);
+    assert_eq!(EXAMPLE_CONST_INF, f64::INFINITY);
+    assert_eq!(EXAMPLE_CONST_NEG_INF, f64::NEG_INFINITY);
+    assert!(EXAMPLE_CONST_NAN.is_nan());
+    assert_eq!(EXAMPLE_CONST_FLOAT32, 0.5f32);
+    assert_eq!(EXAMPLE_CONST_EXPONENT, 1000.0);
+    assert_eq!(EXAMPLE_CONST_WHOLE, 7.0);
+}
+
+#[test]
+fn bools() {
+    assert_eq!([EXAMPLE_CONST_FALSE, EXAMPLE_CONST_TRUE], [false, true]);
+}
+
+#[test]
+fn strings() {
+    assert_eq!(EXAMPLE_CONST_STRING, "hello \"world\"\nwith newlines");
+    assert_eq!(EXAMPLE_CONST_EMPTY_STRING, "");
+}
+
+#[test]
+fn guids() {
+    assert_eq!(
+        EXAMPLE_CONST_GUID,
+        Guid::from_ms_bytes(&[
+            0x46, 0xa9, 0x15, 0xe2, 0x6f, 0xb2, 0x67, 0x45, 0xa2, 0x76, 0x13, 0x13, 0x6f, 0x0a,
+            0x17, 0x08
+        ])
+    );
+    assert_eq!(
+        EXAMPLE_CONST_GUID.to_string(),
+        "e215a946-b26f-4567-a276-13136f0a1708"
+    );
+}
@@ -0,0 +1,28 @@
+use crate::generated::derives::*;
+use std::collections::HashSet;
+
+fn release(minor: u16, name: &str) -> Release {
+    Release {
+        version: Version { major: 1, minor },
+        name,
+    }
+}
+
+#[test]
+fn hash_by_hand() {
+    let releases: HashSet<_> = vec![release(0, "a"), release(0, "a"), release(1, "b")]
+        .into_iter()
+        .collect();
+    assert_eq!(releases.len(), 2);
+    assert!(release(0, "a") < release(1, "a"));
+}
+
+#[test]
+fn hash_without_ordering() {
+    let score = |points| Score {
+        player: Some("a"),
+        points: Some(points),
+    };
+    let scores: HashSet<_> = vec![score(1), score(1), score(2)].into_iter().collect();
+    assert_eq!(scores.len(), 2);
+}
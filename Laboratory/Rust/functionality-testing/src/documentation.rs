@@ -0,0 +1,17 @@
+// enum fields are only a different type with `lenient_enums`
+#![allow(clippy::useless_conversion)]
+
+use crate::generated::documentation::*;
+use bebop::Record;
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_field_round_trip() {
+    let settings = Settings {
+        name: Some("current"),
+        legacy_name: Some("legacy"),
+        shade: Some(Shade::Solid.into()),
+    };
+    let buf = settings.encode_to_vec().unwrap();
+    assert_eq!(Settings::deserialize(&buf).unwrap(), settings);
+}
@@ -1,6 +1,8 @@
 use crate::generated::enum_size::SmallEnum;
 use crate::generated::enum_size::HugeEnum;
-use bebop::SubRecord;
+use crate::generated::jazz::Instrument;
+use bebop::{MaybeUnknown, SliceWrapper, SubRecord};
+use bebop::DeserializeError;
 use std::mem::size_of;
 
 #[test]
@@ -8,3 +10,56 @@ fn correct_sizes() {
     assert_eq!(size_of::<SmallEnum>(), size_of::<u8>());
     assert_eq!(size_of::<HugeEnum>(), size_of::<i64>());
 }
+
+#[test]
+fn discriminants_are_wire_values() {
+    assert_eq!(SmallEnum::A as u8, 1);
+    assert_eq!(SmallEnum::B as u8, 255);
+    assert_eq!(HugeEnum::Neg as i64, -1);
+    assert_eq!(HugeEnum::MaxInt as i64, i64::MAX);
+
+    // enums without a type are `uint32`, and values do not have to be contiguous
+    assert_eq!(size_of::<Instrument>(), size_of::<u32>());
+    let values = [
+        (Instrument::Sax, 0),
+        (Instrument::Trumpet, 1),
+        (Instrument::Clarinet, 2),
+        (Instrument::Piano, 5),
+    ];
+    for &(instrument, value) in values.iter() {
+        assert_eq!(instrument as u32, value);
+        assert_eq!(u32::from(instrument), value);
+    }
+}
+
+#[test]
+fn unknown_values_are_rejected() {
+    assert!(matches!(
+        SmallEnum::_deserialize_chained(&[2]),
+        Err(DeserializeError::InvalidEnumDiscriminant { value: 2 })
+    ));
+    assert!(matches!(
+        HugeEnum::_deserialize_chained(&(-2i64).to_le_bytes()),
+        Err(DeserializeError::InvalidEnumDiscriminant { value: -2 })
+    ));
+    // byte sized enums are checked in arrays as well
+    assert!(matches!(
+        SliceWrapper::<SmallEnum>::_deserialize_chained(&[2, 0, 0, 0, 1, 2]),
+        Err(DeserializeError::InvalidEnumDiscriminant { value: 2 })
+    ));
+}
+
+#[test]
+fn unknown_values_are_kept() {
+    assert_eq!(
+        MaybeUnknown::<SmallEnum>::_deserialize_chained(&[2]).unwrap(),
+        (1, MaybeUnknown::Unknown(2))
+    );
+    let (_, values) =
+        SliceWrapper::<MaybeUnknown<SmallEnum>>::_deserialize_chained(&[2, 0, 0, 0, 1, 2]).unwrap();
+    assert_eq!(values, [SmallEnum::A.into(), MaybeUnknown::Unknown(2)][..]);
+
+    let mut buf = Vec::new();
+    values._serialize_chained(&mut buf).unwrap();
+    assert_eq!(buf, [2, 0, 0, 0, 1, 2]);
+}
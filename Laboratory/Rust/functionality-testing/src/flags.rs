@@ -1,5 +1,5 @@
-use crate::generated::flags::MyFlags;
-use bebop::SubRecord;
+use crate::generated::flags::{Access, Grant, MyFlags};
+use bebop::{Record, SubRecord};
 
 #[test]
 fn correct_values() {
@@ -24,3 +24,29 @@ fn can_encode_flags() {
     assert_eq!(written, 4);
     assert_eq!(buf, vec![0x03, 0x00, 0x00, 0x00]);
 }
+
+#[test]
+fn combined_flags_round_trip() {
+    let grant = Grant {
+        user: Some("root"),
+        access: Some(Access::READ | Access::WRITE),
+    };
+    let buf = grant.encode_to_vec().unwrap();
+    let de = Grant::deserialize(&buf).unwrap();
+    assert_eq!(de, grant);
+    let access = de.access.unwrap();
+    assert!(access.contains(Access::READ | Access::WRITE));
+    assert!(!access.contains(Access::EXECUTE));
+    assert_eq!(access & Access::WRITE, Access::WRITE);
+    assert_eq!(access.bits(), 0x3);
+}
+
+#[test]
+fn unknown_flags_round_trip() {
+    let buf = [0x0b, 0x00, 0x00, 0x00];
+    let (_, access) = Access::_deserialize_chained(&buf).unwrap();
+    assert_eq!(access.bits(), 0xb);
+    let mut out = Vec::new();
+    access._serialize_chained(&mut out).unwrap();
+    assert_eq!(out, buf);
+}
@@ -1,5 +1,11 @@
+// enum fields are only a different type with `lenient_enums`
+#![allow(clippy::useless_conversion)]
+
 use crate::generated::jazz::*;
-use bebop::{test_serialization, collection, Date, Guid, Record, SubRecord, ENUM_SIZE, LEN_SIZE};
+use bebop::{
+    collection, test_serialization, ArrayIter, Date, DeserializeError, Guid, MissingFieldError,
+    OwnedRecord, Record, SubRecord, ENUM_SIZE, LEN_SIZE,
+};
 
 fn song1() -> Song<'static> {
     Song {
@@ -8,11 +14,11 @@ fn song1() -> Song<'static> {
         performers: Some(vec![
             Performer {
                 name: "Dizzy Gillespie",
-                plays: Instrument::Trumpet,
+                plays: Instrument::Trumpet.into(),
             },
             Performer {
                 name: "Frank Paparelli",
-                plays: Instrument::Piano,
+                plays: Instrument::Piano.into(),
             },
         ]),
     }
@@ -83,7 +89,7 @@ test_serialization!(
     Performer,
     Performer {
         name: "Charlie Parker",
-        plays: Instrument::Sax,
+        plays: Instrument::Sax.into(),
     },
     LEN_SIZE + 14 + ENUM_SIZE
 );
@@ -173,6 +179,209 @@ test_serialization!(
     LEN_SIZE * 4 + 10 + 1
 );
 
+#[test]
+fn encode_song_to_vec() {
+    let song = song1();
+    let mut buf = Vec::new();
+    song.serialize(&mut buf).unwrap();
+    let encoded = song.encode_to_vec().unwrap();
+    assert_eq!(encoded, buf);
+    assert_eq!(encoded.len(), song.serialized_size());
+    assert_eq!(Song::deserialize(&encoded).unwrap(), song);
+}
+
+#[test]
+fn deserialize_owned_song_outlives_buffer() {
+    let song: owned::Song = {
+        let buf = song1().encode_to_vec().unwrap();
+        owned::Song::deserialize(&buf).unwrap()
+    };
+    assert_eq!(song.title.as_deref(), Some("A Night in Tunisia"));
+    assert_eq!(song.year, Some(1942));
+    let performers = song.performers.unwrap();
+    assert_eq!(performers[1].name, "Frank Paparelli");
+    assert_eq!(performers[1].plays, Instrument::Piano);
+}
+
+#[test]
+fn owned_album_outlives_buffer() {
+    let album = Album::StudioAlbum {
+        tracks: vec![song1(), song2()],
+    };
+    let buf = album.encode_to_vec().unwrap();
+    let owned = Album::deserialize(&buf).unwrap().to_owned();
+    drop(buf);
+    let owned = std::thread::spawn(move || owned).join().unwrap();
+    assert_eq!(owned.as_borrowed(), album);
+    assert_eq!(song1().to_owned().as_borrowed(), song1());
+}
+
+#[test]
+fn deserialize_into_owned_song() {
+    let mut song = owned::Song::deserialize(&song1().encode_to_vec().unwrap()).unwrap();
+    let title = song.title.as_ref().unwrap().as_ptr();
+    let performers = song.performers.as_ref().unwrap().as_ptr();
+
+    let other = Song {
+        title: Some("Groovin' High"),
+        year: None,
+        performers: Some(vec![Performer {
+            name: "Charlie Parker",
+            plays: Instrument::Sax.into(),
+        }]),
+    };
+    song.deserialize_into(&other.encode_to_vec().unwrap()).unwrap();
+    assert_eq!(song.as_borrowed(), other);
+    assert_eq!(song.title.as_ref().unwrap().as_ptr(), title);
+    assert_eq!(song.performers.as_ref().unwrap().as_ptr(), performers);
+
+    song.deserialize_into(&song2().encode_to_vec().unwrap()).unwrap();
+    assert_eq!(song.as_borrowed(), song2());
+}
+
+#[test]
+fn decode_owned_songs_from_reader() {
+    let mut buf = song1().encode_to_vec().unwrap();
+    song2().serialize(&mut buf).unwrap();
+    let mut reader = std::io::Cursor::new(buf);
+    let first = owned::Song::decode_from_reader(&mut reader).unwrap();
+    assert_eq!(first.title.as_deref(), Some("A Night in Tunisia"));
+    let second = owned::Song::decode_from_reader(&mut reader).unwrap();
+    assert_eq!(second.title.as_deref(), Some("Ornithology"));
+    assert!(matches!(
+        owned::Song::decode_from_reader(&mut reader),
+        Err(DeserializeError::MoreDataExpected(_))
+    ));
+}
+
+#[test]
+fn decode_owned_album_from_truncated_reader() {
+    let buf = Album::StudioAlbum {
+        tracks: vec![song1()],
+    }
+    .encode_to_vec()
+    .unwrap();
+    let mut reader = &buf[..buf.len() - 3];
+    assert!(matches!(
+        owned::Album::decode_from_reader(&mut reader),
+        Err(DeserializeError::MoreDataExpected(3))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn song_to_json() {
+    let json = serde_json::to_value(&song1()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "title": "A Night in Tunisia",
+            "year": 1942,
+            "performers": [
+                { "name": "Dizzy Gillespie", "plays": "Trumpet" },
+                { "name": "Frank Paparelli", "plays": "Piano" },
+            ],
+        })
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn owned_album_json_round_trip() {
+    let album = owned::Album::LiveAlbum {
+        tracks: None,
+        venue_name: Some("Perdido".to_owned()),
+        concert_date: Some(Date::from_secs_since_unix_epoch(1627595855)),
+    };
+    let json = serde_json::to_string(&album).unwrap();
+    assert_eq!(
+        json,
+        r#"{"LiveAlbum":{"tracks":null,"venue_name":"Perdido","concert_date":"2021-07-29T21:57:35Z"}}"#
+    );
+    assert_eq!(serde_json::from_str::<owned::Album>(&json).unwrap(), album);
+}
+
+#[test]
+fn album_to_debug_json() {
+    let album = Album::LiveAlbum {
+        tracks: Some(vec![song2()]),
+        venue_name: None,
+        concert_date: Some(Date::from_secs_since_unix_epoch(1627595855)),
+    };
+    assert_eq!(
+        bebop::debug_json::to_value(&album),
+        serde_json::json!({
+            "discriminator": 2,
+            "value": {
+                "tracks": [{ "title": "Ornithology", "year": 1946 }],
+                "concertDate": "2021-07-29T21:57:35Z",
+            },
+        })
+    );
+    assert_eq!(
+        bebop::debug_json::to_value(&Album::Unknown(7)),
+        serde_json::json!({ "discriminator": 7, "value": null })
+    );
+}
+
+#[test]
+fn build_performer() {
+    let performer = Performer::builder()
+        .name("Dizzy Gillespie")
+        .plays(Instrument::Trumpet)
+        .build()
+        .unwrap();
+    assert_eq!(performer, song1().performers.unwrap()[0]);
+    assert_eq!(
+        Performer::builder().name("Dizzy Gillespie").build(),
+        Err(MissingFieldError("plays"))
+    );
+}
+
+#[test]
+fn build_song() {
+    let song = Song::builder().title("Ornithology").year(1946).build();
+    assert_eq!(song, song2());
+    assert_eq!(Song::builder().build(), Song::default());
+}
+
+#[test]
+fn find_song_without_decoding_all() {
+    let mut buf = Vec::new();
+    vec![song1(), song2()]._serialize_chained(&mut buf).unwrap();
+    let found = ArrayIter::<Song>::new(&buf)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|song| song.year == Some(1946));
+    assert_eq!(found, Some(song2()));
+}
+
+#[test]
+fn performers_as_keys() {
+    let mut performers = std::collections::HashSet::new();
+    for song in [song1(), song1()] {
+        performers.extend(song.performers.unwrap());
+    }
+    assert_eq!(performers.len(), 2);
+
+    let mut songs = vec![song2(), song1(), Song::default()];
+    songs.sort();
+    assert_eq!(songs, vec![Song::default(), song1(), song2()]);
+    assert!(Instrument::Sax < Instrument::Piano);
+}
+
+#[test]
+fn decode_checked_song() {
+    assert_ne!(SONG_WIRE_HASH, PERFORMER_WIRE_HASH);
+    let mut buf = Vec::new();
+    song1().encode_checked(SONG_WIRE_HASH, &mut buf).unwrap();
+    assert_eq!(Song::decode_checked(&buf, SONG_WIRE_HASH).unwrap(), song1());
+    assert!(matches!(
+        Performer::decode_checked(&buf, PERFORMER_WIRE_HASH),
+        Err(DeserializeError::SchemaMismatch { found, .. }) if found == SONG_WIRE_HASH
+    ));
+}
+
 #[test]
 fn deserialization_of_song_unknown_fields() {
     let buf = [
@@ -189,10 +398,67 @@ fn deserialization_of_song_unknown_fields() {
     );
 }
 
+#[test]
+fn album_accessors() {
+    let studio = Album::StudioAlbum {
+        tracks: vec![song2()],
+    };
+    assert!(studio.is_studio_album());
+    assert!(!studio.is_live_album());
+    assert!(!studio.is_unknown());
+    assert_eq!(studio.as_studio_album(), Some(&vec![song2()]));
+    assert_eq!(studio.as_live_album(), None);
+
+    let live = Album::LiveAlbum {
+        tracks: None,
+        venue_name: Some("Perdido"),
+        concert_date: None,
+    };
+    assert_eq!(live.as_live_album(), Some((&None, &Some("Perdido"), &None)));
+    let (_, venue_name, _) = live.into_live_album().unwrap();
+    assert_eq!(venue_name, Some("Perdido"));
+    assert_eq!(studio.into_live_album(), None);
+    assert!(Album::Unknown(5).is_unknown());
+}
+
 #[test]
 fn deserialization_of_unknown_album() {
     let buf = [0x04, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00];
     let (read, de_album) = Album::_deserialize_chained(&buf).unwrap();
     assert_eq!(read, 9);
-    assert_eq!(de_album, Album::Unknown);
+    assert_eq!(de_album, Album::Unknown(5));
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn stream_library_async() {
+    let library = Library {
+        albums: collection! {
+            "Milestones" => Album::StudioAlbum {
+                tracks: vec![song1(), song2()]
+            },
+            "Live at Birdland" => Album::LiveAlbum {
+                tracks: Some(vec![song2()]),
+                venue_name: Some("Birdland"),
+                concert_date: None,
+            }
+        },
+    };
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut buf = Vec::new();
+    rt.block_on(library.encode_async(&mut buf)).unwrap();
+    assert_eq!(buf, library.encode_to_vec().unwrap());
+
+    buf.clear();
+    let written = rt
+        .block_on(bebop::framing::encode_framed_async(&library, &mut buf))
+        .unwrap();
+    assert_eq!(written, LEN_SIZE + library.serialized_size());
+    assert_eq!(bebop::read_len(&buf).unwrap(), library.serialized_size());
+    assert_eq!(Library::deserialize(&buf[LEN_SIZE..]).unwrap(), library);
+
+    let unknown = Album::Unknown(5);
+    assert!(rt.block_on(unknown.encode_async(&mut buf)).is_err());
 }
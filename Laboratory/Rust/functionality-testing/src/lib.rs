@@ -1,5 +1,31 @@
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
 pub mod generated;
 
+/// `Version` leaves out the derived `Hash` so only the major version is hashed, which `Release`
+/// derives `Hash` with.
+impl std::hash::Hash for generated::derives::Version {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // copied out since the struct is packed
+        let major = self.major;
+        major.hash(state);
+    }
+}
+
+/// The allocator of arrays in the generated code with `allocator_api`. It is the global allocator so
+/// the tests can build the same values as without the feature.
+#[cfg(feature = "allocator_api")]
+pub type BebopAllocator = std::alloc::Global;
+
+#[cfg(test)]
+mod consts;
+
+#[cfg(test)]
+mod derives;
+
+#[cfg(test)]
+mod documentation;
+
 #[cfg(test)]
 mod enum_size;
 
@@ -11,3 +37,12 @@ mod flags;
 
 #[cfg(test)]
 mod jazz;
+
+#[cfg(test)]
+mod recursive;
+
+#[cfg(test)]
+mod versioning;
+
+#[cfg(test)]
+mod wire_kinds;
@@ -0,0 +1,130 @@
+use crate::generated::recursive::*;
+use bebop::{collection, DecodeOptions, DeserializeError, Record, DEFAULT_MAX_DEPTH};
+
+fn leaf(label: &str) -> Tree {
+    Tree {
+        label: Some(label),
+        children: None,
+    }
+}
+
+#[test]
+fn tree_round_trip() {
+    let tree = Tree {
+        label: Some("root"),
+        children: Some(vec![
+            Tree {
+                label: None,
+                children: Some(vec![leaf("a"), leaf("b")]),
+            },
+            leaf("c"),
+        ]),
+    };
+    let buf = tree.encode_to_vec().unwrap();
+    assert_eq!(Tree::deserialize(&buf).unwrap(), tree);
+}
+
+/// A list of `labels` linked through the `next` field, there must be at least one.
+fn list(labels: &[&'static str]) -> Node<'static> {
+    let mut next = None;
+    for label in labels.iter().rev() {
+        next = Some(Box::new(Node {
+            label: Some(label),
+            next,
+        }));
+    }
+    *next.unwrap()
+}
+
+#[test]
+fn node_round_trip() {
+    let node = list(&["a", "b", "c"]);
+    let buf = node.encode_to_vec().unwrap();
+    assert_eq!(Node::deserialize(&buf).unwrap(), node);
+
+    let owned = owned::Node::deserialize(&buf).unwrap();
+    assert_eq!(owned.as_borrowed(), node);
+    assert_eq!(node.to_owned(), owned);
+    assert_eq!(
+        Node::builder()
+            .label("a")
+            .next(Node::builder().label("b").build())
+            .build(),
+        list(&["a", "b"])
+    );
+}
+
+#[test]
+fn chain_round_trip() {
+    let chain = Chain {
+        link: Some(Box::new(Link {
+            name: "first",
+            rest: Chain {
+                link: Some(Box::new(Link {
+                    name: "second",
+                    rest: Chain { link: None },
+                })),
+            },
+        })),
+    };
+    let buf = chain.encode_to_vec().unwrap();
+    assert_eq!(Chain::deserialize(&buf).unwrap(), chain);
+}
+
+#[test]
+fn directory_round_trip() {
+    let dir = Directory {
+        name: "/",
+        entries: collection! {
+            "usr" => Directory {
+                name: "usr",
+                entries: collection! {
+                    "bin" => Directory { name: "bin", entries: Default::default() },
+                },
+            },
+        },
+    };
+    let buf = dir.encode_to_vec().unwrap();
+    assert_eq!(Directory::deserialize(&buf).unwrap(), dir);
+}
+
+#[test]
+fn lifetime_through_cycle() {
+    let inner: Inner<'static> = Inner {
+        outer: vec![Outer {
+            inner: vec![Inner { outer: vec![] }],
+            name: "outer",
+        }],
+    };
+    let buf = inner.encode_to_vec().unwrap();
+    assert_eq!(Inner::deserialize(&buf).unwrap(), inner);
+}
+
+/// A tree which is `depth` messages deep with one child at each level.
+fn deep_tree(depth: usize) -> Tree<'static> {
+    let mut tree = leaf("leaf");
+    for _ in 1..depth {
+        tree = Tree {
+            label: None,
+            children: Some(vec![tree]),
+        };
+    }
+    tree
+}
+
+#[test]
+fn depth_limit() {
+    let buf = deep_tree(DEFAULT_MAX_DEPTH).encode_to_vec().unwrap();
+    assert!(Tree::deserialize(&buf).is_ok());
+    let buf = deep_tree(DEFAULT_MAX_DEPTH + 1).encode_to_vec().unwrap();
+    assert!(matches!(
+        Tree::deserialize(&buf),
+        Err(DeserializeError::DepthLimitExceeded)
+    ));
+
+    let options = DecodeOptions::default().max_depth(DEFAULT_MAX_DEPTH + 1);
+    assert_eq!(
+        Tree::deserialize_with_options(&buf, &options).unwrap(),
+        deep_tree(DEFAULT_MAX_DEPTH + 1)
+    );
+}
@@ -0,0 +1,110 @@
+use crate::generated::versioning::*;
+use bebop::{collection, Record, SubRecord};
+
+fn release() -> ReleaseV2<'static> {
+    ReleaseV2 {
+        title: Some("Kind of Blue"),
+        year: Some(1959),
+        tracks: Some(vec![
+            Track {
+                title: "So What",
+                seconds: 562,
+            },
+            Track {
+                title: "Freddie Freeloader",
+                seconds: 586,
+            },
+        ]),
+        credits: Some(collection! {
+            "trumpet" => "Miles Davis",
+            "piano" => "Bill Evans",
+        }),
+    }
+}
+
+#[test]
+fn older_message_skips_newer_fields() {
+    let buf = release().encode_to_vec().unwrap();
+    let (read, old) = ReleaseV1::_deserialize_chained(&buf).unwrap();
+    assert_eq!(read, buf.len());
+    assert_eq!(
+        old,
+        ReleaseV1 {
+            title: Some("Kind of Blue"),
+            year: Some(1959),
+        }
+    );
+}
+
+#[test]
+fn older_message_skips_newer_fields_when_nested() {
+    let buf = CatalogV2 {
+        releases: vec![release(), ReleaseV2::default(), release()],
+        owner: "Columbia",
+    }
+    .encode_to_vec()
+    .unwrap();
+    let catalog = Catalog::deserialize(&buf).unwrap();
+    assert_eq!(catalog.releases.len(), 3);
+    assert_eq!(catalog.releases[1], ReleaseV1::default());
+    assert_eq!(catalog.releases[2].title, Some("Kind of Blue"));
+    assert_eq!(catalog.owner, "Columbia");
+}
+
+#[test]
+fn newer_message_reads_older_fields() {
+    let buf = ReleaseV1 {
+        title: None,
+        year: Some(1959),
+    }
+    .encode_to_vec()
+    .unwrap();
+    assert_eq!(
+        ReleaseV2::deserialize(&buf).unwrap(),
+        ReleaseV2 {
+            year: Some(1959),
+            ..Default::default()
+        }
+    );
+}
+
+#[cfg(feature = "conversions")]
+#[test]
+fn older_version_converts_to_newer() {
+    let v2 = ReleaseV2::from(ReleaseV1 {
+        title: Some("Kind of Blue"),
+        year: Some(1959),
+    });
+    assert_eq!(
+        v2,
+        ReleaseV2 {
+            title: Some("Kind of Blue"),
+            year: Some(1959),
+            ..Default::default()
+        }
+    );
+
+    let catalog: CatalogV2 = Catalog {
+        releases: vec![ReleaseV1::default()],
+        owner: "Columbia",
+    }
+    .into();
+    assert_eq!(catalog.releases, vec![ReleaseV2::default()]);
+    assert_eq!(catalog.owner, "Columbia");
+}
+
+#[cfg(all(feature = "conversions", feature = "owned"))]
+#[test]
+fn older_owned_version_converts_to_newer() {
+    use crate::generated::versioning::owned;
+
+    let catalog = owned::CatalogV2::from(owned::Catalog {
+        releases: vec![owned::ReleaseV1 {
+            title: Some("Kind of Blue".into()),
+            year: None,
+        }],
+        owner: "Columbia".into(),
+    });
+    assert_eq!(catalog.releases[0].title.as_deref(), Some("Kind of Blue"));
+    assert_eq!(catalog.releases[0].tracks, None);
+}
@@ -0,0 +1,211 @@
+use crate::generated::wire_kinds::*;
+use bebop::collection;
+use bebop::prelude::*;
+
+/// Enum fields are a `MaybeUnknown` with `lenient_enums`, and `into` can't be used in a const.
+#[cfg(not(feature = "lenient_enums"))]
+type ColorField = Color;
+#[cfg(feature = "lenient_enums")]
+type ColorField = bebop::MaybeUnknown<Color>;
+
+const fn color(color: Color) -> ColorField {
+    #[cfg(feature = "lenient_enums")]
+    let color = bebop::MaybeUnknown::Known(color);
+    color
+}
+
+const SCALARS: Scalars = Scalars {
+    b: true,
+    u8v: 8,
+    u16v: 16,
+    i16v: -16,
+    u32v: 32,
+    i32v: -32,
+    u64v: 64,
+    i64v: -64,
+    f32v: 3.2,
+    f64v: 6.4,
+    id: Guid::from_be_bytes([
+        0xa3, 0x62, 0x8e, 0xc7, 0x28, 0xd4, 0x45, 0x46, 0xad, 0x4a, 0xf6, 0xeb, 0xf5, 0x37, 0x5c,
+        0x96,
+    ]),
+    when: Date::from_secs_since_unix_epoch(1627595855),
+    color: color(Color::Green),
+};
+
+const POINTS: [Point; 2] = [Point { x: 1, y: 2 }, Point { x: -3, y: -4 }];
+
+const COLORS: [ColorField; 2] = [color(Color::Red), color(Color::Green)];
+
+fn inner(name: &str) -> Inner {
+    Inner {
+        name: Some(name),
+        location: Some(POINTS[0]),
+    }
+}
+
+fn shapes() -> Vec<Shape<'static>> {
+    vec![
+        Shape::Circle {
+            center: POINTS[1],
+            radius: 2.5,
+        },
+        Shape::Polygon {
+            points: Some(SliceWrapper::Cooked(&POINTS)),
+            label: Some("triangle-ish"),
+        },
+        Shape::Polygon {
+            points: None,
+            label: None,
+        },
+        Shape::Nothing {},
+    ]
+}
+
+fn everything() -> Everything<'static> {
+    Everything {
+        scalars: Some(SCALARS),
+        text: Some("some text"),
+        bytes: Some(SliceWrapper::Cooked(&[1, 2, 3])),
+        numbers: Some(SliceWrapper::Cooked(&[4, 5, 6, 7])),
+        words: Some(vec!["a", "", "bc"]),
+        points: Some(SliceWrapper::Cooked(&POINTS)),
+        colors: Some(SliceWrapper::Cooked(&COLORS)),
+        // single entries keep the encoding deterministic
+        inners: Some(collection! { "first" => inner("inner") }),
+        shape: Some(shapes().remove(1)),
+        shapes: Some(shapes()),
+        empty: Some(Empty {}),
+        empty_message: Some(EmptyMessage {}),
+        permissions: Some(Permissions::READ | Permissions::WRITE),
+        nested: Some(collection! { SCALARS.id => SliceWrapper::Cooked(&[8, 9]) }),
+        grid: Some(vec![vec![inner("a"), Inner::default()], vec![]]),
+    }
+}
+
+fn wrapper() -> Wrapper<'static> {
+    Wrapper {
+        text: "wrapped",
+        shape: shapes().remove(0),
+        everything: everything(),
+        scalars: SliceWrapper::Cooked(&[SCALARS, SCALARS]),
+    }
+}
+
+/// The size must be exact or preallocated buffers will be too small. Decoding and encoding again
+/// must produce the same bytes, which also checks the size of the decoded value.
+macro_rules! check {
+    ($type:ident, $value:expr) => {{
+        let value = $value;
+        let buf = value.encode_to_vec().unwrap();
+        assert_eq!(value.serialized_size(), buf.len(), "{:?}", value);
+        let decoded = <$type>::deserialize(&buf).unwrap();
+        assert_eq!(decoded.serialized_size(), buf.len(), "{:?}", value);
+        assert_eq!(decoded.encode_to_vec().unwrap(), buf, "{:?}", value);
+    }};
+}
+
+#[test]
+fn scalars() {
+    check!(Scalars, SCALARS);
+}
+
+#[test]
+fn unknown_enum_value() {
+    let mut buf = SCALARS.encode_to_vec().unwrap();
+    // the color is the last field
+    *buf.last_mut().unwrap() = 9;
+    let decoded = Scalars::deserialize(&buf);
+    #[cfg(not(feature = "lenient_enums"))]
+    assert!(matches!(
+        decoded,
+        Err(bebop::DeserializeError::InvalidEnumDiscriminant { value: 9 })
+    ));
+    #[cfg(feature = "lenient_enums")]
+    {
+        let decoded = decoded.unwrap();
+        assert_eq!(decoded.color, bebop::MaybeUnknown::Unknown(9));
+        assert_eq!(decoded.encode_to_vec().unwrap(), buf);
+    }
+}
+
+#[test]
+fn inner_message() {
+    check!(Inner, inner("x"));
+    check!(Inner, Inner::default());
+}
+
+#[test]
+fn union_branches() {
+    for shape in shapes() {
+        check!(Shape, shape);
+    }
+}
+
+#[test]
+fn union_discriminants() {
+    let discriminants = shapes()
+        .iter()
+        .map(|shape| {
+            let buf = shape.encode_to_vec().unwrap();
+            assert_eq!(
+                bebop::peek_union_discriminant(&buf).unwrap(),
+                shape.discriminant()
+            );
+            shape.discriminant()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(discriminants, [1, 2, 2, 3]);
+    assert_eq!(Shape::Unknown(9).discriminant(), 9);
+}
+
+#[test]
+fn roundtrip_helper() {
+    bebop::testing::assert_roundtrip_in(&wrapper(), &mut Vec::new());
+}
+
+/// Only types without floats are generated since a `NaN` is not equal to itself.
+#[cfg(feature = "arbitrary")]
+#[test]
+fn arbitrary_roundtrips() {
+    use bebop::arbitrary::{Arbitrary, Unstructured};
+
+    let data = (0..4096u32)
+        .map(|i| (i * 31 % 251) as u8)
+        .collect::<Vec<_>>();
+    let mut u = Unstructured::new(&data);
+    for _ in 0..16 {
+        bebop::testing::assert_roundtrip_in(&Inner::arbitrary(&mut u).unwrap(), &mut Vec::new());
+        bebop::testing::assert_roundtrip_in(&Point::arbitrary(&mut u).unwrap(), &mut Vec::new());
+        bebop::testing::assert_roundtrip(&owned::Inner::arbitrary(&mut u).unwrap());
+    }
+}
+
+#[test]
+fn invalid_utf8_offset() {
+    let mut buf = inner("x").encode_to_vec().unwrap();
+    // the name is the first field, after the message length and the field index
+    let name_at = bebop::LEN_SIZE + 1;
+    buf[name_at + bebop::LEN_SIZE] = 0xff;
+    match Inner::deserialize(&buf) {
+        Err(bebop::DeserializeError::InvalidUtf8 {
+            field_offset,
+            utf8_error,
+        }) => {
+            assert_eq!(field_offset, name_at);
+            assert_eq!(utf8_error.valid_up_to(), 0);
+        }
+        other => panic!("expected invalid utf-8 but got {:?}", other),
+    }
+}
+
+#[test]
+fn everything_message() {
+    check!(Everything, everything());
+    check!(Everything, Everything::default());
+}
+
+#[test]
+fn wrapper_struct() {
+    check!(Wrapper, wrapper());
+}
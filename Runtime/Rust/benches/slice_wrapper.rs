@@ -2,12 +2,12 @@ use bebop::error::{DeResult, SeResult};
 use bebop::fixed_sized::FixedSized;
 use bebop::SubRecord;
 use bebop::{SliceWrapper, LEN_SIZE};
-use criterion::{black_box, criterion_group, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, Criterion};
 use std::convert::TryInto;
-use std::io::Write;
+use bebop::Write;
 
 /// a struct designed to be a nightmare for alignment
-#[repr(packed)]
+#[repr(C, packed)]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 struct Fixed {
     a: u8,
@@ -26,7 +26,7 @@ impl<'raw> SubRecord<'raw> for Fixed {
 
     fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
         self.a._serialize_chained(dest)?;
-        self.b._serialize_chained(dest)?;
+        { self.b }._serialize_chained(dest)?;
         Ok(9)
     }
 
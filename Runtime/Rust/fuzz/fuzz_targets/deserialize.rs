@@ -0,0 +1,41 @@
+//! Decode arbitrary input as each of the runtime types. Decoding may fail but it must never panic or
+//! allocate more than the input could fill. Run with `cargo fuzz run deserialize`.
+
+#![no_main]
+
+use bebop::{Date, Guid, HashMap, SliceWrapper, SubRecord};
+use libfuzzer_sys::fuzz_target;
+
+/// Decode a value and make sure it consumed no more than it was given and encodes back to the same
+/// size.
+fn decode<'raw, T: SubRecord<'raw>>(data: &'raw [u8]) {
+    if let Ok((read, value)) = T::_deserialize_chained(data) {
+        assert!(read <= data.len());
+        assert_eq!(value.serialized_size(), read);
+    }
+}
+
+/// Maps may shrink when re-encoded since duplicate keys are merged.
+fn decode_map<'raw, T: SubRecord<'raw>>(data: &'raw [u8]) {
+    if let Ok((read, value)) = T::_deserialize_chained(data) {
+        assert!(read <= data.len());
+        assert!(value.serialized_size() <= read);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    decode::<bool>(data);
+    decode::<u32>(data);
+    decode::<f64>(data);
+    decode::<Guid>(data);
+    decode::<Date>(data);
+    decode::<&str>(data);
+    decode::<String>(data);
+    decode::<SliceWrapper<u8>>(data);
+    decode::<SliceWrapper<u64>>(data);
+    decode::<Vec<&str>>(data);
+    decode::<Vec<Vec<u16>>>(data);
+    decode::<Vec<SliceWrapper<Guid>>>(data);
+    decode_map::<HashMap<u32, &str>>(data);
+    decode_map::<HashMap<&str, Vec<HashMap<Guid, Date>>>>(data);
+});
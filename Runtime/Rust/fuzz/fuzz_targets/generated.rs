@@ -0,0 +1,35 @@
+//! Decode arbitrary input as records generated from the lab schemas, which covers the generated
+//! message and union decoders. Decoding may fail but must not panic, and anything decoded must
+//! encode to the size it reports and decode to the same bytes again. Run with
+//! `cargo fuzz run generated`, `bebopc` has to be built first for the lab crate's build script.
+
+#![no_main]
+
+use bebop::Record;
+use functionality_testing::generated::{jazz, recursive, versioning, wire_kinds};
+use libfuzzer_sys::fuzz_target;
+
+macro_rules! check {
+    ($type:ty, $data:expr) => {
+        if let Ok(record) = <$type>::deserialize($data) {
+            // unknown union branches can't be written
+            if let Ok(buf) = record.encode_to_vec() {
+                assert_eq!(buf.len(), record.serialized_size());
+                let again = <$type>::deserialize(&buf).unwrap();
+                assert_eq!(again.encode_to_vec().unwrap(), buf);
+            }
+        }
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    check!(jazz::Album, data);
+    check!(jazz::Library, data);
+    check!(jazz::owned::Song, data);
+    check!(recursive::Tree, data);
+    check!(recursive::Directory, data);
+    check!(versioning::ReleaseV2, data);
+    check!(wire_kinds::Shape, data);
+    check!(wire_kinds::Everything, data);
+    check!(wire_kinds::owned::Everything, data);
+});
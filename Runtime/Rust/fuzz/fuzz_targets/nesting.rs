@@ -0,0 +1,61 @@
+//! Decode arbitrary input as a record which contains itself. However deeply the input nests it must
+//! fail with `DepthLimitExceeded` instead of overflowing the stack, and anything decoded must be
+//! within the limit. Run with `cargo fuzz run nesting`.
+
+#![no_main]
+
+use bebop::{
+    nested_depth, DeResult, DecodeOptions, Record, SeResult, SubRecord, Write, DEFAULT_MAX_DEPTH,
+};
+use libfuzzer_sys::fuzz_target;
+
+/// Shaped like a generated struct with a field that is an array of itself.
+struct Node<'raw> {
+    name: &'raw str,
+    children: Vec<Node<'raw>>,
+}
+
+impl<'raw> Node<'raw> {
+    fn depth(&self) -> usize {
+        1 + self.children.iter().map(Node::depth).max().unwrap_or(0)
+    }
+}
+
+impl<'raw> SubRecord<'raw> for Node<'raw> {
+    const MIN_SERIALIZED_SIZE: usize =
+        <&str>::MIN_SERIALIZED_SIZE + <Vec<Node>>::MIN_SERIALIZED_SIZE;
+
+    fn serialized_size(&self) -> usize {
+        self.name.serialized_size() + self.children.serialized_size()
+    }
+
+    fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+        Ok(self.name._serialize_chained(dest)? + self.children._serialize_chained(dest)?)
+    }
+
+    fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        Self::_deserialize_chained_with_depth(raw, DEFAULT_MAX_DEPTH)
+    }
+
+    fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> DeResult<(usize, Self)> {
+        let depth = nested_depth(depth)?;
+        let (i, name) = <&str>::_deserialize_chained(raw)?;
+        let (read, children) = <Vec<Node>>::_deserialize_chained_with_depth(&raw[i..], depth)?;
+        Ok((i + read, Node { name, children }))
+    }
+}
+
+impl<'raw> Record<'raw> for Node<'raw> {}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(node) = Node::deserialize(data) {
+        assert!(node.depth() <= DEFAULT_MAX_DEPTH);
+    }
+    // a limit from the input so small ones are reached as well
+    if let Some((&max_depth, data)) = data.split_first() {
+        let options = DecodeOptions::default().max_depth(max_depth as usize);
+        if let Ok(node) = Node::deserialize_with_options(data, &options) {
+            assert!(node.depth() <= max_depth as usize);
+        }
+    }
+});
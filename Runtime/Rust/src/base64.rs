@@ -0,0 +1,122 @@
+//! Represent byte arrays as base64 strings with serde. Generated code uses these with
+//! `#[serde(with = "...")]` for `byte[]` fields.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+
+pub fn serialize<S, B>(bytes: &B, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    B: AsRef<[u8]>,
+{
+    serializer.serialize_str(&::base64::encode(bytes.as_ref()))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let encoded = String::deserialize(deserializer)?;
+    ::base64::decode(encoded).map_err(D::Error::custom)
+}
+
+/// The same representation for optional byte arrays such as message fields.
+pub mod option {
+    use super::*;
+
+    struct Encode<'a>(&'a [u8]);
+
+    impl<'a> Serialize for Encode<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::serialize(&self.0, serializer)
+        }
+    }
+
+    struct Decode(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for Decode {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::deserialize(deserializer).map(Decode)
+        }
+    }
+
+    pub fn serialize<S, B>(bytes: &Option<B>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        B: AsRef<[u8]>,
+    {
+        match bytes {
+            Some(b) => serializer.serialize_some(&Encode(b.as_ref())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Decode>::deserialize(deserializer)?.map(|d| d.0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::SliceWrapper;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Owned {
+        #[serde(with = "crate::base64")]
+        data: Vec<u8>,
+        #[serde(default, with = "crate::base64::option")]
+        extra: Option<Vec<u8>>,
+    }
+
+    #[derive(serde::Serialize)]
+    struct Borrowed<'raw> {
+        #[serde(serialize_with = "crate::base64::serialize")]
+        data: SliceWrapper<'raw, u8>,
+        #[serde(serialize_with = "crate::base64::option::serialize")]
+        extra: Option<SliceWrapper<'raw, u8>>,
+    }
+
+    #[test]
+    fn serialize_borrowed() {
+        let v = Borrowed {
+            data: SliceWrapper::Raw(b"bebop"),
+            extra: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&v).unwrap(),
+            r#"{"data":"YmVib3A=","extra":null}"#
+        );
+    }
+
+    #[test]
+    fn round_trip_owned() {
+        let v = Owned {
+            data: vec![0, 1, 2, 254, 255],
+            extra: Some(b"bebop".to_vec()),
+        };
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, r#"{"data":"AAEC/v8=","extra":"YmVib3A="}"#);
+        assert_eq!(serde_json::from_str::<Owned>(&json).unwrap(), v);
+    }
+
+    #[test]
+    fn deserialize_missing_option() {
+        assert_eq!(
+            serde_json::from_str::<Owned>(r#"{"data":""}"#).unwrap(),
+            Owned {
+                data: vec![],
+                extra: None
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_invalid() {
+        assert!(serde_json::from_str::<Owned>(r#"{"data":"not base64!"}"#).is_err());
+    }
+}
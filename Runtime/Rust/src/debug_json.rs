@@ -0,0 +1,260 @@
+//! Render records as JSON for logging and debugging without the generated serde derives. Generated
+//! code implements [`DebugJson`] for each definition when the `debug_json` feature of the crate
+//! with the generated code is enabled.
+//!
+//! The output is only meant to be read, there is no way to decode it. Fields are keyed by their
+//! names in the schema, unset message fields are left out, enums are their member names, flags are
+//! their bits, and unions are `{ "discriminator": N, "value": {...} }`. Guids are hyphenated
+//! strings, Dates are RFC3339 timestamps, and byte arrays are base64 strings.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use core::hash::Hash;
+
+pub use serde_json::{Map, Value};
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use crate::HashMap;
+use crate::{Date, Enum, FixedSized, Guid, MaybeUnknown, SliceWrapper, SubRecord};
+
+/// A value which can be rendered as JSON for debugging.
+pub trait DebugJson {
+    fn to_debug_json(&self) -> Value;
+
+    /// Render an array of this type, this is only overridden so byte arrays become base64 strings.
+    #[doc(hidden)]
+    fn slice_to_debug_json(items: &[Self]) -> Value
+    where
+        Self: Sized,
+    {
+        Value::Array(items.iter().map(DebugJson::to_debug_json).collect())
+    }
+}
+
+/// Render a record as JSON.
+#[inline]
+pub fn to_value<T: DebugJson + ?Sized>(record: &T) -> Value {
+    record.to_debug_json()
+}
+
+/// Render a record as pretty-printed JSON text.
+pub fn to_string_pretty<T: DebugJson + ?Sized>(record: &T) -> String {
+    alloc::format!("{:#}", record.to_debug_json())
+}
+
+/// The representation of a union used by generated code.
+#[doc(hidden)]
+pub fn union(discriminator: u8, value: Value) -> Value {
+    let mut map = Map::new();
+    map.insert("discriminator".into(), discriminator.into());
+    map.insert("value".into(), value);
+    Value::Object(map)
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(impl DebugJson for $t {
+            #[inline]
+            fn to_debug_json(&self) -> Value {
+                Value::from(*self)
+            }
+        })*
+    };
+}
+
+impl_number!(bool, i8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+impl DebugJson for u8 {
+    #[inline]
+    fn to_debug_json(&self) -> Value {
+        Value::from(*self)
+    }
+
+    fn slice_to_debug_json(items: &[Self]) -> Value {
+        Value::String(::base64::encode(items))
+    }
+}
+
+impl DebugJson for str {
+    #[inline]
+    fn to_debug_json(&self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl DebugJson for &str {
+    #[inline]
+    fn to_debug_json(&self) -> Value {
+        Value::String((*self).into())
+    }
+}
+
+impl DebugJson for String {
+    #[inline]
+    fn to_debug_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl DebugJson for Guid {
+    fn to_debug_json(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl DebugJson for Date {
+    fn to_debug_json(&self) -> Value {
+        Value::String(self.to_rfc3339())
+    }
+}
+
+/// Unknown enum values are rendered as their value.
+impl<E> DebugJson for MaybeUnknown<E>
+where
+    E: Enum + DebugJson,
+    E::Repr: DebugJson,
+{
+    fn to_debug_json(&self) -> Value {
+        match self {
+            MaybeUnknown::Known(known) => known.to_debug_json(),
+            MaybeUnknown::Unknown(value) => value.to_debug_json(),
+        }
+    }
+}
+
+impl<T: DebugJson + ?Sized> DebugJson for Box<T> {
+    fn to_debug_json(&self) -> Value {
+        (**self).to_debug_json()
+    }
+}
+
+impl<T: DebugJson> DebugJson for Option<T> {
+    fn to_debug_json(&self) -> Value {
+        match self {
+            Some(v) => v.to_debug_json(),
+            None => Value::Null,
+        }
+    }
+}
+
+#[cfg(not(feature = "allocator_api"))]
+impl<T: DebugJson> DebugJson for Vec<T> {
+    fn to_debug_json(&self) -> Value {
+        T::slice_to_debug_json(self)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T: DebugJson, A: core::alloc::Allocator> DebugJson for Vec<T, A> {
+    fn to_debug_json(&self) -> Value {
+        T::slice_to_debug_json(self)
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A> DebugJson for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: DebugJson,
+{
+    fn to_debug_json(&self) -> Value {
+        A::Item::slice_to_debug_json(self)
+    }
+}
+
+impl<'a, T> DebugJson for SliceWrapper<'a, T>
+where
+    T: FixedSized + SubRecord<'a> + DebugJson,
+{
+    fn to_debug_json(&self) -> Value {
+        match self {
+            SliceWrapper::Cooked(items) => T::slice_to_debug_json(items),
+            SliceWrapper::Raw(_) => T::slice_to_debug_json(&self.iter().collect::<Vec<_>>()),
+        }
+    }
+}
+
+/// Keys are rendered as strings, using the JSON of the key when it is not already a string.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+impl<K, V> DebugJson for HashMap<K, V>
+where
+    K: DebugJson + Eq + Hash,
+    V: DebugJson,
+{
+    fn to_debug_json(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| {
+                    let key = match k.to_debug_json() {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, v.to_debug_json())
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bytes_are_base64() {
+        let bytes = [1u8, 2, 3, 4];
+        assert_eq!(
+            SliceWrapper::Cooked(&bytes).to_debug_json(),
+            json!("AQIDBA==")
+        );
+        assert_eq!(
+            SliceWrapper::<u8>::Raw(&bytes).to_debug_json(),
+            json!("AQIDBA==")
+        );
+        assert_eq!(bytes.to_vec().to_debug_json(), json!("AQIDBA=="));
+    }
+
+    #[test]
+    fn arrays_are_arrays() {
+        let raw = [1u8, 0, 2, 0];
+        assert_eq!(
+            SliceWrapper::<u16>::Raw(&raw).to_debug_json(),
+            json!([1, 2])
+        );
+        assert_eq!(vec![Some("a"), None].to_debug_json(), json!(["a", null]));
+    }
+
+    #[test]
+    fn runtime_types_are_strings() {
+        let guid = Guid::from_be_bytes([
+            0xa3, 0x62, 0x8e, 0xc7, 0x28, 0xd4, 0x45, 0x46, 0xad, 0x4a, 0xf6, 0xeb, 0xf5, 0x37,
+            0x5c, 0x96,
+        ]);
+        assert_eq!(
+            guid.to_debug_json(),
+            json!("a3628ec7-28d4-4546-ad4a-f6ebf5375c96")
+        );
+        assert_eq!(
+            Date::from_secs_since_unix_epoch(1627595855).to_debug_json(),
+            json!("2021-07-29T21:57:35Z")
+        );
+    }
+
+    #[test]
+    fn map_keys_are_strings() {
+        let mut map = HashMap::new();
+        map.insert(5u32, "five");
+        assert_eq!(to_value(&map), json!({ "5": "five" }));
+    }
+
+    #[test]
+    fn union_shape() {
+        assert_eq!(
+            union(2, json!({ "x": 1 })),
+            json!({ "discriminator": 2, "value": { "x": 1 } })
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! A checksummed container for records which are stored, such as on disk or in a cache, where
+//! corruption should be detected before decoding rather than producing a record with wrong values.
+//!
+//! An envelope is a 13 byte header followed by the serialized record. The header contains
+//! [`MAGIC`], the [`VERSION`] of the envelope format, the little-endian `u32` length of the
+//! record, and the little-endian CRC-32 (IEEE) of the record bytes.
+
+use core::convert::TryInto as _;
+use core::fmt::{Debug, Display, Formatter};
+
+use crate::{read_len, write_len, DeserializeError, Record, SeResult, Write, LEN_SIZE};
+
+/// The bytes every envelope starts with.
+pub const MAGIC: [u8; 4] = *b"BBOP";
+/// The version of the envelope format written by `write_envelope`.
+pub const VERSION: u8 = 1;
+/// Size of the header before the record.
+pub const HEADER_SIZE: usize = MAGIC.len() + 1 + LEN_SIZE + CHECKSUM_SIZE;
+
+const CHECKSUM_SIZE: usize = core::mem::size_of::<u32>();
+
+pub enum EnvelopeError {
+    /// The data does not start with `MAGIC` so it is not an envelope.
+    BadMagic,
+    /// The envelope was written with a version of the format this does not know how to read.
+    UnsupportedVersion(u8),
+    /// The record bytes do not match the checksum in the header, contains the checksum from the
+    /// header and the one computed from the bytes.
+    ChecksumMismatch { expected: u32, found: u32 },
+    /// The envelope is shorter than its header says or the record in it could not be decoded.
+    Deserialize(DeserializeError),
+}
+
+impl From<DeserializeError> for EnvelopeError {
+    fn from(err: DeserializeError) -> Self {
+        EnvelopeError::Deserialize(err)
+    }
+}
+
+impl Display for EnvelopeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EnvelopeError::BadMagic => write!(f, "Envelope Error, data is not an envelope"),
+            EnvelopeError::UnsupportedVersion(version) => {
+                write!(f, "Envelope Error, unsupported version {}", version)
+            }
+            EnvelopeError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Envelope Error, checksum {:#010x} does not match {:#010x}, the data is corrupt",
+                found, expected
+            ),
+            EnvelopeError::Deserialize(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl Debug for EnvelopeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        (self as &dyn Display).fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EnvelopeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EnvelopeError::Deserialize(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Write a record in an envelope. Returns the number of bytes written including the header.
+pub fn write_envelope<'raw, R, W>(record: &R, dest: &mut W) -> SeResult<usize>
+where
+    R: Record<'raw>,
+    W: Write,
+{
+    // the checksum comes before the record so it has to be serialized first
+    let payload = record.encode_to_vec()?;
+    dest.write_all(&MAGIC)?;
+    dest.write_all(&[VERSION])?;
+    write_len(dest, payload.len())?;
+    dest.write_all(&crc32(&payload).to_le_bytes())?;
+    dest.write_all(&payload)?;
+    Ok(HEADER_SIZE + payload.len())
+}
+
+/// Read a record from an envelope at the front of `raw`, the checksum is verified before the
+/// record is decoded. Any bytes after the envelope are ignored.
+pub fn read_envelope<'raw, R>(raw: &'raw [u8]) -> Result<R, EnvelopeError>
+where
+    R: Record<'raw>,
+{
+    Ok(R::deserialize(open_envelope(raw)?)?)
+}
+
+/// Verify the envelope at the front of `raw` and get the bytes of the record in it without
+/// decoding them.
+pub fn open_envelope(raw: &[u8]) -> Result<&[u8], EnvelopeError> {
+    if raw.len() < HEADER_SIZE {
+        if !MAGIC.starts_with(&raw[..raw.len().min(MAGIC.len())]) {
+            return Err(EnvelopeError::BadMagic);
+        }
+        return Err(DeserializeError::MoreDataExpected(HEADER_SIZE - raw.len()).into());
+    }
+    if raw[..MAGIC.len()] != MAGIC {
+        return Err(EnvelopeError::BadMagic);
+    }
+    let version = raw[MAGIC.len()];
+    if version != VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(version));
+    }
+    let len = read_len(&raw[MAGIC.len() + 1..])?;
+    let expected = u32::from_le_bytes(
+        raw[HEADER_SIZE - CHECKSUM_SIZE..HEADER_SIZE]
+            .try_into()
+            .unwrap(),
+    );
+    let payload = &raw[HEADER_SIZE..];
+    if payload.len() < len {
+        return Err(DeserializeError::MoreDataExpected(len - payload.len()).into());
+    }
+    let payload = &payload[..len];
+    let found = crc32(payload);
+    if found != expected {
+        return Err(EnvelopeError::ChecksumMismatch { expected, found });
+    }
+    Ok(payload)
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 as used by zip and ethernet.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, b| {
+        CRC32_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::Point;
+    use alloc::vec::Vec;
+
+    fn envelope() -> Vec<u8> {
+        let mut buf = Vec::new();
+        let written = write_envelope(&Point { x: 1, y: -1 }, &mut buf).unwrap();
+        assert_eq!(written, buf.len());
+        buf
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = envelope();
+        assert_eq!(buf.len(), HEADER_SIZE + 8);
+        assert_eq!(buf[..9], [b'B', b'B', b'O', b'P', 1, 8, 0, 0, 0]);
+        buf.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(read_envelope::<Point>(&buf).unwrap(), Point { x: 1, y: -1 });
+    }
+
+    #[test]
+    fn corrupt_payload() {
+        let mut buf = envelope();
+        *buf.last_mut().unwrap() ^= 0x10;
+        assert!(matches!(
+            read_envelope::<Point>(&buf),
+            Err(EnvelopeError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn corrupt_header() {
+        let mut buf = envelope();
+        buf[0] = b'X';
+        assert!(matches!(
+            read_envelope::<Point>(&buf),
+            Err(EnvelopeError::BadMagic)
+        ));
+
+        let mut buf = envelope();
+        buf[4] = 2;
+        assert!(matches!(
+            read_envelope::<Point>(&buf),
+            Err(EnvelopeError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let buf = envelope();
+        assert!(matches!(
+            read_envelope::<Point>(&buf[..buf.len() - 3]),
+            Err(EnvelopeError::Deserialize(
+                DeserializeError::MoreDataExpected(3)
+            ))
+        ));
+        assert!(matches!(
+            read_envelope::<Point>(&buf[..2]),
+            Err(EnvelopeError::Deserialize(
+                DeserializeError::MoreDataExpected(11)
+            ))
+        ));
+        assert!(matches!(
+            read_envelope::<Point>(&[0, 1]),
+            Err(EnvelopeError::BadMagic)
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+//! Length prefixed framing for sending multiple records over a stream. Each frame is a
+//! little-endian `u32` length followed by that many bytes of record data.
+
+use alloc::vec::Vec;
+#[cfg(feature = "tokio")]
+use core::future::Future;
+
+use crate::{read_len, write_len, DeResult, DeserializeError, Record, SeResult, Write, LEN_SIZE};
+#[cfg(feature = "tokio")]
+use crate::{AsyncSink, AsyncSubRecord, AsyncWrite};
+
+/// Write a record as a single frame. Returns the number of bytes written including the length.
+pub fn encode_framed<'raw, R, W>(record: &R, dest: &mut W) -> SeResult<usize>
+where
+    R: Record<'raw>,
+    W: Write,
+{
+    let size = record.serialized_size();
+    write_len(dest, size)?;
+    let written = record.serialize(dest)?;
+    debug_assert_eq!(written, size);
+    Ok(LEN_SIZE + written)
+}
+
+/// Stream a record as a single frame to an async writer. The length is taken from
+/// `serialized_size` and written first, then the body is streamed the same way as
+/// `Record::encode_async`. Returns the number of bytes written including the length.
+#[cfg(feature = "tokio")]
+pub fn encode_framed_async<'raw, 'a, R, W>(
+    record: &'a R,
+    dest: &'a mut W,
+) -> impl Future<Output = std::io::Result<usize>> + Send + 'a
+where
+    R: Record<'raw> + AsyncSubRecord<'raw>,
+    W: AsyncWrite + ?Sized,
+{
+    async move {
+        let size = record.serialized_size();
+        let mut sink = AsyncSink::new(dest);
+        write_len(&mut sink, size)?;
+        let written = record._serialize_chained_async(&mut sink).await?;
+        sink.finish().await?;
+        debug_assert_eq!(written, size);
+        Ok(LEN_SIZE + written)
+    }
+}
+
+/// Accumulates bytes as they arrive and splits them into complete frames.
+///
+/// ```
+/// use bebop::framing::FrameDecoder;
+///
+/// let mut decoder = FrameDecoder::new();
+/// decoder.push(&[3, 0, 0, 0, 1, 2]);
+/// assert_eq!(decoder.next_frame().unwrap(), None);
+/// decoder.push(&[3]);
+/// assert_eq!(decoder.next_frame().unwrap(), Some(&[1, 2, 3][..]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    /// Start of the first frame which has not been returned yet.
+    start: usize,
+    max_frame_size: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder {
+    /// Largest frame accepted by default, 16 MiB.
+    pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+    pub fn new() -> Self {
+        Self::with_max_frame_size(Self::DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Create a decoder which fails on any frame longer than `max_frame_size` bytes (not including
+    /// the length) instead of buffering it.
+    pub fn with_max_frame_size(max_frame_size: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            start: 0,
+            max_frame_size,
+        }
+    }
+
+    /// Add received bytes. Frames previously returned by `next_frame` are discarded.
+    pub fn push(&mut self, bytes: &[u8]) {
+        if self.start > 0 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Get the next complete frame without its length, or `None` if more bytes need to be pushed
+    /// first. If a frame is larger than the maximum size the stream cannot be recovered and
+    /// `FrameTooLarge` will be returned from then on.
+    pub fn next_frame(&mut self) -> DeResult<Option<&[u8]>> {
+        let pending = &self.buf[self.start..];
+        if pending.len() < LEN_SIZE {
+            return Ok(None);
+        }
+        let len = read_len(pending)?;
+        if len > self.max_frame_size {
+            return Err(DeserializeError::FrameTooLarge(len));
+        }
+        if pending.len() - LEN_SIZE < len {
+            return Ok(None);
+        }
+        let frame = self.start + LEN_SIZE..self.start + LEN_SIZE + len;
+        self.start = frame.end;
+        Ok(Some(&self.buf[frame]))
+    }
+
+    /// Number of bytes received which have not been returned as part of a frame yet.
+    pub fn pending(&self) -> usize {
+        self.buf.len() - self.start
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::Point;
+    use crate::Record;
+
+    #[test]
+    fn encode() {
+        let mut buf = Vec::new();
+        let written = encode_framed(&Point { x: 1, y: -1 }, &mut buf).unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(buf, [8, 0, 0, 0, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn encode_async() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut buf = Vec::new();
+        rt.block_on(Point { x: 1, y: -1 }.encode_async(&mut buf))
+            .unwrap();
+        let written = rt
+            .block_on(encode_framed_async(&Point { x: 2, y: 3 }, &mut buf))
+            .unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(
+            buf,
+            [1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 8, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn round_trip_partial_frames() {
+        let points = [
+            Point { x: 1, y: 2 },
+            Point { x: 3, y: 4 },
+            Point { x: 5, y: 6 },
+        ];
+        let mut buf = Vec::new();
+        for p in points.iter() {
+            encode_framed(p, &mut buf).unwrap();
+        }
+
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = Vec::new();
+        // deliver the stream in awkward chunks
+        for chunk in buf.chunks(5) {
+            decoder.push(chunk);
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                decoded.push(Point::deserialize(frame).unwrap());
+            }
+        }
+        assert_eq!(decoded, points);
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn empty_frame() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&[0, 0, 0, 0, 1, 0]);
+        assert_eq!(decoder.next_frame().unwrap(), Some(&[][..]));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        assert_eq!(decoder.pending(), 2);
+    }
+
+    #[test]
+    fn frame_too_large() {
+        let mut decoder = FrameDecoder::with_max_frame_size(4);
+        decoder.push(&[4, 0, 0, 0, 1, 2, 3, 4, 5, 0, 0, 0]);
+        assert_eq!(decoder.next_frame().unwrap(), Some(&[1, 2, 3, 4][..]));
+        // fails as soon as the length is known
+        assert!(matches!(
+            decoder.next_frame(),
+            Err(DeserializeError::FrameTooLarge(5))
+        ));
+    }
+}
@@ -1,15 +1,48 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+// re-exported for generated code which needs `Vec` and `String` without assuming `std`
+#[doc(hidden)]
+pub extern crate alloc;
+
 mod serialization;
 mod types;
 
+#[cfg(feature = "serde")]
+pub mod base64;
+#[cfg(feature = "debug_json")]
+pub mod debug_json;
+pub mod envelope;
+pub mod framing;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(test)]
+mod test_support;
+
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
 pub use bitflags::bitflags;
+#[cfg(feature = "serde")]
+pub use serde;
+#[cfg(feature = "smallvec")]
+pub use smallvec;
 pub use serialization::*;
 pub use types::*;
 
+/// The map type used by generated code, `std::collections::HashMap` or `hashbrown::HashMap` when
+/// building without `std`.
+#[cfg(feature = "std")]
+pub use std::collections::HashMap;
+#[cfg(all(not(feature = "std"), feature = "hashbrown"))]
+pub use hashbrown::HashMap;
+
+/// The traits and types most code using records needs, `use bebop::prelude::*;` brings them into
+/// scope. Generated code imports this as well.
 pub mod prelude {
-    pub use crate::serialization::{FixedSized, Record};
+    pub use crate::serialization::{DeResult, FixedSized, OwnedRecord, Record, SeResult, SubRecord};
     pub use crate::types::{Date, Guid};
     pub use crate::SliceWrapper;
+    #[cfg(feature = "tokio")]
+    pub use crate::AsyncSubRecord;
 }
-
-#[cfg(feature = "cargo")]
-pub mod cargo;
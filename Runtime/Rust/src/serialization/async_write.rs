@@ -0,0 +1,370 @@
+//! Streaming records to a tokio `AsyncWrite`, see `Record::encode_async`.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use tokio::io::AsyncWriteExt;
+
+use super::write_len_or;
+use crate::{
+    Date, Enum, FixedSized, Guid, HashMap, MaybeUnknown, SeResult, SerializeError, SliceWrapper,
+    SubRecord, SubRecordHashMapKey, Write, LEN_SIZE,
+};
+
+/// An async writer records can be streamed to. This is implemented for every
+/// `tokio::io::AsyncWrite` which is `Unpin` and `Send`, such as a `TcpStream`.
+pub trait AsyncWrite: tokio::io::AsyncWrite + Unpin + Send {}
+
+impl<W: tokio::io::AsyncWrite + Unpin + Send + ?Sized> AsyncWrite for W {}
+
+/// The future returned by `AsyncSubRecord::_serialize_chained_async`. It is boxed since records
+/// can contain themselves.
+pub type SerializeFuture<'a> = Pin<Box<dyn Future<Output = SeResult<usize>> + Send + 'a>>;
+
+/// Serialized bytes are gathered until there are this many before they are written to the async
+/// writer.
+pub const ASYNC_CHUNK_SIZE: usize = 8 * 1024;
+
+/// A buffer of at most about `ASYNC_CHUNK_SIZE` bytes in front of an async writer.
+///
+/// Values are serialized into the buffer with `Write` and it is written out with `flush_full`
+/// between fields and items once a chunk has been filled. Strings and raw arrays longer than what
+/// is left of a chunk are written to the async writer straight from the record.
+pub struct AsyncSink<'w, W: ?Sized> {
+    dest: &'w mut W,
+    buf: Vec<u8>,
+}
+
+impl<'w, W: AsyncWrite + ?Sized> AsyncSink<'w, W> {
+    pub fn new(dest: &'w mut W) -> Self {
+        Self {
+            dest,
+            buf: Vec::with_capacity(ASYNC_CHUNK_SIZE),
+        }
+    }
+
+    /// Write out the buffer if a whole chunk has been serialized into it.
+    pub async fn flush_full(&mut self) -> SeResult<()> {
+        if self.buf.len() >= ASYNC_CHUNK_SIZE {
+            self.write_buffered().await?;
+        }
+        Ok(())
+    }
+
+    /// Write bytes which are already in wire format, copying them into the buffer only if they fit
+    /// in the current chunk.
+    pub async fn write_bytes(&mut self, bytes: &[u8]) -> SeResult<()> {
+        if self.buf.len() + bytes.len() <= ASYNC_CHUNK_SIZE {
+            self.buf.extend_from_slice(bytes);
+            return Ok(());
+        }
+        self.write_buffered().await?;
+        self.dest.write_all(bytes).await?;
+        Ok(())
+    }
+
+    /// Write out whatever is left in the buffer. The async writer is not flushed.
+    pub async fn finish(mut self) -> SeResult<()> {
+        self.write_buffered().await
+    }
+
+    async fn write_buffered(&mut self) -> SeResult<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.dest.write_all(&self.buf).await?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W: ?Sized> Write for AsyncSink<'_, W> {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> SeResult<()> {
+        self.buf.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Internal trait for records which can be streamed to an async writer, generated code implements
+/// this when the `tokio` feature of the crate it is in is enabled.
+///
+/// The default serializes the whole value into the sink at once, which is what values of a fixed
+/// size use. Records, arrays and maps write each field or item in turn so no more than a chunk is
+/// buffered.
+pub trait AsyncSubRecord<'raw>: SubRecord<'raw> + Sync {
+    /// Should only be called from generated code!
+    /// Serialize this record through `dest`, returning the number of bytes written.
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        Box::pin(async move {
+            let written = self._serialize_chained(dest)?;
+            dest.flush_full().await?;
+            Ok(written)
+        })
+    }
+}
+
+/// Write an array. Items of a fixed size are serialized a chunk at a time and others are streamed
+/// one by one.
+pub(crate) fn serialize_items_async<'raw, 'a, T, W>(
+    items: &'a [T],
+    dest: &'a mut AsyncSink<'_, W>,
+) -> SerializeFuture<'a>
+where
+    T: AsyncSubRecord<'raw>,
+    W: AsyncWrite + ?Sized,
+{
+    Box::pin(async move {
+        write_len_or(dest, items.len(), |len, max| SerializeError::ArrayTooLong {
+            len,
+            max,
+        })?;
+        let mut i = LEN_SIZE;
+        if let Some(size) = T::EXACT_SERIALIZED_SIZE {
+            for chunk in items.chunks((ASYNC_CHUNK_SIZE / size.max(1)).max(1)) {
+                for v in chunk {
+                    i += v._serialize_chained(dest)?;
+                }
+                dest.flush_full().await?;
+            }
+        } else {
+            for v in items {
+                i += v._serialize_chained_async(dest).await?;
+            }
+        }
+        Ok(i)
+    })
+}
+
+impl<'raw> AsyncSubRecord<'raw> for &'raw str {
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        Box::pin(async move {
+            let raw = self.as_bytes();
+            write_len_or(dest, raw.len(), |len, max| SerializeError::StringTooLong {
+                len,
+                max,
+            })?;
+            dest.write_bytes(raw).await?;
+            Ok(LEN_SIZE + raw.len())
+        })
+    }
+}
+
+impl<'raw> AsyncSubRecord<'raw> for String {
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        Box::pin(async move { self.as_str()._serialize_chained_async(dest).await })
+    }
+}
+
+macro_rules! impl_vec_async {
+    ($($alloc:ident)?) => {
+        impl<'raw, T $(, $alloc)?> AsyncSubRecord<'raw> for Vec<T $(, $alloc)?>
+        where
+            T: AsyncSubRecord<'raw>,
+            $($alloc: core::alloc::Allocator + Default + Sync,)?
+        {
+            fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+                &'a self,
+                dest: &'a mut AsyncSink<'_, W>,
+            ) -> SerializeFuture<'a> {
+                serialize_items_async(self, dest)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "allocator_api"))]
+impl_vec_async!();
+#[cfg(feature = "allocator_api")]
+impl_vec_async!(A);
+
+#[cfg(feature = "smallvec")]
+impl<'raw, A> AsyncSubRecord<'raw> for smallvec::SmallVec<A>
+where
+    A: smallvec::Array + Sync,
+    A::Item: AsyncSubRecord<'raw>,
+{
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        serialize_items_async(self, dest)
+    }
+}
+
+impl<'raw, K, V> AsyncSubRecord<'raw> for HashMap<K, V>
+where
+    K: SubRecordHashMapKey<'raw> + AsyncSubRecord<'raw>,
+    V: AsyncSubRecord<'raw>,
+{
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        Box::pin(async move {
+            #[cfg(feature = "sorted_maps")]
+            use itertools::Itertools;
+
+            write_len_or(dest, self.len(), |len, max| SerializeError::MapTooLong {
+                len,
+                max,
+            })?;
+            let mut i = LEN_SIZE;
+
+            #[cfg(feature = "sorted_maps")]
+            let iter = self
+                .iter()
+                .sorted_unstable_by(|(k1, _), (k2, _)| k1.cmp(k2));
+            #[cfg(not(feature = "sorted_maps"))]
+            let iter = self.iter();
+
+            for (k, v) in iter {
+                i += k._serialize_chained_async(dest).await?;
+                i += v._serialize_chained_async(dest).await?;
+            }
+            Ok(i)
+        })
+    }
+}
+
+impl<'raw, T> AsyncSubRecord<'raw> for SliceWrapper<'raw, T>
+where
+    T: FixedSized + AsyncSubRecord<'raw>,
+{
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        Box::pin(async move {
+            match *self {
+                SliceWrapper::Raw(raw) => {
+                    write_len_or(dest, self.len(), |len, max| SerializeError::ArrayTooLong {
+                        len,
+                        max,
+                    })?;
+                    dest.write_bytes(raw).await?;
+                    Ok(raw.len() + LEN_SIZE)
+                }
+                SliceWrapper::Cooked(ary) => serialize_items_async(ary, dest).await,
+            }
+        })
+    }
+}
+
+impl<'raw, T> AsyncSubRecord<'raw> for Box<T>
+where
+    T: AsyncSubRecord<'raw>,
+{
+    fn _serialize_chained_async<'a, W: AsyncWrite + ?Sized>(
+        &'a self,
+        dest: &'a mut AsyncSink<'_, W>,
+    ) -> SerializeFuture<'a> {
+        (**self)._serialize_chained_async(dest)
+    }
+}
+
+impl<'raw, E> AsyncSubRecord<'raw> for MaybeUnknown<E>
+where
+    E: Enum + Sync,
+    E::Repr: Sync,
+{
+}
+impl<'raw> AsyncSubRecord<'raw> for Guid {}
+impl<'raw> AsyncSubRecord<'raw> for Date {}
+impl<'raw> AsyncSubRecord<'raw> for bool {}
+impl<'raw> AsyncSubRecord<'raw> for u8 {}
+impl<'raw> AsyncSubRecord<'raw> for u16 {}
+impl<'raw> AsyncSubRecord<'raw> for i16 {}
+impl<'raw> AsyncSubRecord<'raw> for u32 {}
+impl<'raw> AsyncSubRecord<'raw> for i32 {}
+impl<'raw> AsyncSubRecord<'raw> for f32 {}
+impl<'raw> AsyncSubRecord<'raw> for u64 {}
+impl<'raw> AsyncSubRecord<'raw> for i64 {}
+impl<'raw> AsyncSubRecord<'raw> for f64 {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use core::task::{Context, Poll};
+
+    /// Keeps each write separately.
+    #[derive(Default)]
+    struct Writes(Vec<Vec<u8>>);
+
+    impl tokio::io::AsyncWrite for Writes {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.0.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn stream<'raw, T: AsyncSubRecord<'raw>>(value: &T) -> Writes {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut writes = Writes::default();
+        rt.block_on(async {
+            let mut sink = AsyncSink::new(&mut writes);
+            let written = value._serialize_chained_async(&mut sink).await.unwrap();
+            sink.finish().await.unwrap();
+            assert_eq!(written, value.serialized_size());
+        });
+        let mut sync = Vec::new();
+        value._serialize_chained(&mut sync).unwrap();
+        assert_eq!(writes.0.concat(), sync);
+        writes
+    }
+
+    #[test]
+    fn items_are_written_in_chunks() {
+        let writes = stream(&vec![7u32; ASYNC_CHUNK_SIZE]);
+        let sizes: Vec<_> = writes.0.iter().map(Vec::len).collect();
+        assert_eq!(
+            sizes,
+            [
+                LEN_SIZE + ASYNC_CHUNK_SIZE,
+                ASYNC_CHUNK_SIZE,
+                ASYNC_CHUNK_SIZE,
+                ASYNC_CHUNK_SIZE
+            ]
+        );
+    }
+
+    #[test]
+    fn long_strings_are_not_buffered() {
+        let long = "a".repeat(ASYNC_CHUNK_SIZE * 2);
+        let writes = stream(&vec![String::from("short"), long.clone(), String::new()]);
+        let sizes: Vec<_> = writes.0.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [LEN_SIZE * 3 + 5, long.len(), LEN_SIZE]);
+    }
+
+    #[test]
+    fn small_values_are_written_at_once() {
+        let writes = stream(&vec![vec![1u8, 2, 3], vec![], vec![4]]);
+        assert_eq!(writes.0.len(), 1);
+    }
+}
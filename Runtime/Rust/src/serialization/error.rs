@@ -1,5 +1,5 @@
-use std::error::Error;
-use std::fmt::{Debug, Display, Formatter};
+use core::fmt::{Debug, Display, Formatter};
+#[cfg(feature = "std")]
 use std::io;
 
 pub enum DeserializeError {
@@ -8,21 +8,56 @@ pub enum DeserializeError {
     MoreDataExpected(usize),
     /// The data seems to be invalid and cannot be deserialized.
     CorruptFrame,
-    /// There was an issue with a string encoding
-    Utf8EncodingError(std::str::Utf8Error),
-    InvalidEnumDiscriminator(i128),
+    /// A string is not valid UTF-8. `field_offset` is where the string, including its length
+    /// prefix, starts within the data being decoded and `utf8_error` says where in the string the
+    /// invalid bytes are.
+    InvalidUtf8 {
+        field_offset: usize,
+        utf8_error: core::str::Utf8Error,
+    },
+    /// An enum value is not one of the members of the enum, contains the value.
+    InvalidEnumDiscriminant { value: i128 },
     /// A message type had multiple definitions for the same field
     DuplicateMessageField,
+    /// The reader being decoded from failed
+    #[cfg(feature = "std")]
+    IoError(io::Error),
+    /// A frame was longer than the allowed maximum, contains the length of the frame.
+    FrameTooLarge(usize),
+    /// The data was written with a schema which has a different wire hash.
+    SchemaMismatch { expected: u64, found: u64 },
+    /// Records are nested deeper than `DecodeOptions::max_depth` allows.
+    DepthLimitExceeded,
+}
+
+impl DeserializeError {
+    /// Move the position of an error found while decoding a value which starts `offset` bytes
+    /// into the data of the value containing it, so the position is relative to the outer value.
+    /// Errors without a position are returned unchanged.
+    #[inline]
+    pub fn at_offset(self, offset: usize) -> Self {
+        match self {
+            DeserializeError::InvalidUtf8 {
+                field_offset,
+                utf8_error,
+            } => DeserializeError::InvalidUtf8 {
+                field_offset: field_offset + offset,
+                utf8_error,
+            },
+            err => err,
+        }
+    }
 }
 
-impl From<std::str::Utf8Error> for DeserializeError {
-    fn from(err: std::str::Utf8Error) -> Self {
-        DeserializeError::Utf8EncodingError(err)
+#[cfg(feature = "std")]
+impl From<io::Error> for DeserializeError {
+    fn from(err: io::Error) -> Self {
+        DeserializeError::IoError(err)
     }
 }
 
 impl Display for DeserializeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             DeserializeError::MoreDataExpected(bytes) => {
                 write!(f, "Deserialization Error, {} more bytes expected", bytes)
@@ -30,62 +65,180 @@ impl Display for DeserializeError {
             DeserializeError::CorruptFrame => {
                 write!(f, "Deserialization Error, corrupt frame data")
             }
-            DeserializeError::Utf8EncodingError(err) => {
-                write!(f, "Deserialization Error, Utf-8 encoding error: {}", err)
-            }
-            DeserializeError::InvalidEnumDiscriminator(d) => {
-                write!(f, "Deserialization Error, Invalid enum discriminator {}", d)
+            DeserializeError::InvalidUtf8 {
+                field_offset,
+                utf8_error,
+            } => write!(
+                f,
+                "Deserialization Error, string at byte {} is not valid Utf-8: {}",
+                field_offset, utf8_error
+            ),
+            DeserializeError::InvalidEnumDiscriminant { value } => {
+                write!(
+                    f,
+                    "Deserialization Error, Invalid enum discriminant {}",
+                    value
+                )
             }
             DeserializeError::DuplicateMessageField => write!(
                 f,
                 "Deserialization Error, duplicate message field encountered"
             ),
+            #[cfg(feature = "std")]
+            DeserializeError::IoError(err) => write!(f, "Deserialization Error, IO Error: {}", err),
+            DeserializeError::FrameTooLarge(len) => {
+                write!(
+                    f,
+                    "Deserialization Error, frame of {} bytes is too large",
+                    len
+                )
+            }
+            DeserializeError::SchemaMismatch { expected, found } => write!(
+                f,
+                "Deserialization Error, expected schema wire hash {:#018x} but found {:#018x}",
+                expected, found
+            ),
+            DeserializeError::DepthLimitExceeded => {
+                write!(f, "Deserialization Error, records are nested too deeply")
+            }
         }
     }
 }
 
 impl Debug for DeserializeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         (self as &dyn Display).fmt(f)
     }
 }
 
-impl Error for DeserializeError {}
+#[cfg(feature = "std")]
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializeError::InvalidUtf8 { utf8_error, .. } => Some(utf8_error),
+            DeserializeError::IoError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 pub enum SerializeError {
-    IoError(io::Error),
-    LengthExceeds32Bits,
+    /// The writer being serialized to failed
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// A string is longer than can be written, contains its length in bytes and the maximum.
+    StringTooLong {
+        len: usize,
+        max: usize,
+    },
+    /// An array has more items than can be written, contains its length and the maximum.
+    ArrayTooLong {
+        len: usize,
+        max: usize,
+    },
+    /// A map has more entries than can be written, contains its length and the maximum.
+    MapTooLong {
+        len: usize,
+        max: usize,
+    },
+    /// The body of a message or union, or a frame, is longer than can be written, contains its
+    /// length in bytes and the maximum.
+    RecordTooLong {
+        len: usize,
+        max: usize,
+    },
     CannotSerializeUnknownUnion,
+    /// The slice being written to is too short, contains the number of bytes which needed to be
+    /// written and how many were available.
+    BufferTooSmall {
+        needed: usize,
+        available: usize,
+    },
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for SerializeError {
     fn from(err: io::Error) -> Self {
-        SerializeError::IoError(err)
+        SerializeError::Io(err)
     }
 }
 
 impl Display for SerializeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            SerializeError::IoError(err) => write!(f, "Serialization Error, IO Error: {}", err),
-            SerializeError::LengthExceeds32Bits => write!(
+            #[cfg(feature = "std")]
+            SerializeError::Io(err) => write!(f, "Serialization Error, IO Error: {}", err),
+            SerializeError::StringTooLong { len, max } => write!(
+                f,
+                "Serialization Error, string of {} bytes is longer than the maximum of {}",
+                len, max
+            ),
+            SerializeError::ArrayTooLong { len, max } => write!(
+                f,
+                "Serialization Error, array of {} items is longer than the maximum of {}",
+                len, max
+            ),
+            SerializeError::MapTooLong { len, max } => write!(
+                f,
+                "Serialization Error, map of {} entries is longer than the maximum of {}",
+                len, max
+            ),
+            SerializeError::RecordTooLong { len, max } => write!(
                 f,
-                "Serialization Error, length value does not fit within 32 bits"
+                "Serialization Error, record of {} bytes is longer than the maximum of {}",
+                len, max
             ),
             SerializeError::CannotSerializeUnknownUnion => {
                 write!(f, "Serialization Error, cannot write unknown union value")
             }
+            SerializeError::BufferTooSmall { needed, available } => write!(
+                f,
+                "Serialization Error, {} bytes needed but only {} are available",
+                needed, available
+            ),
         }
     }
 }
 
 impl Debug for SerializeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         (self as &dyn Display).fmt(f)
     }
 }
 
-impl Error for SerializeError {}
+#[cfg(feature = "std")]
+impl std::error::Error for SerializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerializeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// IO errors are unwrapped, anything else is an `InvalidData` error.
+#[cfg(feature = "std")]
+impl From<SerializeError> for io::Error {
+    fn from(err: SerializeError) -> Self {
+        match err {
+            SerializeError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// A required field was not set when building a struct, contains the name of the field.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MissingFieldError(pub &'static str);
+
+impl Display for MissingFieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Missing required field `{}`", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MissingFieldError {}
 
 pub type DeResult<T> = core::result::Result<T, DeserializeError>;
 pub type SeResult<T> = core::result::Result<T, SerializeError>;
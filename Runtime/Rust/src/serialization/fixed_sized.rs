@@ -1,18 +1,27 @@
-use crate::{Guid, Date, LEN_SIZE};
+use crate::{Guid, Date, Enum, MaybeUnknown, LEN_SIZE};
 
 /// A trait which should be given to any type which will always take up the same amount of space
 /// when serialized. This goes one step beyond the `Sized` trait which only requires it to take up
 /// a set amount of space on the stack. This is saying that the total data it contains and points to
 /// must always be of the exact same size for every instance.
 pub trait FixedSized: Copy + Sized {
-    const SERIALIZED_SIZE: usize = std::mem::size_of::<Self>();
+    const SERIALIZED_SIZE: usize = core::mem::size_of::<Self>();
+
+    /// Whether values are stored in memory exactly as they are serialized and any serialized
+    /// bytes are a valid value, so arrays can be converted to and from raw data without looking at
+    /// each item. This is not the case for `bool`s or enums, which are checked item by item.
+    const IS_WIRE_FORMAT: bool = true;
 }
 
 impl FixedSized for Guid {}
 impl FixedSized for Date {}
 
-impl FixedSized for bool {}
-impl FixedSized for char {}
+impl FixedSized for bool {
+    const IS_WIRE_FORMAT: bool = false;
+}
+impl FixedSized for char {
+    const IS_WIRE_FORMAT: bool = false;
+}
 
 impl FixedSized for i8 {}
 impl FixedSized for u8 {}
@@ -29,6 +38,12 @@ impl FixedSized for isize {}
 impl FixedSized for i128 {}
 impl FixedSized for u128 {}
 
+// the value read is stored along with whether it is a member, so this is never the wire format
+impl<E: Enum> FixedSized for MaybeUnknown<E> {
+    const SERIALIZED_SIZE: usize = E::Repr::SERIALIZED_SIZE;
+    const IS_WIRE_FORMAT: bool = false;
+}
+
 impl<T: FixedSized, const S: usize> FixedSized for [T; S] {
     const SERIALIZED_SIZE: usize = T::SERIALIZED_SIZE * S + LEN_SIZE;
 }
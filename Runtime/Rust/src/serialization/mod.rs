@@ -1,28 +1,50 @@
 // TODO: Create an Array wrapper and a String wrapper to enable the user to use owned types or slices
 // TODO: Test "unchecked" feature
 
-use std::collections::HashMap;
-use std::convert::TryInto;
-use std::hash::Hash;
-use std::io::Write;
-
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use core::hash::Hash;
+#[cfg(feature = "tokio")]
+use core::future::Future;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[cfg(feature = "tokio")]
+pub use async_write::*;
 pub use error::*;
 pub use fixed_sized::*;
+pub use write::*;
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
+use crate::HashMap;
 
-use crate::{test_serialization, Date, Guid, SliceWrapper};
+use crate::{test_serialization, Date, Enum, Guid, MaybeUnknown, SliceWrapper};
 // not sure why but this is "unused"
 #[allow(unused_imports)]
 use crate::collection;
+#[cfg(test)]
+use alloc::vec;
 
+#[cfg(feature = "tokio")]
+pub mod async_write;
 pub mod error;
 pub mod fixed_sized;
 pub mod testing;
+pub mod write;
 
 pub type Len = u32;
 /// Size of length data
 pub const LEN_SIZE: usize = core::mem::size_of::<Len>();
+/// Largest length of a string, array, map, or message body
+pub const MAX_LEN: usize = Len::MAX as usize;
 /// Size of an enum
 pub const ENUM_SIZE: usize = 4;
+/// Size of the header written by `Record::encode_checked`
+pub const WIRE_HASH_SIZE: usize = core::mem::size_of::<u64>();
 
 /// Bebop message type which can be serialized and deserialized.
 pub trait Record<'raw>: SubRecord<'raw> {
@@ -34,14 +56,166 @@ pub trait Record<'raw>: SubRecord<'raw> {
         Ok(Self::_deserialize_chained(raw)?.1)
     }
 
+    /// Deserialize this record with limits for data which may be malicious, `deserialize` uses
+    /// the default options.
+    #[inline]
+    fn deserialize_with_options(raw: &'raw [u8], options: &DecodeOptions) -> DeResult<Self> {
+        Ok(Self::_deserialize_chained_with_depth(raw, options.max_depth)?.1)
+    }
+
+    /// Deserialize this record from the front of `raw` and return it with the number of bytes
+    /// read, which is where the next record starts if several are written back to back.
+    #[inline(always)]
+    fn deserialize_with_len(raw: &'raw [u8]) -> DeResult<(Self, usize)> {
+        let (read, v) = Self::_deserialize_chained(raw)?;
+        Ok((v, read))
+    }
+
+    /// Deserialize this record in place of `self`, reusing the allocations of its arrays, maps,
+    /// and owned strings where possible. This avoids allocating when decoding many records of the
+    /// same type in a loop. If this fails `self` is left in a valid but unspecified state.
+    #[inline(always)]
+    fn deserialize_into(&mut self, raw: &'raw [u8]) -> DeResult<()> {
+        self._deserialize_chained_into(raw)?;
+        Ok(())
+    }
+
     /// Serialize this record. It is highly recommend to use a buffered writer.
     #[inline(always)]
     fn serialize<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
         self._serialize_chained(dest)
     }
 
-    // TODO: support async serialization
-    // fn serialize_async<W: AsyncWrite>(&self, dest: &mut W) -> impl Future<Type=SeResult<usize>>;
+    /// Serialize this record into a new buffer, allocating exactly once using `serialized_size`.
+    ///
+    /// Writing to memory cannot fail, so this only errors if a length does not fit within 32 bits
+    /// or the record contains an `Unknown` union value.
+    fn encode_to_vec(&self) -> SeResult<Vec<u8>> {
+        let size = self.serialized_size();
+        let mut buf = Vec::with_capacity(size);
+        self._serialize_chained(&mut buf)?;
+        debug_assert_eq!(buf.len(), size, "serialized_size does not match the bytes written");
+        Ok(buf)
+    }
+
+    /// Serialize this record into the front of `buf` without allocating and return the number of
+    /// bytes written. Use `serialized_size` to know how large the buffer needs to be, if it is too
+    /// short a `BufferTooSmall` error is returned and nothing is written.
+    fn encode_into(&self, buf: &mut [u8]) -> SeResult<usize> {
+        let size = self.serialized_size();
+        if buf.len() < size {
+            return Err(SerializeError::BufferTooSmall {
+                needed: size,
+                available: buf.len(),
+            });
+        }
+        let mut dest = &mut buf[..size];
+        let written = self._serialize_chained(&mut dest)?;
+        debug_assert_eq!(written, size, "serialized_size does not match the bytes written");
+        Ok(written)
+    }
+
+    /// Serialize this record after a header containing the wire hash of its schema, such as the
+    /// generated `<NAME>_WIRE_HASH` constant, so the receiver can use `decode_checked` to detect
+    /// a different schema. Returns the number of bytes written including the 8 byte header.
+    fn encode_checked<W: Write>(&self, wire_hash: u64, dest: &mut W) -> SeResult<usize> {
+        dest.write_all(&wire_hash.to_le_bytes())?;
+        Ok(WIRE_HASH_SIZE + self._serialize_chained(dest)?)
+    }
+
+    /// Deserialize a record written by `encode_checked`, returning a `SchemaMismatch` error if it
+    /// was written with a different wire hash than `expected_hash`.
+    fn decode_checked(raw: &'raw [u8], expected_hash: u64) -> DeResult<Self> {
+        if raw.len() < WIRE_HASH_SIZE {
+            return Err(DeserializeError::MoreDataExpected(WIRE_HASH_SIZE - raw.len()));
+        }
+        let found = u64::from_le_bytes(raw[..WIRE_HASH_SIZE].try_into().unwrap());
+        if found != expected_hash {
+            return Err(DeserializeError::SchemaMismatch {
+                expected: expected_hash,
+                found,
+            });
+        }
+        Self::deserialize(&raw[WIRE_HASH_SIZE..]).map_err(|err| err.at_offset(WIRE_HASH_SIZE))
+    }
+
+    /// Stream this record to an async writer such as a tokio `TcpStream`.
+    ///
+    /// Fields and array items are written in turn through an `AsyncSink`, which holds no more
+    /// than about `ASYNC_CHUNK_SIZE` bytes at a time and writes long strings and byte arrays
+    /// straight from the record, so the record is never serialized into a buffer of its own. Use
+    /// `framing::encode_framed_async` to write it as a length prefixed frame.
+    #[cfg(feature = "tokio")]
+    fn encode_async<'a, W>(
+        &'a self,
+        dest: &'a mut W,
+    ) -> impl Future<Output = std::io::Result<()>> + Send + 'a
+    where
+        W: AsyncWrite + ?Sized,
+        Self: AsyncSubRecord<'raw>,
+    {
+        async move {
+            let mut sink = AsyncSink::new(dest);
+            self._serialize_chained_async(&mut sink).await?;
+            sink.finish().await?;
+            Ok(())
+        }
+    }
+}
+
+/// A record which does not borrow from the buffer it was read from, such as the definitions in the
+/// generated `owned` module. This is implemented automatically.
+pub trait OwnedRecord: for<'raw> Record<'raw> {
+    #[cfg(feature = "std")]
+    /// Deserialize this record directly from a reader. Only the bytes belonging to this record are
+    /// consumed, so the reader will be positioned at whatever comes next.
+    ///
+    /// Bytes are read in steps using the `MoreDataExpected` hint of each attempt, messages and
+    /// unions are read in at most two steps since their length comes first. If the stream ends
+    /// early a `MoreDataExpected` error is returned. The hint comes from lengths in the data, so
+    /// memory is only allocated as bytes actually arrive rather than for what a length claims.
+    ///
+    /// Each step decodes the bytes read so far from the start again. Arrays of strings or other
+    /// variable sized values need a step for each item, taking time quadratic in their length,
+    /// so wrap these in a message or read them into a buffer and use `deserialize` instead.
+    fn decode_from_reader<R: Read>(reader: &mut R) -> DeResult<Self> {
+        let mut buf = Vec::new();
+        let mut missing = <Self as SubRecord>::MIN_SERIALIZED_SIZE;
+        loop {
+            read_more(reader, &mut buf, missing)?;
+            match <Self as SubRecord>::_deserialize_chained(&buf) {
+                Ok((read, v)) => {
+                    debug_assert_eq!(read, buf.len());
+                    return Ok(v);
+                }
+                Err(DeserializeError::MoreDataExpected(n)) => missing = n.max(1),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<T> OwnedRecord for T where T: for<'raw> Record<'raw> {}
+
+/// The most `read_more` allocates ahead of the bytes which fill it.
+#[cfg(feature = "std")]
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Append exactly `n` bytes from the reader to `buf`. `n` may come from a length in untrusted
+/// data, so the bytes are read in chunks instead of reserving all of them up front.
+#[cfg(feature = "std")]
+fn read_more<R: Read>(reader: &mut R, buf: &mut Vec<u8>, n: usize) -> DeResult<()> {
+    let mut remaining = n;
+    while remaining > 0 {
+        let chunk = remaining.min(READ_CHUNK_SIZE);
+        buf.reserve(chunk);
+        let read = reader.by_ref().take(chunk as u64).read_to_end(buf)?;
+        remaining -= read;
+        if read < chunk {
+            return Err(DeserializeError::MoreDataExpected(remaining));
+        }
+    }
+    Ok(())
 }
 
 /// Internal trait used to reduce the amount of code that needs to be generated.
@@ -63,6 +237,99 @@ pub trait SubRecord<'raw>: Sized {
     /// Deserialize this object as a sub component of a larger message. Returns a tuple of
     /// (bytes_read, deserialized_value).
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)>;
+
+    /// Should only be called from generated code!
+    /// Deserialize this object in place of `self`, reusing any allocations it already has. Returns
+    /// the number of bytes read. If this fails `self` is left in a valid but unspecified state.
+    #[inline]
+    fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+        let (read, v) = Self::_deserialize_chained(raw)?;
+        *self = v;
+        Ok(read)
+    }
+
+    /// Should only be called from generated code!
+    /// Same as `_deserialize_chained` where `depth` is how many more records can be nested within
+    /// this value. Records decode their fields with one less and give a `DepthLimitExceeded` error
+    /// when there is none left, collections pass it on to their items. Values which can't contain
+    /// records can ignore it.
+    #[inline]
+    fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> DeResult<(usize, Self)> {
+        let _ = depth;
+        Self::_deserialize_chained(raw)
+    }
+
+    /// Should only be called from generated code!
+    /// Same as `_deserialize_chained_into` with a depth limit, see
+    /// `_deserialize_chained_with_depth`.
+    #[inline]
+    fn _deserialize_chained_into_with_depth(
+        &mut self,
+        raw: &'raw [u8],
+        depth: usize,
+    ) -> DeResult<usize> {
+        let (read, v) = Self::_deserialize_chained_with_depth(raw, depth)?;
+        *self = v;
+        Ok(read)
+    }
+}
+
+/// How many records can be nested within each other when decoding unless the limit is changed
+/// with `DecodeOptions`.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Limits for decoding data which may be malicious, see `Record::deserialize_with_options`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// How many records can be nested within each other, such as a union containing itself
+    /// through an array. Decoding fails with `DepthLimitExceeded` rather than overflowing the
+    /// stack on data which goes deeper.
+    pub max_depth: usize,
+}
+
+impl DecodeOptions {
+    pub const fn new() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Set how many records can be nested within each other.
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The depth left for the fields of a record which is being decoded with `depth` left. Used by
+/// generated code.
+#[doc(hidden)]
+#[inline]
+pub fn nested_depth(depth: usize) -> DeResult<usize> {
+    depth
+        .checked_sub(1)
+        .ok_or(DeserializeError::DepthLimitExceeded)
+}
+
+/// Deserialize a value, reusing the allocations of `old` if there is one. Used by generated code
+/// for message fields.
+#[doc(hidden)]
+#[inline]
+pub fn deserialize_reusing<'raw, T: SubRecord<'raw>>(
+    old: Option<T>,
+    raw: &'raw [u8],
+    depth: usize,
+) -> DeResult<(usize, T)> {
+    match old {
+        Some(mut v) => Ok((v._deserialize_chained_into_with_depth(raw, depth)?, v)),
+        None => T::_deserialize_chained_with_depth(raw, depth),
+    }
 }
 
 impl<'raw> SubRecord<'raw> for &'raw str {
@@ -75,24 +342,32 @@ impl<'raw> SubRecord<'raw> for &'raw str {
 
     fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
         let raw = self.as_bytes();
-        write_len(dest, raw.len())?;
+        write_len_or(dest, raw.len(), |len, max| SerializeError::StringTooLong { len, max })?;
         dest.write_all(raw)?;
         Ok(LEN_SIZE + raw.len())
     }
 
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
         let len = read_len(raw)?;
-        let raw_str = &raw[LEN_SIZE..len + LEN_SIZE];
-        if raw_str.len() < len {
-            return Err(DeserializeError::MoreDataExpected(len - raw_str.len()));
+        // compared without adding to the length so it can't overflow on 32 bit targets
+        let available = raw.len() - LEN_SIZE;
+        if available < len {
+            return Err(DeserializeError::MoreDataExpected(len - available));
         }
+        let raw_str = &raw[LEN_SIZE..len + LEN_SIZE];
         #[cfg(not(feature = "unchecked"))]
         {
-            Ok((len + LEN_SIZE, std::str::from_utf8(raw_str)?))
+            let s = core::str::from_utf8(raw_str).map_err(|utf8_error| {
+                DeserializeError::InvalidUtf8 {
+                    field_offset: 0,
+                    utf8_error,
+                }
+            })?;
+            Ok((len + LEN_SIZE, s))
         }
         #[cfg(feature = "unchecked")]
         unsafe {
-            Ok((len + LEN_SIZE, std::str::from_utf8_unchecked(raw_str)))
+            Ok((len + LEN_SIZE, core::str::from_utf8_unchecked(raw_str)))
         }
     }
 }
@@ -114,21 +389,243 @@ impl<'raw> SubRecord<'raw> for String {
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
         <&str>::_deserialize_chained(raw).map(|(c, s)| (c, s.to_owned()))
     }
+
+    fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+        let (read, s) = <&str>::_deserialize_chained(raw)?;
+        self.clear();
+        self.push_str(s);
+        Ok(read)
+    }
+
+    #[inline]
+    fn _deserialize_chained_into_with_depth(
+        &mut self,
+        raw: &'raw [u8],
+        _depth: usize,
+    ) -> DeResult<usize> {
+        self._deserialize_chained_into(raw)
+    }
+}
+
+/// Generated code boxes message fields which lead back to the message they are in, a box is written
+/// the same as the value in it.
+impl<'raw, T> SubRecord<'raw> for Box<T>
+where
+    T: SubRecord<'raw>,
+{
+    const MIN_SERIALIZED_SIZE: usize = T::MIN_SERIALIZED_SIZE;
+    const EXACT_SERIALIZED_SIZE: Option<usize> = T::EXACT_SERIALIZED_SIZE;
+
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        (**self).serialized_size()
+    }
+
+    #[inline]
+    fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+        (**self)._serialize_chained(dest)
+    }
+
+    #[inline]
+    fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        T::_deserialize_chained(raw).map(|(read, v)| (read, Box::new(v)))
+    }
+
+    #[inline]
+    fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+        (**self)._deserialize_chained_into(raw)
+    }
+
+    #[inline]
+    fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> DeResult<(usize, Self)> {
+        T::_deserialize_chained_with_depth(raw, depth).map(|(read, v)| (read, Box::new(v)))
+    }
+
+    #[inline]
+    fn _deserialize_chained_into_with_depth(
+        &mut self,
+        raw: &'raw [u8],
+        depth: usize,
+    ) -> DeResult<usize> {
+        (**self)._deserialize_chained_into_with_depth(raw, depth)
+    }
 }
 
 test_serialization!(serialization_str, &str, "some random string", 18 + LEN_SIZE);
 test_serialization!(serialization_str_long, &str, "some random string that is a bit longer because I had seem some errors that seemed exclusive to longer string values.", 117 + LEN_SIZE);
 test_serialization!(serialization_str_empty, &str, "", LEN_SIZE);
+test_serialization!(serialization_box, Box<&str>, Box::new("boxed"), 5 + LEN_SIZE);
+
+/// Arrays are decoded into a new `Vec`. With the `allocator_api` feature any allocator which can be
+/// made with `Default` is allowed, such as a handle to a per-request arena, so decoded arrays are
+/// placed in it.
+macro_rules! impl_vec {
+    ($($alloc:ident)?) => {
+        impl<'raw, T $(, $alloc)?> SubRecord<'raw> for Vec<T $(, $alloc)?>
+        where
+            T: SubRecord<'raw>,
+            $($alloc: core::alloc::Allocator + Default,)?
+        {
+            const MIN_SERIALIZED_SIZE: usize = LEN_SIZE;
 
-impl<'raw, T> SubRecord<'raw> for Vec<T>
+            #[inline]
+            fn serialized_size(&self) -> usize {
+                if let Some(size) = T::EXACT_SERIALIZED_SIZE {
+                    self.len() * size + LEN_SIZE
+                } else {
+                    self.iter().fold(0, |acc, v| acc + v.serialized_size()) + LEN_SIZE
+                }
+            }
+
+            fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+                write_len_or(dest, self.len(), |len, max| SerializeError::ArrayTooLong {
+                    len,
+                    max,
+                })?;
+                let mut i = LEN_SIZE;
+                for v in self.iter() {
+                    i += v._serialize_chained(dest)?;
+                }
+                Ok(i)
+            }
+
+            #[inline]
+            fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+                Self::_deserialize_chained_with_depth(raw, DEFAULT_MAX_DEPTH)
+            }
+
+            #[inline]
+            fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+                self._deserialize_chained_into_with_depth(raw, DEFAULT_MAX_DEPTH)
+            }
+
+            fn _deserialize_chained_with_depth(
+                raw: &'raw [u8],
+                depth: usize,
+            ) -> DeResult<(usize, Self)> {
+                let len = read_len(raw)?;
+                check_len(raw, len, T::MIN_SERIALIZED_SIZE)?;
+                let mut i = LEN_SIZE;
+                let mut v = impl_vec!(@with_capacity len $($alloc)?);
+                for _ in 0..len {
+                    let (read, t) = T::_deserialize_chained_with_depth(&raw[i..], depth)
+                        .map_err(|err| err.at_offset(i))?;
+                    i += read;
+                    v.push(t);
+                }
+                Ok((i, v))
+            }
+
+            // elements which are already in the vector are decoded in place
+            fn _deserialize_chained_into_with_depth(
+                &mut self,
+                raw: &'raw [u8],
+                depth: usize,
+            ) -> DeResult<usize> {
+                let len = read_len(raw)?;
+                check_len(raw, len, T::MIN_SERIALIZED_SIZE)?;
+                let mut i = LEN_SIZE;
+                self.truncate(len);
+                for v in self.iter_mut() {
+                    i += v
+                        ._deserialize_chained_into_with_depth(&raw[i..], depth)
+                        .map_err(|err| err.at_offset(i))?;
+                }
+                self.reserve(len - self.len());
+                while self.len() < len {
+                    let (read, t) = T::_deserialize_chained_with_depth(&raw[i..], depth)
+                        .map_err(|err| err.at_offset(i))?;
+                    i += read;
+                    self.push(t);
+                }
+                Ok(i)
+            }
+        }
+    };
+    (@with_capacity $len:ident $alloc:ident) => { Vec::with_capacity_in($len, $alloc::default()) };
+    (@with_capacity $len:ident) => { Vec::with_capacity($len) };
+}
+
+#[cfg(not(feature = "allocator_api"))]
+impl_vec!();
+#[cfg(feature = "allocator_api")]
+impl_vec!(A);
+
+/// Arrays in generated code which are made from an iterator, `FromIterator` is only implemented
+/// for a `Vec` in the global allocator.
+#[doc(hidden)]
+pub trait CollectArray<T>: Sized {
+    fn collect_array<I: IntoIterator<Item = T>>(items: I) -> Self;
+}
+
+#[cfg(not(feature = "allocator_api"))]
+impl<T> CollectArray<T> for Vec<T> {
+    #[inline]
+    fn collect_array<I: IntoIterator<Item = T>>(items: I) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T, A> CollectArray<T> for Vec<T, A>
 where
-    T: SubRecord<'raw>,
+    A: core::alloc::Allocator + Default,
+{
+    fn collect_array<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let items = items.into_iter();
+        let mut v = Vec::with_capacity_in(items.size_hint().0, A::default());
+        v.extend(items);
+        v
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A: smallvec::Array> CollectArray<A::Item> for smallvec::SmallVec<A> {
+    #[inline]
+    fn collect_array<I: IntoIterator<Item = A::Item>>(items: I) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+/// The inline capacity of arrays in generated code when the `smallvec` feature is enabled and
+/// `BEBOP_SMALL_ARRAY_CAPACITY` is not set.
+#[cfg(feature = "smallvec")]
+pub const DEFAULT_SMALL_ARRAY_CAPACITY: usize = 4;
+
+/// The inline capacity of arrays in generated code, `capacity` is the value of the
+/// `BEBOP_SMALL_ARRAY_CAPACITY` environment variable when the crate with the generated code was
+/// built.
+#[cfg(feature = "smallvec")]
+#[doc(hidden)]
+pub const fn small_array_capacity(capacity: Option<&str>) -> usize {
+    let digits = match capacity {
+        Some(capacity) if !capacity.is_empty() => capacity.as_bytes(),
+        _ => return DEFAULT_SMALL_ARRAY_CAPACITY,
+    };
+    let mut n = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        if !digits[i].is_ascii_digit() {
+            panic!("BEBOP_SMALL_ARRAY_CAPACITY must be a number");
+        }
+        n = n * 10 + (digits[i] - b'0') as usize;
+        i += 1;
+    }
+    n
+}
+
+/// Same as `Vec` but items are stored inline until there are more than the capacity of the array.
+#[cfg(feature = "smallvec")]
+impl<'raw, A> SubRecord<'raw> for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: SubRecord<'raw>,
 {
     const MIN_SERIALIZED_SIZE: usize = LEN_SIZE;
 
     #[inline]
     fn serialized_size(&self) -> usize {
-        if let Some(size) = T::EXACT_SERIALIZED_SIZE {
+        if let Some(size) = A::Item::EXACT_SERIALIZED_SIZE {
             self.len() * size + LEN_SIZE
         } else {
             self.iter().fold(0, |acc, v| acc + v.serialized_size()) + LEN_SIZE
@@ -136,7 +633,10 @@ where
     }
 
     fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
-        write_len(dest, self.len())?;
+        write_len_or(dest, self.len(), |len, max| SerializeError::ArrayTooLong {
+            len,
+            max,
+        })?;
         let mut i = LEN_SIZE;
         for v in self.iter() {
             i += v._serialize_chained(dest)?;
@@ -144,17 +644,94 @@ where
         Ok(i)
     }
 
+    #[inline]
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        Self::_deserialize_chained_with_depth(raw, DEFAULT_MAX_DEPTH)
+    }
+
+    #[inline]
+    fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+        self._deserialize_chained_into_with_depth(raw, DEFAULT_MAX_DEPTH)
+    }
+
+    fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> DeResult<(usize, Self)> {
         let len = read_len(raw)?;
+        check_len(raw, len, A::Item::MIN_SERIALIZED_SIZE)?;
         let mut i = LEN_SIZE;
-        let mut v = Vec::with_capacity(len);
+        let mut v = Self::with_capacity(len);
         for _ in 0..len {
-            let (read, t) = T::_deserialize_chained(&raw[i..])?;
+            let (read, t) = A::Item::_deserialize_chained_with_depth(&raw[i..], depth)
+                .map_err(|err| err.at_offset(i))?;
             i += read;
             v.push(t);
         }
         Ok((i, v))
     }
+
+    fn _deserialize_chained_into_with_depth(
+        &mut self,
+        raw: &'raw [u8],
+        depth: usize,
+    ) -> DeResult<usize> {
+        let len = read_len(raw)?;
+        check_len(raw, len, A::Item::MIN_SERIALIZED_SIZE)?;
+        let mut i = LEN_SIZE;
+        self.truncate(len);
+        for v in self.iter_mut() {
+            i += v
+                ._deserialize_chained_into_with_depth(&raw[i..], depth)
+                .map_err(|err| err.at_offset(i))?;
+        }
+        self.reserve(len - self.len());
+        while self.len() < len {
+            let (read, t) = A::Item::_deserialize_chained_with_depth(&raw[i..], depth)
+                .map_err(|err| err.at_offset(i))?;
+            i += read;
+            self.push(t);
+        }
+        Ok(i)
+    }
+}
+
+/// Allocator which counts the allocations it makes.
+#[cfg(all(test, feature = "allocator_api"))]
+#[derive(Default)]
+struct CountingAlloc;
+
+#[cfg(all(test, feature = "allocator_api"))]
+static ALLOCATIONS: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(all(test, feature = "allocator_api"))]
+unsafe impl core::alloc::Allocator for CountingAlloc {
+    fn allocate(
+        &self,
+        layout: core::alloc::Layout,
+    ) -> Result<core::ptr::NonNull<[u8]>, core::alloc::AllocError> {
+        ALLOCATIONS.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+        alloc::alloc::Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: core::ptr::NonNull<u8>, layout: core::alloc::Layout) {
+        alloc::alloc::Global.deallocate(ptr, layout)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+#[test]
+fn deserialization_vec_in_allocator() {
+    let mut buf = Vec::new();
+    vec![3u16, 4, 5]._serialize_chained(&mut buf).unwrap();
+    let (read, v) = Vec::<u16, CountingAlloc>::_deserialize_chained(&buf).unwrap();
+    assert_eq!(read, buf.len());
+    assert_eq!(v[..], [3, 4, 5]);
+    assert_eq!(ALLOCATIONS.load(core::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "allocator_api")]
+#[test]
+fn collect_array_in_allocator() {
+    let v: Vec<u16, alloc::alloc::Global> = CollectArray::collect_array([3u16, 4, 5].iter().copied());
+    assert_eq!(v[..], [3, 4, 5]);
 }
 
 test_serialization!(
@@ -180,16 +757,65 @@ test_serialization!(
 );
 test_serialization!(serialization_vec_empty_i16, Vec<i16>, Vec::new(), LEN_SIZE);
 
+#[cfg(feature = "smallvec")]
+#[test]
+fn serialization_smallvec() {
+    let mut vec_buf = Vec::new();
+    vec!["abc", "def", "ghij"]
+        ._serialize_chained(&mut vec_buf)
+        .unwrap();
+    let mut buf = Vec::new();
+    let small: smallvec::SmallVec<[&str; 2]> = smallvec::smallvec!["abc", "def", "ghij"];
+    assert_eq!(
+        small._serialize_chained(&mut buf).unwrap(),
+        small.serialized_size()
+    );
+    assert_eq!(buf, vec_buf);
+
+    let (read, de) = smallvec::SmallVec::<[&str; 4]>::_deserialize_chained(&buf).unwrap();
+    assert_eq!(read, buf.len());
+    assert_eq!(de[..], small[..]);
+    assert!(!de.spilled());
+}
+
+#[cfg(feature = "smallvec")]
+#[test]
+fn deserialization_smallvec_into() {
+    let mut buf = Vec::new();
+    vec![7i32]._serialize_chained(&mut buf).unwrap();
+    let mut small: smallvec::SmallVec<[i32; 2]> = smallvec::smallvec![1, 2, 3];
+    assert_eq!(small._deserialize_chained_into(&buf).unwrap(), buf.len());
+    assert_eq!(small[..], [7]);
+}
+
+#[cfg(feature = "smallvec")]
+#[test]
+fn small_array_capacity_from_env() {
+    assert_eq!(small_array_capacity(None), DEFAULT_SMALL_ARRAY_CAPACITY);
+    assert_eq!(small_array_capacity(Some("")), DEFAULT_SMALL_ARRAY_CAPACITY);
+    assert_eq!(small_array_capacity(Some("16")), 16);
+    const CAPACITY: usize = small_array_capacity(Some("3"));
+    let _: smallvec::SmallVec<[u8; CAPACITY]> = smallvec::SmallVec::from_buf([1, 2, 3]);
+}
+
+#[cfg(any(feature = "std", feature = "hashbrown"))]
 #[cfg(feature = "sorted_maps")]
 pub trait SubRecordHashMapKey<'raw>: SubRecord<'raw> + Eq + Hash + Ord {}
+#[cfg(any(feature = "std", feature = "hashbrown"))]
 #[cfg(not(feature = "sorted_maps"))]
 pub trait SubRecordHashMapKey<'raw>: SubRecord<'raw> + Eq + Hash {}
 
+#[cfg(any(feature = "std", feature = "hashbrown"))]
 #[cfg(feature = "sorted_maps")]
 impl<'raw, T> SubRecordHashMapKey<'raw> for T where T: SubRecord<'raw> + Eq + Hash + Ord {}
+#[cfg(any(feature = "std", feature = "hashbrown"))]
 #[cfg(not(feature = "sorted_maps"))]
 impl<'raw, T> SubRecordHashMapKey<'raw> for T where T: SubRecord<'raw> + Eq + Hash {}
 
+/// Maps are encoded as a count followed by each key and then its value, which is the same as a
+/// dictionary in the other runtimes. Entries are written in iteration order unless `sorted_maps` is
+/// enabled. If a key appears more than once when decoding the last value is kept.
+#[cfg(any(feature = "std", feature = "hashbrown"))]
 impl<'raw, K, V> SubRecord<'raw> for HashMap<K, V>
 where
     K: SubRecordHashMapKey<'raw>,
@@ -223,7 +849,7 @@ where
         #[cfg(feature = "sorted_maps")]
         use itertools::Itertools;
 
-        write_len(dest, self.len())?;
+        write_len_or(dest, self.len(), |len, max| SerializeError::MapTooLong { len, max })?;
         let mut i = LEN_SIZE;
 
         #[cfg(feature = "sorted_maps")]
@@ -240,19 +866,52 @@ where
         Ok(i)
     }
 
+    #[inline]
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        Self::_deserialize_chained_with_depth(raw, DEFAULT_MAX_DEPTH)
+    }
+
+    #[inline]
+    fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+        self._deserialize_chained_into_with_depth(raw, DEFAULT_MAX_DEPTH)
+    }
+
+    fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> DeResult<(usize, Self)> {
         let len = read_len(raw)?;
+        check_len(raw, len, K::MIN_SERIALIZED_SIZE + V::MIN_SERIALIZED_SIZE)?;
         let mut i = LEN_SIZE;
         let mut m = HashMap::with_capacity(len);
         for _ in 0..len {
-            let (read, k) = K::_deserialize_chained(&raw[i..])?;
+            let (read, k) = K::_deserialize_chained(&raw[i..]).map_err(|err| err.at_offset(i))?;
             i += read;
-            let (read, v) = V::_deserialize_chained(&raw[i..])?;
+            let (read, v) = V::_deserialize_chained_with_depth(&raw[i..], depth)
+                .map_err(|err| err.at_offset(i))?;
             i += read;
             m.insert(k, v);
         }
         Ok((i, m))
     }
+
+    fn _deserialize_chained_into_with_depth(
+        &mut self,
+        raw: &'raw [u8],
+        depth: usize,
+    ) -> DeResult<usize> {
+        let len = read_len(raw)?;
+        check_len(raw, len, K::MIN_SERIALIZED_SIZE + V::MIN_SERIALIZED_SIZE)?;
+        let mut i = LEN_SIZE;
+        self.clear();
+        self.reserve(len);
+        for _ in 0..len {
+            let (read, k) = K::_deserialize_chained(&raw[i..]).map_err(|err| err.at_offset(i))?;
+            i += read;
+            let (read, v) = V::_deserialize_chained_with_depth(&raw[i..], depth)
+                .map_err(|err| err.at_offset(i))?;
+            i += read;
+            self.insert(k, v);
+        }
+        Ok(i)
+    }
 }
 
 test_serialization!(serialization_map_str_str, HashMap<&str, &str>, collection! { "k1" => "v1", "key2" => "value2" }, 14 + LEN_SIZE * 5);
@@ -262,6 +921,15 @@ test_serialization!(serialization_map_i16_i16, HashMap<i16, i16>, collection! {
 test_serialization!(serialization_map_i16_i16_empty, HashMap<i16, i16>, HashMap::new(), LEN_SIZE);
 test_serialization!(serialization_map_str_str_empty, HashMap<&str, &str>, HashMap::new(), LEN_SIZE);
 test_serialization!(serialization_map_str_vec_empty_vec, HashMap<&str, Vec<i32>>, collection! {"abc" => vec![]}, LEN_SIZE * 3 + 3);
+test_serialization!(serialization_map_guid_bool, HashMap<Guid, bool>, collection! { Guid::from_be_bytes([1; 16]) => true, Guid::from_be_bytes([2; 16]) => false }, 34 + LEN_SIZE);
+
+#[test]
+fn deserialize_map_duplicate_keys() {
+    let buf = [2, 0, 0, 0, 7, 0, 1, 0, 7, 0, 2, 0];
+    let (read, m) = <HashMap<u16, u16>>::_deserialize_chained(&buf).unwrap();
+    assert_eq!(read, buf.len());
+    assert_eq!(m, collection! { 7 => 2 });
+}
 
 impl<'raw> SubRecord<'raw> for Guid {
     const MIN_SERIALIZED_SIZE: usize = Self::SERIALIZED_SIZE;
@@ -274,7 +942,7 @@ impl<'raw> SubRecord<'raw> for Guid {
 
     #[inline]
     fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
-        dest.write_all(&self.to_ms_bytes())?;
+        dest.write_all(&self.to_bytes())?;
         Ok(16)
     }
 
@@ -283,9 +951,10 @@ impl<'raw> SubRecord<'raw> for Guid {
         Ok((
             16,
             Guid::from_ms_bytes(
-                raw[0..16]
+                raw.get(0..16)
+                    .ok_or_else(|| DeserializeError::MoreDataExpected(16 - raw.len()))?
                     .try_into()
-                    .map_err(|_| DeserializeError::MoreDataExpected(16 - raw.len()))?,
+                    .unwrap(),
             ),
         ))
     }
@@ -301,6 +970,27 @@ test_serialization!(
     16
 );
 
+impl<'raw, E: Enum> SubRecord<'raw> for MaybeUnknown<E> {
+    const MIN_SERIALIZED_SIZE: usize = Self::SERIALIZED_SIZE;
+    const EXACT_SERIALIZED_SIZE: Option<usize> = Some(Self::SERIALIZED_SIZE);
+
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        Self::SERIALIZED_SIZE
+    }
+
+    #[inline]
+    fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+        self.to_repr()._serialize_chained(dest)
+    }
+
+    #[inline]
+    fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        let (read, value) = E::Repr::_deserialize_chained(raw)?;
+        Ok((read, Self::from_repr(value)))
+    }
+}
+
 impl<'raw> SubRecord<'raw> for Date {
     const MIN_SERIALIZED_SIZE: usize = Self::SERIALIZED_SIZE;
     const EXACT_SERIALIZED_SIZE: Option<usize> = Some(Self::SERIALIZED_SIZE);
@@ -317,7 +1007,7 @@ impl<'raw> SubRecord<'raw> for Date {
 
     #[inline]
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
-        let (read, date) = u64::_deserialize_chained(&raw)?;
+        let (read, date) = u64::_deserialize_chained(raw)?;
         Ok((read, Date::from_ticks(date)))
     }
 }
@@ -364,20 +1054,27 @@ where
     }
 
     fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
-        write_len(dest, self.len())?;
+        write_len_or(dest, self.len(), |len, max| SerializeError::ArrayTooLong { len, max })?;
         match *self {
             SliceWrapper::Raw(raw) => {
-                dest.write_all(&raw)?;
+                dest.write_all(raw)?;
                 Ok(raw.len() + LEN_SIZE)
             }
+            SliceWrapper::Cooked(ary) if !T::IS_WIRE_FORMAT => {
+                let mut i = LEN_SIZE;
+                for v in ary {
+                    i += v._serialize_chained(dest)?;
+                }
+                Ok(i)
+            }
             SliceWrapper::Cooked(ary) => {
                 #[cfg(target_endian = "big")]
                 todo!();
 
                 let b: &[u8] = unsafe {
-                    std::slice::from_raw_parts(
+                    core::slice::from_raw_parts(
                         ary.as_ptr() as *const u8,
-                        ary.len() * core::mem::size_of::<T>(),
+                        core::mem::size_of_val(ary),
                     )
                 };
                 dest.write_all(b)?;
@@ -388,15 +1085,19 @@ where
 
     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
         let len = read_len(raw)?;
-        let bytes = len * std::mem::size_of::<T>() + LEN_SIZE;
-        if bytes > raw.len() {
-            return Err(DeserializeError::MoreDataExpected(bytes - raw.len()));
+        check_len(raw, len, T::SERIALIZED_SIZE)?;
+        let bytes = len * T::SERIALIZED_SIZE + LEN_SIZE;
+        if !T::IS_WIRE_FORMAT {
+            // make sure every item is valid now so reading them later can't fail
+            for item in raw[LEN_SIZE..bytes].chunks_exact(T::SERIALIZED_SIZE) {
+                T::_deserialize_chained(item)?;
+            }
         }
         Ok((
             bytes,
-            if std::mem::align_of::<T>() == 1 {
+            if core::mem::align_of::<T>() == 1 && T::IS_WIRE_FORMAT {
                 SliceWrapper::from_cooked(unsafe {
-                    std::slice::from_raw_parts((&raw[LEN_SIZE..bytes]).as_ptr() as *const T, len)
+                    core::slice::from_raw_parts(raw[LEN_SIZE..bytes].as_ptr() as *const T, len)
                 })
             } else {
                 SliceWrapper::from_raw(&raw[LEN_SIZE..bytes])
@@ -428,6 +1129,55 @@ fn serialization_slicewrapper_i16_cooked() {
     );
 }
 
+#[test]
+fn deserialization_slicewrapper_checks_items() {
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(u8)]
+    enum Small {
+        A = 1,
+    }
+
+    impl FixedSized for Small {
+        const IS_WIRE_FORMAT: bool = false;
+    }
+
+    impl<'raw> SubRecord<'raw> for Small {
+        const MIN_SERIALIZED_SIZE: usize = 1;
+        const EXACT_SERIALIZED_SIZE: Option<usize> = Some(1);
+
+        fn serialized_size(&self) -> usize {
+            1
+        }
+
+        fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+            (*self as u8)._serialize_chained(dest)
+        }
+
+        fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+            match u8::_deserialize_chained(raw)? {
+                (n, 1) => Ok((n, Small::A)),
+                (_, d) => Err(DeserializeError::InvalidEnumDiscriminant { value: d.into() }),
+            }
+        }
+    }
+
+    let (_, items) = SliceWrapper::<Small>::_deserialize_chained(&[2, 0, 0, 0, 1, 1]).unwrap();
+    assert!(items.is_raw());
+    assert_eq!(items, [Small::A, Small::A][..]);
+    assert!(matches!(
+        SliceWrapper::<Small>::_deserialize_chained(&[2, 0, 0, 0, 1, 2]),
+        Err(DeserializeError::InvalidEnumDiscriminant { value: 2 })
+    ));
+    let mut buf = Vec::new();
+    SliceWrapper::Cooked(&[Small::A])._serialize_chained(&mut buf).unwrap();
+    assert_eq!(buf, [1, 0, 0, 0, 1]);
+
+    // bools are never cooked since only 0 and 1 are valid in memory
+    let (_, flags) = SliceWrapper::<bool>::_deserialize_chained(&[2, 0, 0, 0, 0, 2]).unwrap();
+    assert!(flags.is_raw());
+    assert_eq!(flags, [false, true][..]);
+}
+
 macro_rules! impl_record_for_num {
     ($t:ty) => {
         impl<'raw> SubRecord<'raw> for $t {
@@ -449,13 +1199,16 @@ macro_rules! impl_record_for_num {
             fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
                 Ok((
                     core::mem::size_of::<$t>(),
-                    <$t>::from_le_bytes(raw[0..core::mem::size_of::<$t>()].try_into().map_err(
-                        |_| {
-                            DeserializeError::MoreDataExpected(
-                                core::mem::size_of::<$t>() - raw.len(),
-                            )
-                        },
-                    )?),
+                    <$t>::from_le_bytes(
+                        raw.get(0..core::mem::size_of::<$t>())
+                            .ok_or_else(|| {
+                                DeserializeError::MoreDataExpected(
+                                    core::mem::size_of::<$t>() - raw.len(),
+                                )
+                            })?
+                            .try_into()
+                            .unwrap(),
+                    ),
                 ))
             }
         }
@@ -479,7 +1232,33 @@ impl_record_for_num!(f64);
 /// hacking.
 #[inline(always)]
 pub fn read_len(raw: &[u8]) -> DeResult<usize> {
-    Ok(Len::_deserialize_chained(&raw)?.1 as usize)
+    Ok(Len::_deserialize_chained(raw)?.1 as usize)
+}
+
+/// Read the discriminator of the union encoded at the front of `raw` without decoding the branch,
+/// to decide how to handle it before paying for a full decode. Like deserializing the union, this
+/// fails if `raw` is shorter than the length of the union.
+pub fn peek_union_discriminant(raw: &[u8]) -> DeResult<u8> {
+    let len = read_len(raw)?
+        .checked_add(LEN_SIZE + 1)
+        .ok_or(DeserializeError::CorruptFrame)?;
+    if raw.len() < len {
+        return Err(DeserializeError::MoreDataExpected(len - raw.len()));
+    }
+    Ok(raw[LEN_SIZE])
+}
+
+/// Make sure `raw` could hold the length prefix and `len` items of at least `item_size` bytes before
+/// anything is allocated for them, so a corrupt length cannot request more memory than the input
+/// could ever fill.
+#[inline]
+fn check_len(raw: &[u8], len: usize, item_size: usize) -> DeResult<()> {
+    let needed = len.saturating_mul(item_size).saturating_add(LEN_SIZE);
+    if needed > raw.len() {
+        Err(DeserializeError::MoreDataExpected(needed - raw.len()))
+    } else {
+        Ok(())
+    }
 }
 
 #[test]
@@ -490,16 +1269,27 @@ fn read_len_test() {
     assert_eq!(read_len(&buf[2..]).unwrap(), 84017152);
 }
 
-/// Write a 4-byte length value to the writer.
+/// Write a 4-byte length value to the writer, failing with `RecordTooLong` if it does not fit.
 ///
 /// This should only be called from within an auto-implemented deserialize function or for byte
 /// hacking.
 #[inline(always)]
 pub fn write_len<W: Write>(dest: &mut W, len: usize) -> SeResult<()> {
-    if len > u32::MAX as usize {
-        Err(SerializeError::LengthExceeds32Bits)
+    write_len_or(dest, len, |len, max| SerializeError::RecordTooLong { len, max })
+}
+
+/// Write a 4-byte length value to the writer, failing with the error made by `too_long` if it
+/// does not fit.
+#[inline(always)]
+fn write_len_or<W: Write>(
+    dest: &mut W,
+    len: usize,
+    too_long: fn(usize, usize) -> SerializeError,
+) -> SeResult<()> {
+    if len > MAX_LEN {
+        Err(too_long(len, MAX_LEN))
     } else {
-        (len as u32)._serialize_chained(dest)?;
+        (len as Len)._serialize_chained(dest)?;
         Ok(())
     }
 }
@@ -515,3 +1305,378 @@ fn write_len_test() {
     assert_eq!(buf[7..11], [123, 0, 0, 0]);
     assert_eq!(buf[11..], [247, 85, 1, 0]);
 }
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn write_len_too_long() {
+    let mut buf = Vec::new();
+    assert!(matches!(
+        write_len(&mut buf, MAX_LEN + 1),
+        Err(SerializeError::RecordTooLong { len, max: MAX_LEN }) if len == MAX_LEN + 1
+    ));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn peek_union() {
+    // a union with discriminator 3 and a 2 byte body, followed by something else
+    let raw = [2, 0, 0, 0, 3, 0xaa, 0xbb, 0xff];
+    assert_eq!(peek_union_discriminant(&raw).unwrap(), 3);
+    assert!(matches!(
+        peek_union_discriminant(&raw[..6]),
+        Err(DeserializeError::MoreDataExpected(1))
+    ));
+    assert!(matches!(
+        peek_union_discriminant(&raw[..2]),
+        Err(DeserializeError::MoreDataExpected(_))
+    ));
+    // the largest length can't have the header added to it on 32 bit targets
+    let result = peek_union_discriminant(&[0xff, 0xff, 0xff, 0xff, 3]);
+    #[cfg(target_pointer_width = "64")]
+    assert!(matches!(result, Err(DeserializeError::MoreDataExpected(_))));
+    #[cfg(not(target_pointer_width = "64"))]
+    assert!(matches!(result, Err(DeserializeError::CorruptFrame)));
+}
+
+#[test]
+fn str_longest_length() {
+    assert!(matches!(
+        <&str>::_deserialize_chained(&[0xff, 0xff, 0xff, 0xff, b'a']),
+        Err(DeserializeError::MoreDataExpected(n)) if n == u32::MAX as usize - 1
+    ));
+}
+
+#[test]
+fn deserialize_truncated() {
+    assert!(matches!(
+        u32::_deserialize_chained(&[1, 2]),
+        Err(DeserializeError::MoreDataExpected(2))
+    ));
+    assert!(matches!(
+        Guid::_deserialize_chained(&[0; 10]),
+        Err(DeserializeError::MoreDataExpected(6))
+    ));
+    assert!(matches!(
+        <&str>::_deserialize_chained(&[5, 0, 0, 0, b'a', b'b']),
+        Err(DeserializeError::MoreDataExpected(3))
+    ));
+}
+
+#[test]
+fn deserialize_huge_length() {
+    // claims u32::MAX items but only has the data for one
+    let buf = [0xff, 0xff, 0xff, 0xff, 1, 0, 0, 0];
+    assert!(matches!(
+        <Vec<u32>>::_deserialize_chained(&buf),
+        Err(DeserializeError::MoreDataExpected(n)) if n == 0xffff_ffff * 4 + LEN_SIZE - buf.len()
+    ));
+    assert!(matches!(
+        <Vec<&str>>::_deserialize_chained(&buf),
+        Err(DeserializeError::MoreDataExpected(_))
+    ));
+    assert!(matches!(
+        <HashMap<u16, &str>>::_deserialize_chained(&buf),
+        Err(DeserializeError::MoreDataExpected(_))
+    ));
+    assert!(matches!(
+        <SliceWrapper<u64>>::_deserialize_chained(&buf),
+        Err(DeserializeError::MoreDataExpected(_))
+    ));
+}
+
+#[test]
+#[cfg(not(feature = "unchecked"))]
+fn deserialize_invalid_utf8() {
+    let utf8_at = |err| match err {
+        Err(DeserializeError::InvalidUtf8 {
+            field_offset,
+            utf8_error,
+        }) => (field_offset, utf8_error.valid_up_to()),
+        err => panic!("expected invalid utf-8 but got {:?}", err),
+    };
+    assert_eq!(
+        utf8_at(<&str>::_deserialize_chained(&[3, 0, 0, 0, b'a', 0xff, b'b']).map(|_| ())),
+        (0, 1)
+    );
+
+    let mut buf = Vec::new();
+    vec!["abc", "de"]._serialize_chained(&mut buf).unwrap();
+    buf[LEN_SIZE * 2 + 3 + LEN_SIZE + 1] = 0xc0;
+    assert_eq!(
+        utf8_at(<Vec<&str>>::_deserialize_chained(&buf).map(|_| ())),
+        (LEN_SIZE * 2 + 3, 1)
+    );
+    let mut names = vec![String::new()];
+    assert_eq!(
+        utf8_at(names._deserialize_chained_into(&buf).map(|_| ())),
+        (LEN_SIZE * 2 + 3, 1)
+    );
+
+    let mut buf = Vec::new();
+    let mut map = HashMap::new();
+    map.insert(7u16, "xyz");
+    map._serialize_chained(&mut buf).unwrap();
+    *buf.last_mut().unwrap() = 0x80;
+    assert_eq!(
+        utf8_at(<HashMap<u16, &str>>::_deserialize_chained(&buf).map(|_| ())),
+        (LEN_SIZE + 2, 2)
+    );
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct Names(Vec<String>);
+
+#[cfg(test)]
+impl<'raw> SubRecord<'raw> for Names {
+    const MIN_SERIALIZED_SIZE: usize = <Vec<String>>::MIN_SERIALIZED_SIZE;
+
+    fn serialized_size(&self) -> usize {
+        self.0.serialized_size()
+    }
+
+    fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+        self.0._serialize_chained(dest)
+    }
+
+    fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        let (read, v) = <Vec<String>>::_deserialize_chained(raw)?;
+        Ok((read, Names(v)))
+    }
+
+    fn _deserialize_chained_into(&mut self, raw: &'raw [u8]) -> DeResult<usize> {
+        self.0._deserialize_chained_into(raw)
+    }
+}
+
+#[cfg(test)]
+impl<'raw> Record<'raw> for Names {}
+
+#[test]
+fn encode_into() {
+    let names = Names(vec!["abc".into(), "defgh".into()]);
+    let mut buf = [0xff; 32];
+    let written = names.encode_into(&mut buf).unwrap();
+    assert_eq!(written, names.serialized_size());
+    assert_eq!(buf[..written], names.encode_to_vec().unwrap()[..]);
+    assert!(buf[written..].iter().all(|&b| b == 0xff));
+}
+
+#[test]
+fn encode_into_too_small() {
+    let names = Names(vec!["abc".into()]);
+    let mut buf = [0; 8];
+    assert!(matches!(
+        names.encode_into(&mut buf),
+        Err(SerializeError::BufferTooSmall {
+            needed: 11,
+            available: 8
+        })
+    ));
+    assert_eq!(buf, [0; 8]);
+}
+
+#[test]
+fn decode_checked() {
+    let names = Names(vec!["abc".into()]);
+    let mut buf = Vec::new();
+    let written = names.encode_checked(0x1234, &mut buf).unwrap();
+    assert_eq!(written, WIRE_HASH_SIZE + names.serialized_size());
+    assert_eq!(buf.len(), written);
+    assert_eq!(Names::decode_checked(&buf, 0x1234).unwrap(), names);
+    assert!(matches!(
+        Names::decode_checked(&buf, 0x4321),
+        Err(DeserializeError::SchemaMismatch {
+            expected: 0x4321,
+            found: 0x1234
+        })
+    ));
+    assert!(matches!(
+        Names::decode_checked(&buf[..3], 0x1234),
+        Err(DeserializeError::MoreDataExpected(5))
+    ));
+}
+
+#[test]
+fn deserialize_into_reuses_allocations() {
+    let mut names = Names(vec![
+        String::with_capacity(16),
+        String::new(),
+        String::new(),
+    ]);
+    let first = names.0[0].as_ptr();
+    let buf = Names(vec!["abc".into(), "de".into()])
+        .encode_to_vec()
+        .unwrap();
+    names.deserialize_into(&buf).unwrap();
+    assert_eq!(names, Names(vec!["abc".into(), "de".into()]));
+    assert_eq!(names.0[0].as_ptr(), first);
+    assert!(names.0.capacity() >= 3);
+
+    let buf = Names(vec!["x".into(), "y".into(), "z".into(), "w".into()])
+        .encode_to_vec()
+        .unwrap();
+    names.deserialize_into(&buf).unwrap();
+    assert_eq!(names.0, ["x", "y", "z", "w"]);
+    assert_eq!(names.0[0].as_ptr(), first);
+}
+
+#[test]
+fn deserialize_map_into() {
+    let mut m: HashMap<u16, u16> = collection! { 1 => 1, 2 => 2 };
+    let buf = [1, 0, 0, 0, 7, 0, 2, 0];
+    assert_eq!(m._deserialize_chained_into(&buf).unwrap(), buf.len());
+    assert_eq!(m, collection! { 7 => 2 });
+}
+
+#[test]
+fn deserialize_with_len() {
+    let first = Names(vec!["abc".into()]);
+    let second = Names(vec!["de".into(), "f".into()]);
+    let mut buf = first.encode_to_vec().unwrap();
+    second.serialize(&mut buf).unwrap();
+    let (de, read) = Names::deserialize_with_len(&buf).unwrap();
+    assert_eq!(de, first);
+    assert_eq!(read, first.serialized_size());
+    let (de, rest) = Names::deserialize_with_len(&buf[read..]).unwrap();
+    assert_eq!(de, second);
+    assert_eq!(read + rest, buf.len());
+}
+
+#[test]
+#[cfg(feature = "test-util")]
+fn assert_roundtrip() {
+    testing::assert_roundtrip(&Names(vec!["abc".into(), "de".into()]));
+    testing::assert_roundtrip(&Names(Vec::new()));
+}
+
+/// Record which contains itself so it can be nested as deeply as the data says.
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct Tree(Vec<Tree>);
+
+#[cfg(test)]
+impl<'raw> SubRecord<'raw> for Tree {
+    const MIN_SERIALIZED_SIZE: usize = LEN_SIZE;
+
+    fn serialized_size(&self) -> usize {
+        self.0.serialized_size()
+    }
+
+    fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+        self.0._serialize_chained(dest)
+    }
+
+    fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        Self::_deserialize_chained_with_depth(raw, DEFAULT_MAX_DEPTH)
+    }
+
+    fn _deserialize_chained_with_depth(raw: &'raw [u8], depth: usize) -> DeResult<(usize, Self)> {
+        let depth = nested_depth(depth)?;
+        let (read, v) = <Vec<Tree>>::_deserialize_chained_with_depth(raw, depth)?;
+        Ok((read, Tree(v)))
+    }
+}
+
+#[cfg(test)]
+impl<'raw> Record<'raw> for Tree {}
+
+/// A tree which is `depth` records deep with one child at each level.
+#[cfg(test)]
+fn nested_tree(depth: usize) -> Vec<u8> {
+    let mut buf = [1, 0, 0, 0].repeat(depth - 1);
+    buf.extend_from_slice(&[0, 0, 0, 0]);
+    buf
+}
+
+#[test]
+fn depth_limit() {
+    let tree = Tree::deserialize(&nested_tree(DEFAULT_MAX_DEPTH)).unwrap();
+    assert_eq!(tree.0.len(), 1);
+    assert!(matches!(
+        Tree::deserialize(&nested_tree(DEFAULT_MAX_DEPTH + 1)),
+        Err(DeserializeError::DepthLimitExceeded)
+    ));
+    // without a limit this would overflow the stack
+    assert!(matches!(
+        Tree::deserialize(&nested_tree(1_000_000)),
+        Err(DeserializeError::DepthLimitExceeded)
+    ));
+
+    let options = DecodeOptions::default().max_depth(3);
+    assert!(Tree::deserialize_with_options(&nested_tree(3), &options).is_ok());
+    assert!(matches!(
+        Tree::deserialize_with_options(&nested_tree(4), &options),
+        Err(DeserializeError::DepthLimitExceeded)
+    ));
+    let options = DecodeOptions::default().max_depth(200);
+    assert!(Tree::deserialize_with_options(&nested_tree(200), &options).is_ok());
+}
+
+/// Reader which only hands out one byte at a time.
+#[cfg(all(test, feature = "std"))]
+struct Trickle<'a>(&'a [u8]);
+
+#[cfg(all(test, feature = "std"))]
+impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match (buf.first_mut(), self.0.split_first()) {
+            (Some(b), Some((&v, rest))) => {
+                *b = v;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_from_reader() {
+    let names = Names(vec!["abc".into(), "".into(), "defgh".into()]);
+    let mut buf = names.encode_to_vec().unwrap();
+    buf.extend_from_slice(&[0x05, 0x01, 0x00, 0x00, 0x13, 0x42, 0x12]);
+    let mut reader = Trickle(&buf);
+    assert_eq!(Names::decode_from_reader(&mut reader).unwrap(), names);
+    // only the record was consumed
+    assert_eq!(reader.0, [0x05, 0x01, 0x00, 0x00, 0x13, 0x42, 0x12]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_from_reader_consecutive() {
+    let (a, b) = (Names(vec!["abc".into()]), Names(vec![]));
+    let mut buf = a.encode_to_vec().unwrap();
+    b.serialize(&mut buf).unwrap();
+    let mut reader = buf.as_slice();
+    assert_eq!(Names::decode_from_reader(&mut reader).unwrap(), a);
+    assert_eq!(Names::decode_from_reader(&mut reader).unwrap(), b);
+    assert!(reader.is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_from_reader_early_eof() {
+    let buf = Names(vec!["abc".into(), "defgh".into()])
+        .encode_to_vec()
+        .unwrap();
+    let mut reader = Trickle(&buf[..buf.len() - 2]);
+    assert!(matches!(
+        Names::decode_from_reader(&mut reader),
+        Err(DeserializeError::MoreDataExpected(2))
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_from_reader_huge_length() {
+    // claims 2^32 - 1 strings without any of them following, reserving space for all of them up
+    // front would fail to allocate
+    let mut reader = &[0xff, 0xff, 0xff, 0xff, 3, 0, 0, 0, b'a'][..];
+    assert!(matches!(
+        Names::decode_from_reader(&mut reader),
+        Err(DeserializeError::MoreDataExpected(_))
+    ));
+    assert!(reader.is_empty());
+}
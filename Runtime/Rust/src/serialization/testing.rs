@@ -23,12 +23,80 @@ macro_rules! test_serialization {
 macro_rules! collection {
     // map-like
     ($($k:expr => $v:expr),* $(,)?) => {{
-        use std::iter::{Iterator, IntoIterator};
+        use core::iter::{Iterator, IntoIterator};
         Iterator::collect(IntoIterator::into_iter([$(($k, $v),)*]))
     }};
     // set-like
     ($($v:expr),* $(,)?) => {{
-        use std::iter::{Iterator, IntoIterator};
+        use core::iter::{Iterator, IntoIterator};
         Iterator::collect(IntoIterator::into_iter([$($v,)*]))
     }};
 }
+
+/// Encode `value`, decode it again, and assert the decoded value is equal and that the sizes
+/// reported along the way match the number of bytes written. This is for records which own their
+/// data, use `assert_roundtrip_in` for records which borrow from the data they are decoded from.
+///
+/// With the `arbitrary` feature this can check values made from fuzzer or property test input.
+///
+/// ```
+/// # use bebop::prelude::*;
+/// # #[derive(Debug, PartialEq)]
+/// # struct Id(u64);
+/// # impl<'raw> SubRecord<'raw> for Id {
+/// #     const MIN_SERIALIZED_SIZE: usize = 8;
+/// #     fn serialized_size(&self) -> usize { 8 }
+/// #     fn _serialize_chained<W: bebop::Write>(&self, dest: &mut W) -> SeResult<usize> {
+/// #         self.0._serialize_chained(dest)
+/// #     }
+/// #     fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+/// #         u64::_deserialize_chained(raw).map(|(read, v)| (read, Id(v)))
+/// #     }
+/// # }
+/// # impl<'raw> Record<'raw> for Id {}
+/// bebop::testing::assert_roundtrip(&Id(7));
+/// ```
+#[cfg(feature = "test-util")]
+#[track_caller]
+pub fn assert_roundtrip<T>(value: &T)
+where
+    T: crate::OwnedRecord + PartialEq + core::fmt::Debug,
+{
+    assert_roundtrip_in(value, &mut alloc::vec::Vec::new())
+}
+
+/// The same as `assert_roundtrip` but the value is written to `buf`, which is cleared first, so
+/// records which borrow from it can be decoded.
+#[cfg(feature = "test-util")]
+#[track_caller]
+pub fn assert_roundtrip_in<'raw, T>(value: &T, buf: &'raw mut alloc::vec::Vec<u8>)
+where
+    T: crate::Record<'raw> + PartialEq + core::fmt::Debug,
+{
+    buf.clear();
+    let written = value
+        .serialize(buf)
+        .expect("the value could not be encoded");
+    assert_eq!(
+        written,
+        buf.len(),
+        "the bytes written do not match the length returned"
+    );
+    assert_eq!(
+        value.serialized_size(),
+        written,
+        "serialized_size does not match the bytes written"
+    );
+    let raw: &'raw [u8] = buf;
+    let (decoded, read) =
+        T::deserialize_with_len(raw).expect("the encoded value could not be decoded");
+    assert_eq!(
+        read,
+        raw.len(),
+        "the bytes read do not match the bytes written"
+    );
+    assert_eq!(
+        &decoded, value,
+        "the decoded value is not equal to the encoded one"
+    );
+}
@@ -0,0 +1,64 @@
+use crate::{SeResult, SerializeError};
+
+/// Destination for serialized bytes.
+///
+/// With the `std` feature this is implemented for every `std::io::Write`. Without it this is
+/// implemented for `Vec<u8>` and for `&mut [u8]`, which is advanced past the bytes written the same
+/// way `std::io::Write` does for slices.
+pub trait Write {
+    /// Write the entire buffer or fail.
+    fn write_all(&mut self, buf: &[u8]) -> SeResult<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> SeResult<()> {
+        std::io::Write::write_all(self, buf).map_err(SerializeError::Io)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for alloc::vec::Vec<u8> {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> SeResult<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for &mut [u8] {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> SeResult<()> {
+        if buf.len() > self.len() {
+            return Err(SerializeError::BufferTooSmall {
+                needed: buf.len(),
+                available: self.len(),
+            });
+        }
+        let (written, rest) = core::mem::take(self).split_at_mut(buf.len());
+        written.copy_from_slice(buf);
+        *self = rest;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> SeResult<()> {
+        (**self).write_all(buf)
+    }
+}
+
+#[test]
+fn write_to_slice() {
+    let mut buf = [0u8; 6];
+    let mut dest = &mut buf[..];
+    Write::write_all(&mut dest, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(dest.len(), 2);
+    Write::write_all(&mut dest, &[5, 6]).unwrap();
+    assert!(Write::write_all(&mut dest, &[7]).is_err());
+    assert_eq!(buf, [1, 2, 3, 4, 5, 6]);
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{DeResult, DeserializeError, Record, SeResult, SubRecord, Write};
+
+/// A fixed size record of two `int32`s, written the way generated code writes a struct.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl<'raw> SubRecord<'raw> for Point {
+    const MIN_SERIALIZED_SIZE: usize = 8;
+    const EXACT_SERIALIZED_SIZE: Option<usize> = Some(8);
+
+    fn serialized_size(&self) -> usize {
+        8
+    }
+
+    fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
+        Ok(self.x._serialize_chained(dest)? + self.y._serialize_chained(dest)?)
+    }
+
+    fn _deserialize_chained(raw: &'raw [u8]) -> DeResult<(usize, Self)> {
+        if raw.len() < 8 {
+            return Err(DeserializeError::MoreDataExpected(8 - raw.len()));
+        }
+        let (_, x) = i32::_deserialize_chained(raw)?;
+        let (_, y) = i32::_deserialize_chained(&raw[4..])?;
+        Ok((8, Point { x, y }))
+    }
+}
+
+impl<'raw> Record<'raw> for Point {}
+
+#[cfg(feature = "tokio")]
+impl<'raw> crate::AsyncSubRecord<'raw> for Point {}
+
+#[test]
+fn point_too_short() {
+    assert!(matches!(
+        Point::_deserialize_chained(&[1, 0, 0, 0, 2]),
+        Err(DeserializeError::MoreDataExpected(3))
+    ));
+}
@@ -0,0 +1,124 @@
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+use crate::{read_len, DeResult, SubRecord, LEN_SIZE};
+
+/// Lazily decodes the items of an encoded array one at a time. This avoids building a `Vec` when
+/// only some of the items are needed, such as when searching for one of them.
+///
+/// Items are not decoded until they are reached so each is a `DeResult`, the iterator ends after
+/// the first error.
+#[derive(Debug)]
+pub struct ArrayIter<'raw, T> {
+    /// Encoded items which have not been decoded yet.
+    raw: &'raw [u8],
+    remaining: usize,
+    _item: PhantomData<T>,
+}
+
+impl<'raw, T> ArrayIter<'raw, T>
+where
+    T: SubRecord<'raw>,
+{
+    /// Start decoding an encoded array, `raw` must begin with the length of the array.
+    pub fn new(raw: &'raw [u8]) -> DeResult<Self> {
+        let remaining = read_len(raw)?;
+        Ok(Self {
+            raw: &raw[LEN_SIZE..],
+            remaining,
+            _item: PhantomData,
+        })
+    }
+}
+
+impl<'raw, T> Clone for ArrayIter<'raw, T> {
+    fn clone(&self) -> Self {
+        Self {
+            raw: self.raw,
+            remaining: self.remaining,
+            _item: PhantomData,
+        }
+    }
+}
+
+impl<'raw, T> Iterator for ArrayIter<'raw, T>
+where
+    T: SubRecord<'raw>,
+{
+    type Item = DeResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match T::_deserialize_chained(self.raw) {
+            Ok((read, v)) => {
+                self.raw = &self.raw[read..];
+                self.remaining -= 1;
+                Some(Ok(v))
+            }
+            Err(err) => {
+                self.remaining = 0;
+                Some(Err(err))
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<'raw, T> FusedIterator for ArrayIter<'raw, T> where T: SubRecord<'raw> {}
+
+#[cfg(test)]
+mod test {
+    use crate::{ArrayIter, DeserializeError, SubRecord};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn iter_strs() {
+        let mut buf = Vec::new();
+        vec!["abc", "", "defgh"]._serialize_chained(&mut buf).unwrap();
+        let items = ArrayIter::<&str>::new(&buf)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(items, ["abc", "", "defgh"]);
+    }
+
+    #[test]
+    fn iter_stops_early() {
+        let mut buf = Vec::new();
+        vec![1u32, 2, 3]._serialize_chained(&mut buf).unwrap();
+        // everything after the second item is missing but it is never reached
+        buf.truncate(buf.len() - 4);
+        let found = ArrayIter::<u32>::new(&buf)
+            .unwrap()
+            .find(|v| matches!(v, Ok(2)));
+        assert!(found.is_some());
+    }
+
+    #[test]
+    fn iter_truncated() {
+        let mut buf = Vec::new();
+        vec![1u32, 2]._serialize_chained(&mut buf).unwrap();
+        buf.truncate(buf.len() - 1);
+        let mut iter = ArrayIter::<u32>::new(&buf).unwrap();
+        assert!(matches!(iter.next(), Some(Ok(1))));
+        assert!(matches!(
+            iter.next(),
+            Some(Err(DeserializeError::MoreDataExpected(1)))
+        ));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn iter_empty() {
+        let mut iter = ArrayIter::<u32>::new(&[0, 0, 0, 0]).unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        assert!(iter.next().is_none());
+    }
+}
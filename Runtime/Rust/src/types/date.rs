@@ -1,9 +1,19 @@
-use std::time::Duration;
-use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use core::time::Duration;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use alloc::format;
+use alloc::string::String;
 
 /// The number of ticks between 1/1/0001 and 1/1/1970.
 const TICKS_BETWEEN_EPOCHS: u64 = 621355968000000000;
+/// The number of days between 1/1/0001 and 1/1/1970.
+const DAYS_BETWEEN_EPOCHS: i64 = 719162;
+const TICKS_PER_SEC: u64 = 10_000_000;
+const SECS_PER_DAY: u64 = 86_400;
+/// The largest number of ticks, the top two bits are not part of the value.
+const MAX_TICKS: u64 = 0x3fffffffffffffff;
+#[cfg(any(feature = "chrono", feature = "time"))]
+const DATE_RANGE_ERR: &str = "Date is out of the supported range";
 
 /// A date is stored as a 64-bit integer amount of “ticks” since 00:00:00 UTC on January 1 of year
 /// 1 A.D. in the Gregorian calendar, where a “tick” is 100 nanoseconds.
@@ -11,6 +21,10 @@ const TICKS_BETWEEN_EPOCHS: u64 = 621355968000000000;
 /// The top two bits of this value are ignored by Bebop. In .NET, they are used to specify whether a
 /// date is in UTC or local to the current time zone. But in Bebop, all date-times on the wire are
 /// in UTC.
+///
+/// This is the same as .NET `DateTime.Ticks`, so the Unix epoch is 621355968000000000 ticks. With
+/// the `chrono` or `time` features this converts to and from `DateTime<Utc>` and `OffsetDateTime`,
+/// precision beyond one tick is truncated.
 #[derive(Clone, Copy, Debug)]
 #[repr(transparent)]
 pub struct Date(u64);
@@ -33,7 +47,7 @@ impl PartialEq for Date {
 impl Eq for Date {}
 impl PartialOrd for Date {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.to_ticks().cmp(&other.to_ticks()))
+        Some(self.cmp(other))
     }
 }
 impl Ord for Date {
@@ -61,7 +75,7 @@ impl Date {
     #[inline]
     pub const fn to_ticks(self) -> u64 {
         // because .NET is weird we have to remove top bits
-        self.0 & 0x3fffffffffffffff
+        self.0 & MAX_TICKS
     }
 
     #[inline]
@@ -159,3 +173,369 @@ impl Date {
         self.to_millis_since_unix_epoch_f() / 1000.
     }
 }
+
+impl Date {
+    /// Format as an RFC3339 timestamp in UTC, e.g. `2021-07-29T21:57:35.25Z`.
+    pub fn to_rfc3339(self) -> String {
+        let ticks = self.to_ticks();
+        let secs = ticks / TICKS_PER_SEC;
+        let frac = ticks % TICKS_PER_SEC;
+        let (y, m, d) = civil_from_days((secs / SECS_PER_DAY) as i64 - DAYS_BETWEEN_EPOCHS);
+        let time = secs % SECS_PER_DAY;
+        let mut s = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            y,
+            m,
+            d,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        );
+        if frac != 0 {
+            s.push_str(format!(".{:07}", frac).trim_end_matches('0'));
+        }
+        s.push('Z');
+        s
+    }
+
+    /// Parse an RFC3339 timestamp with any offset. Precision beyond one tick is truncated.
+    pub fn from_rfc3339(s: &str) -> Result<Self, &'static str> {
+        const ERR: &str = "Invalid RFC3339 timestamp";
+        let b = s.as_bytes();
+        let num = |start: usize, end: usize| -> Result<i64, &'static str> {
+            match b.get(start..end) {
+                Some(digits) if digits.iter().all(u8::is_ascii_digit) => Ok(digits
+                    .iter()
+                    .fold(0, |acc, &c| acc * 10 + (c - b'0') as i64)),
+                _ => Err(ERR),
+            }
+        };
+
+        if b.len() < 20
+            || b[4] != b'-'
+            || b[7] != b'-'
+            || !matches!(b[10], b'T' | b't' | b' ')
+            || b[13] != b':'
+            || b[16] != b':'
+        {
+            return Err(ERR);
+        }
+        let (year, month, day) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+        let (hour, min, sec) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+        let days = days_from_civil(year, month, day);
+        if !(1..=12).contains(&month)
+            || civil_from_days(days) != (year, month as u32, day as u32)
+            || hour > 23
+            || min > 59
+            || sec > 60
+        {
+            return Err(ERR);
+        }
+
+        let mut i = 19;
+        let mut frac = 0;
+        if b[i] == b'.' {
+            i += 1;
+            let start = i;
+            while i < b.len() && b[i].is_ascii_digit() {
+                if i - start < 7 {
+                    frac = frac * 10 + (b[i] - b'0') as u64;
+                }
+                i += 1;
+            }
+            if i == start {
+                return Err(ERR);
+            }
+            for _ in (i - start)..7 {
+                frac *= 10;
+            }
+        }
+
+        let offset = match &b[i..] {
+            b"Z" | b"z" => 0,
+            [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+                let offset = num(i + 1, i + 3)? * 3600 + num(i + 4, i + 6)? * 60;
+                if *sign == b'+' {
+                    offset
+                } else {
+                    -offset
+                }
+            }
+            _ => return Err(ERR),
+        };
+
+        let secs =
+            (days + DAYS_BETWEEN_EPOCHS) * SECS_PER_DAY as i64 + hour * 3600 + min * 60 + sec
+                - offset;
+        if secs < 0 {
+            return Err(ERR);
+        }
+        Ok(Date::from_ticks(secs as u64 * TICKS_PER_SEC + frac))
+    }
+}
+
+impl Date {
+    /// Ticks relative to the Unix epoch, negative before 1970.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn unix_ticks(self) -> i64 {
+        self.to_ticks() as i64 - TICKS_BETWEEN_EPOCHS as i64
+    }
+
+    /// Create from ticks relative to the Unix epoch, saturating at the representable range.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn from_unix_ticks_saturating(ticks: i128) -> Self {
+        Self::from_ticks((ticks + TICKS_BETWEEN_EPOCHS as i128).clamp(0, MAX_TICKS as i128) as u64)
+    }
+}
+
+/// Times before 0001-01-01 saturate to the earliest date.
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Date {
+    fn from(dt: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::from_unix_ticks_saturating(
+            dt.timestamp() as i128 * TICKS_PER_SEC as i128
+                + (dt.timestamp_subsec_nanos() / 100) as i128,
+        )
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl core::convert::TryFrom<Date> for chrono::DateTime<chrono::Utc> {
+    type Error = &'static str;
+
+    fn try_from(d: Date) -> Result<Self, Self::Error> {
+        let ticks = d.unix_ticks();
+        let secs = ticks.div_euclid(TICKS_PER_SEC as i64);
+        let nanos = ticks.rem_euclid(TICKS_PER_SEC as i64) as u32 * 100;
+        chrono::DateTime::from_timestamp(secs, nanos).ok_or(DATE_RANGE_ERR)
+    }
+}
+
+/// Times before 0001-01-01 saturate to the earliest date.
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Date {
+    fn from(dt: time::OffsetDateTime) -> Self {
+        Self::from_unix_ticks_saturating(dt.unix_timestamp_nanos().div_euclid(100))
+    }
+}
+
+/// The result is in UTC. This fails for dates after the year 9999 unless the `large-dates`
+/// feature of `time` is enabled.
+#[cfg(feature = "time")]
+impl core::convert::TryFrom<Date> for time::OffsetDateTime {
+    type Error = &'static str;
+
+    fn try_from(d: Date) -> Result<Self, Self::Error> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(d.unix_ticks() as i128 * 100)
+            .map_err(|_| DATE_RANGE_ERR)
+    }
+}
+
+/// Convert days since 1/1/1970 to a (year, month, day) in the proleptic Gregorian calendar.
+/// See http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + (m <= 2) as i64, m, d)
+}
+
+/// Convert a (year, month, day) in the proleptic Gregorian calendar to days since 1/1/1970.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Date {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_rfc3339())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Date {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DateVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for DateVisitor {
+            type Value = Date;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("an RFC3339 timestamp")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Date::from_rfc3339(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(DateVisitor)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Date {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Date::from_ticks(u.arbitrary()?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u64::size_hint(depth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Date;
+
+    #[test]
+    fn to_rfc3339() {
+        assert_eq!(Date::from_ticks(0).to_rfc3339(), "0001-01-01T00:00:00Z");
+        assert_eq!(
+            Date::from_secs_since_unix_epoch(1627595855).to_rfc3339(),
+            "2021-07-29T21:57:35Z"
+        );
+        assert_eq!(
+            Date::from_millis_since_unix_epoch(951782400250).to_rfc3339(),
+            "2000-02-29T00:00:00.25Z"
+        );
+        assert_eq!(
+            Date::from_ticks_since_unix_epoch(1).to_rfc3339(),
+            "1970-01-01T00:00:00.0000001Z"
+        );
+    }
+
+    #[test]
+    fn from_rfc3339() {
+        assert_eq!(
+            Date::from_rfc3339("2021-07-29T21:57:35Z"),
+            Ok(Date::from_secs_since_unix_epoch(1627595855))
+        );
+        assert_eq!(
+            Date::from_rfc3339("2021-07-29t23:57:35.000+02:00"),
+            Ok(Date::from_secs_since_unix_epoch(1627595855))
+        );
+        assert_eq!(
+            Date::from_rfc3339("2000-02-28T20:00:00.25-04:00"),
+            Ok(Date::from_millis_since_unix_epoch(951782400250))
+        );
+        assert_eq!(
+            Date::from_rfc3339("1970-01-01T00:00:00.000000123Z"),
+            Ok(Date::from_ticks_since_unix_epoch(1))
+        );
+    }
+
+    #[test]
+    fn from_rfc3339_invalid() {
+        for s in [
+            "",
+            "2021-07-29",
+            "2021-07-29T21:57:35",
+            "2021-02-29T00:00:00Z",
+            "2021-13-01T00:00:00Z",
+            "2021-07-29T24:00:00Z",
+            "2021-07-29T21:57:35.Z",
+            "2021-07-29T21:57:35+0200",
+            "0001-01-01T00:00:00+00:01",
+            "2021-07-29T21:57:35Zé",
+        ] {
+            assert!(Date::from_rfc3339(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn rfc3339_round_trip() {
+        for ticks in [0, 1, 637631926550000000, 3155378975999999999] {
+            let d = Date::from_ticks(ticks);
+            assert_eq!(Date::from_rfc3339(&d.to_rfc3339()), Ok(d));
+        }
+    }
+
+    /// (RFC3339, .NET `DateTime.Ticks`)
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    const REFERENCE: [(&str, u64); 6] = [
+        ("0001-01-01T00:00:00Z", 0),
+        ("1900-01-01T00:00:00Z", 599266080000000000),
+        ("1969-12-31T23:59:59.9999999Z", 621355967999999999),
+        ("1970-01-01T00:00:00Z", 621355968000000000),
+        ("2021-07-29T21:57:35.1234567Z", 637631926551234567),
+        ("9999-12-31T23:59:59.9999999Z", 3155378975999999999),
+    ];
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_reference() {
+        use chrono::{DateTime, Utc};
+        use std::convert::TryFrom;
+
+        for &(s, ticks) in REFERENCE.iter() {
+            let dt = DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+            assert_eq!(Date::from(dt).to_ticks(), ticks, "{}", s);
+            assert_eq!(DateTime::<Utc>::try_from(Date::from_ticks(ticks)), Ok(dt), "{}", s);
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_truncates_and_saturates() {
+        use chrono::{DateTime, Utc};
+
+        let dt = DateTime::from_timestamp(0, 199).unwrap();
+        assert_eq!(Date::from(dt).to_ticks_since_unix_epoch(), 1);
+        // a day before 0001-01-01
+        let dt = DateTime::from_timestamp(-62135596800 - 86400, 0).unwrap();
+        assert_eq!(Date::from(dt).to_ticks(), 0);
+        assert_eq!(Date::from(DateTime::<Utc>::MAX_UTC).to_ticks(), super::MAX_TICKS);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_reference() {
+        use std::convert::TryFrom;
+        use time::OffsetDateTime;
+
+        for &(s, ticks) in REFERENCE.iter() {
+            let rfc3339 = Date::from_ticks(ticks).to_rfc3339();
+            let dt = OffsetDateTime::try_from(Date::from_ticks(ticks)).unwrap();
+            assert_eq!(Date::from(dt).to_ticks(), ticks, "{}", s);
+            assert_eq!(Date::from_rfc3339(s).unwrap().to_ticks(), ticks, "{}", rfc3339);
+            assert_eq!(
+                dt.unix_timestamp_nanos(),
+                (ticks as i128 - super::TICKS_BETWEEN_EPOCHS as i128) * 100,
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_offset_and_range() {
+        use std::convert::TryFrom;
+        use time::{OffsetDateTime, UtcOffset};
+
+        let utc = OffsetDateTime::from_unix_timestamp(1627595855).unwrap();
+        let offset = utc.to_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(Date::from(offset), Date::from(utc));
+        assert!(OffsetDateTime::try_from(Date::from_ticks(super::MAX_TICKS)).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let d = Date::from_millis_since_unix_epoch(951782400250);
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(json, "\"2000-02-29T00:00:00.25Z\"");
+        assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), d);
+    }
+}
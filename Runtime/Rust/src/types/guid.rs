@@ -1,6 +1,7 @@
-use std::fmt::{self, Display, Formatter};
-use std::ops::Deref;
-use std::str::FromStr;
+use core::cmp::Ordering;
+use core::fmt::{self, Debug, Display, Formatter};
+use core::ops::Deref;
+use core::str::FromStr;
 
 /// The Microsoft ordering for GUID bytes, where each GUID_MAPPING[i] is the ith byte if stored in
 /// big endian format.
@@ -8,7 +9,7 @@ const BYTE_MAP: [usize; 16] = [3, 2, 1, 0, 5, 4, 7, 6, 8, 9, 10, 11, 12, 13, 14,
 
 /// A GUID is a unique identifier. Stored internally in the Microsoft Guid format to support
 /// zero-copy deserialization
-#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[derive(Eq, PartialEq, Hash, Clone, Copy)]
 #[repr(transparent)]
 pub struct Guid([u8; 16]);
 
@@ -45,10 +46,34 @@ impl Display for Guid {
     }
 }
 
+impl Debug for Guid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// Ordered the same way as the hyphenated string form, allowing Guids to be keys of sorted maps.
+impl Ord for Guid {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_be_bytes().cmp(&other.to_be_bytes())
+    }
+}
+
+impl PartialOrd for Guid {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Parses the hyphenated 8-4-4-4-12 form or 32 hex digits without hyphens, ignoring case.
 impl FromStr for Guid {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            // would not be valid anyway and prevents slicing within a char
+            return Err(GUID_PARSE_ERR);
+        }
         match s.len() {
             36 => Self::from_str_with_hyphens(s),
             32 => Self::from_str_without_hyphens(s),
@@ -59,6 +84,45 @@ impl FromStr for Guid {
 
 const GUID_PARSE_ERR: &str = "Failed to parse GUID bytes";
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Guid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Guid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct GuidVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for GuidVisitor {
+            type Value = Guid;
+
+            fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+                f.write_str("a GUID string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(GuidVisitor)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Guid {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Guid::from_be_bytes(u.arbitrary()?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <[u8; 16]>::size_hint(depth)
+    }
+}
+
 impl Guid {
     /// Convert from a byte array ordered by
     /// https://docs.microsoft.com/en-us/dotnet/api/system.guid.tobytearray?view=net-5.0#System_Guid_ToByteArray
@@ -77,6 +141,9 @@ impl Guid {
     }
 
     fn from_str_without_hyphens(s: &str) -> Result<Self, &'static str> {
+        if s.len() != 32 || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(GUID_PARSE_ERR);
+        }
         let mut buf = [0u8; 16];
         // this is inefficient because of the unicode representation used by Rust but probably
         // fine for now
@@ -92,11 +159,27 @@ impl Guid {
 
     #[inline]
     fn from_str_with_hyphens(s: &str) -> Result<Self, &'static str> {
+        let b = s.as_bytes();
+        if b.len() != 36 || b[8] != b'-' || b[13] != b'-' || b[18] != b'-' || b[23] != b'-' {
+            return Err(GUID_PARSE_ERR);
+        }
         // avoid extra copy
-        let without_hyphens: String = s.split('-').collect();
+        let without_hyphens: alloc::string::String = s.split('-').collect();
         Self::from_str_without_hyphens(&without_hyphens)
     }
 
+    /// Convert from the bytes as they are encoded on the wire, which is the same order C# uses.
+    #[inline]
+    pub const fn from_bytes(raw: [u8; 16]) -> Self {
+        Self(raw)
+    }
+
+    /// Get the bytes as they are encoded on the wire, which is the same order C# uses.
+    #[inline]
+    pub const fn to_bytes(self) -> [u8; 16] {
+        self.0
+    }
+
     pub const fn from_le_bytes(b: [u8; 16]) -> Self {
         Self([
             b[15 - BYTE_MAP[0]],
@@ -184,6 +267,8 @@ impl Guid {
     }
 }
 
+#[cfg(test)]
+use alloc::{format, string::ToString};
 #[cfg(test)]
 const BYTES_BE: [u8; 16] = [
     0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
@@ -231,6 +316,14 @@ fn to_be_bytes() {
     assert_eq!(Guid(BYTES_MS).to_be_bytes(), BYTES_BE);
 }
 
+#[test]
+fn ordered_like_strings() {
+    let a = Guid::from_str("00000001-0000-0000-0000-000000000000").unwrap();
+    let b = Guid::from_str("00000000-0000-0000-0000-000000000002").unwrap();
+    assert!(a > b);
+    assert_eq!(a.cmp(&a), Ordering::Equal);
+}
+
 #[test]
 fn from_str() {
     assert_eq!(Guid::from_str(BYTES_STR).unwrap().0, BYTES_MS);
@@ -246,3 +339,51 @@ fn from_str() {
 fn to_str() {
     assert_eq!(Guid(BYTES_MS).to_string(), BYTES_STR);
 }
+
+#[test]
+fn debug_str() {
+    assert_eq!(format!("{:?}", Guid(BYTES_MS)), BYTES_STR);
+}
+
+#[test]
+fn from_str_ignores_case() {
+    assert_eq!(
+        Guid::from_str("00010203-0405-0607-0809-0A0B0C0D0E0F").unwrap().0,
+        BYTES_MS
+    );
+}
+
+#[test]
+fn from_str_misplaced_hyphens() {
+    assert!(Guid::from_str("0001020-30405-0607-0809-0a0b0c0d0e0f").is_err());
+    assert!(Guid::from_str("00010203-0405-0607-0809-0a0b0c0d0e-f").is_err());
+    assert!(Guid::from_str("00010203x0405-0607-0809-0a0b0c0d0e0f").is_err());
+    assert!(Guid::from_str("+0010203-0405-0607-0809-0a0b0c0d0e0f").is_err());
+}
+
+#[test]
+fn bytes_match_csharp() {
+    // new Guid("a3628ec7-28d4-4546-ad4a-f6ebf5375c96").ToByteArray()
+    let cs_bytes = [
+        0xc7, 0x8e, 0x62, 0xa3, 0xd4, 0x28, 0x46, 0x45, 0xad, 0x4a, 0xf6, 0xeb, 0xf5, 0x37, 0x5c,
+        0x96,
+    ];
+    let guid = Guid::from_str("a3628ec7-28d4-4546-ad4a-f6ebf5375c96").unwrap();
+    assert_eq!(guid.to_bytes(), cs_bytes);
+    assert_eq!(Guid::from_bytes(cs_bytes), guid);
+    assert_eq!(guid.to_string(), "a3628ec7-28d4-4546-ad4a-f6ebf5375c96");
+}
+
+#[test]
+fn from_str_not_ascii() {
+    assert!(Guid::from_str("00010203-0405-0607-0809-0a0b0c0d0eé").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let json = serde_json::to_string(&Guid(BYTES_MS)).unwrap();
+    assert_eq!(json, format!("\"{}\"", BYTES_STR));
+    assert_eq!(serde_json::from_str::<Guid>(&json).unwrap(), Guid(BYTES_MS));
+    assert!(serde_json::from_str::<Guid>("\"not a guid\"").is_err());
+}
@@ -0,0 +1,173 @@
+use core::fmt::Debug;
+use core::hash::Hash;
+
+use crate::{FixedSized, SubRecord};
+
+/// Implemented by generated enums, other than flags, to give the integer type they are written as.
+pub trait Enum: Copy {
+    type Repr: Copy + Debug + Eq + Hash + Ord + FixedSized + for<'raw> SubRecord<'raw>;
+
+    /// The member with this value, if there is one.
+    fn from_repr(value: Self::Repr) -> Option<Self>;
+
+    /// The value this member is written as.
+    fn to_repr(self) -> Self::Repr;
+}
+
+/// An enum value which might not be a member of the enum in the schema the code was generated
+/// from, so data written with a newer schema which added members can still be read. Generated
+/// fields use this instead of the enum when the `lenient_enums` feature of the crate with the
+/// generated code is enabled. Writing an unknown value writes the value as it was read.
+///
+/// Decoding only gives `Unknown` for values which are not members, so `Unknown` holding the value
+/// of a member is not equal to that member.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        untagged,
+        bound(
+            serialize = "E: serde::Serialize, E::Repr: serde::Serialize",
+            deserialize = "E: serde::Deserialize<'de>, E::Repr: serde::Deserialize<'de>"
+        )
+    )
+)]
+pub enum MaybeUnknown<E: Enum> {
+    Known(E),
+    Unknown(E::Repr),
+}
+
+impl<E: Enum> MaybeUnknown<E> {
+    /// The member with this value, or `Unknown` if there is none.
+    pub fn from_repr(value: E::Repr) -> Self {
+        match E::from_repr(value) {
+            Some(known) => MaybeUnknown::Known(known),
+            None => MaybeUnknown::Unknown(value),
+        }
+    }
+
+    /// The value this is written as.
+    pub fn to_repr(self) -> E::Repr {
+        match self {
+            MaybeUnknown::Known(known) => known.to_repr(),
+            MaybeUnknown::Unknown(value) => value,
+        }
+    }
+
+    pub fn known(self) -> Option<E> {
+        match self {
+            MaybeUnknown::Known(known) => Some(known),
+            MaybeUnknown::Unknown(_) => None,
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, MaybeUnknown::Unknown(_))
+    }
+}
+
+impl<E: Enum> From<E> for MaybeUnknown<E> {
+    #[inline]
+    fn from(known: E) -> Self {
+        MaybeUnknown::Known(known)
+    }
+}
+
+impl<E: Enum + PartialEq> PartialEq<E> for MaybeUnknown<E> {
+    fn eq(&self, other: &E) -> bool {
+        matches!(self, MaybeUnknown::Known(known) if known == other)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, E> arbitrary::Arbitrary<'a> for MaybeUnknown<E>
+where
+    E: Enum + arbitrary::Arbitrary<'a>,
+    E::Repr: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        // most values of the backing type are unknown so members would rarely be picked otherwise
+        if u.arbitrary()? {
+            Ok(MaybeUnknown::Known(u.arbitrary()?))
+        } else {
+            Ok(Self::from_repr(u.arbitrary()?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{DeResult, DeserializeError};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[repr(u8)]
+    enum Color {
+        Red = 1,
+        Green = 2,
+    }
+
+    impl Enum for Color {
+        type Repr = u8;
+
+        fn from_repr(value: u8) -> Option<Self> {
+            match value {
+                1 => Some(Color::Red),
+                2 => Some(Color::Green),
+                _ => None,
+            }
+        }
+
+        fn to_repr(self) -> u8 {
+            self as u8
+        }
+    }
+
+    fn decode(raw: &[u8]) -> DeResult<(usize, MaybeUnknown<Color>)> {
+        MaybeUnknown::_deserialize_chained(raw)
+    }
+
+    #[test]
+    fn known_and_unknown() {
+        assert_eq!(
+            decode(&[2]).unwrap(),
+            (1, MaybeUnknown::Known(Color::Green))
+        );
+        assert_eq!(decode(&[9]).unwrap(), (1, MaybeUnknown::Unknown(9)));
+        assert!(matches!(
+            decode(&[]),
+            Err(DeserializeError::MoreDataExpected(1))
+        ));
+        assert_eq!(MaybeUnknown::from(Color::Red), Color::Red);
+        assert_ne!(MaybeUnknown::<Color>::Unknown(1), Color::Red);
+        assert_eq!(MaybeUnknown::<Color>::Unknown(9).known(), None);
+    }
+
+    #[test]
+    fn unknown_values_are_kept() {
+        let mut buf = alloc::vec::Vec::new();
+        for value in [MaybeUnknown::Known(Color::Red), MaybeUnknown::Unknown(9)].iter() {
+            assert_eq!(value._serialize_chained(&mut buf).unwrap(), 1);
+        }
+        assert_eq!(buf, [1, 9]);
+        let (_, values) =
+            <crate::SliceWrapper<MaybeUnknown<Color>>>::_deserialize_chained(&[2, 0, 0, 0, 1, 9])
+                .unwrap();
+        assert_eq!(
+            values,
+            [MaybeUnknown::Known(Color::Red), MaybeUnknown::Unknown(9)][..]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let values = [MaybeUnknown::Known(Color::Green), MaybeUnknown::Unknown(9)];
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(json, r#"["Green",9]"#);
+        let de: [MaybeUnknown<Color>; 2] = serde_json::from_str(&json).unwrap();
+        assert_eq!(de, values);
+    }
+}
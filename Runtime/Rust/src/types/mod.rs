@@ -1,7 +1,11 @@
+mod array_iter;
 mod date;
 mod guid;
+mod maybe_unknown;
 mod slice;
 
+pub use array_iter::ArrayIter;
 pub use date::Date;
 pub use guid::Guid;
+pub use maybe_unknown::{Enum, MaybeUnknown};
 pub use slice::SliceWrapper;
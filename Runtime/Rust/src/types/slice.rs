@@ -1,15 +1,17 @@
-use std::mem::size_of;
-
 use crate::{FixedSized, SubRecord};
-use std::iter::FusedIterator;
-use std::ops::Deref;
-use std::ptr::slice_from_raw_parts;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::ops::Deref;
+use core::ptr::slice_from_raw_parts;
 
 /// This allows us to either wrap an existing &[T] slice to serialize it OR to store a raw byte
 /// slice from an encoding and access its potentially unaligned values.
 ///
 /// **Warning:** Creating Raw arrays manually may lead to undefined behavior, use `from_raw`.
-#[derive(Copy, Clone, Debug, PartialEq)]
+///
+/// Comparisons are by value so a raw and a cooked wrapper of the same items are equal, and a
+/// wrapper can be compared with a slice or `Vec` directly.
+#[derive(Copy, Clone, Debug)]
 pub enum SliceWrapper<'a, T: FixedSized> {
     Raw(&'a [u8]),
     Cooked(&'a [T]),
@@ -45,6 +47,91 @@ impl<'a> AsRef<[u8]> for SliceWrapper<'a, u8> {
     }
 }
 
+impl<'a> SliceWrapper<'a, u8> {
+    /// Get the bytes, which never requires a copy since bytes have no alignment.
+    #[inline]
+    pub fn as_slice(&self) -> &'a [u8] {
+        **self
+    }
+}
+
+impl<'a, T> PartialEq for SliceWrapper<'a, T>
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (SliceWrapper::Cooked(a), SliceWrapper::Cooked(b)) => a == b,
+            (SliceWrapper::Cooked(a), b) | (b, SliceWrapper::Cooked(a)) => b == *a,
+            (a, b) => a.len() == b.len() && a.iter().eq(b.iter()),
+        }
+    }
+}
+
+impl<'a, T> PartialEq<[T]> for SliceWrapper<'a, T>
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    fn eq(&self, other: &[T]) -> bool {
+        match *self {
+            SliceWrapper::Cooked(a) => a == other,
+            SliceWrapper::Raw(_) => {
+                self.len() == other.len() && self.iter().eq(other.iter().copied())
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T> PartialEq<&'b [T]> for SliceWrapper<'a, T>
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &&'b [T]) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a, T> PartialEq<Vec<T>> for SliceWrapper<'a, T>
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &Vec<T>) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a, T> PartialEq<SliceWrapper<'a, T>> for [T]
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &SliceWrapper<'a, T>) -> bool {
+        *other == *self
+    }
+}
+
+impl<'a, T> PartialEq<SliceWrapper<'a, T>> for &[T]
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &SliceWrapper<'a, T>) -> bool {
+        *other == **self
+    }
+}
+
+impl<'a, T> PartialEq<SliceWrapper<'a, T>> for Vec<T>
+where
+    T: FixedSized + SubRecord<'a> + PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &SliceWrapper<'a, T>) -> bool {
+        *other == **self
+    }
+}
+
 impl<'a, T> SliceWrapper<'a, T>
 where
     T: FixedSized,
@@ -52,7 +139,7 @@ where
     /// Take in a little-endian array. Most not include size bytes since the slice already has that
     /// info.
     pub fn from_raw(bytes: &'a [u8]) -> Self {
-        assert_eq!(bytes.len() % size_of::<T>(), 0);
+        assert_eq!(bytes.len() % T::SERIALIZED_SIZE, 0);
         Self::Raw(bytes)
     }
 
@@ -69,13 +156,13 @@ where
     pub fn get(&self, i: usize) -> Option<T> {
         match *self {
             SliceWrapper::Raw(raw) => {
-                if i * size_of::<T>() + size_of::<T>() > raw.len() {
+                if i * T::SERIALIZED_SIZE + T::SERIALIZED_SIZE > raw.len() {
                     None
                 } else {
                     let raw: &'a [u8] = unsafe {
                         &*slice_from_raw_parts(
-                            raw.as_ptr().offset((i * size_of::<T>()) as isize),
-                            size_of::<T>(),
+                            raw.as_ptr().add(i * T::SERIALIZED_SIZE),
+                            T::SERIALIZED_SIZE,
                         )
                     };
                     Some(T::_deserialize_chained(raw).map(|(_, v)| v).unwrap())
@@ -107,7 +194,7 @@ where
     #[inline]
     pub fn len(&self) -> usize {
         match *self {
-            SliceWrapper::Raw(raw) => raw.len() / size_of::<T>(),
+            SliceWrapper::Raw(raw) => raw.len() / T::SERIALIZED_SIZE,
             SliceWrapper::Cooked(ary) => ary.len(),
         }
     }
@@ -126,7 +213,7 @@ where
     pub fn size(&self) -> usize {
         match *self {
             SliceWrapper::Raw(raw) => raw.len(),
-            SliceWrapper::Cooked(ary) => ary.len() * size_of::<T>(),
+            SliceWrapper::Cooked(ary) => ary.len() * T::SERIALIZED_SIZE,
         }
     }
 }
@@ -144,6 +231,31 @@ where
     }
 }
 
+/// Serialized as a sequence of values. Generated code represents byte arrays as base64 strings
+/// instead, see `bebop::base64`.
+#[cfg(feature = "serde")]
+impl<'a, T> serde::Serialize for SliceWrapper<'a, T>
+where
+    T: FixedSized + SubRecord<'a> + serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// Arrays of items where any bytes are a valid value are raw data taken from the input, other
+/// arrays such as of `bool`s and enums can not be borrowed from it and are always empty.
+#[cfg(feature = "arbitrary")]
+impl<'a, T: FixedSized> arbitrary::Arbitrary<'a> for SliceWrapper<'a, T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        if !T::IS_WIRE_FORMAT {
+            return Ok(SliceWrapper::Cooked(&[]));
+        }
+        let len = u.int_in_range(0..=u.len() / T::SERIALIZED_SIZE)?;
+        Ok(SliceWrapper::from_raw(u.bytes(len * T::SERIALIZED_SIZE)?))
+    }
+}
+
 pub struct Iter<'a, T: FixedSized>(SliceWrapper<'a, T>, usize);
 
 impl<'a, T> Iterator for Iter<'a, T>
@@ -173,10 +285,11 @@ where
 #[cfg(test)]
 mod test {
     use crate::{DeResult, FixedSized, SeResult, SliceWrapper, SubRecord};
-    use std::convert::TryInto;
-    use std::io::Write;
+    use crate::Write;
+    use alloc::vec;
+    use core::convert::TryInto;
 
-    #[repr(packed)]
+    #[repr(C, packed)]
     #[derive(Debug, Eq, PartialEq, Copy, Clone)]
     struct Fixed {
         a: u8,
@@ -207,7 +320,7 @@ mod test {
 
         fn _serialize_chained<W: Write>(&self, dest: &mut W) -> SeResult<usize> {
             self.a._serialize_chained(dest)?;
-            self.b._serialize_chained(dest)?;
+            { self.b }._serialize_chained(dest)?;
             Ok(9)
         }
 
@@ -241,8 +354,8 @@ mod test {
     fn get_cooked_fixed_struct() {
         let array = cooked_array();
         let s = SliceWrapper::Cooked(array);
-        for i in 0..array.len() {
-            assert_eq!(s.get(i).unwrap(), array[i]);
+        for (i, &v) in array.iter().enumerate() {
+            assert_eq!(s.get(i).unwrap(), v);
         }
     }
 
@@ -250,8 +363,8 @@ mod test {
     fn get_cooked_primitive() {
         let array: &'static [u16] = &[0x0000, 0x1243, 0x8f90, 0x097a];
         let s = SliceWrapper::Cooked(array);
-        for i in 0..array.len() {
-            assert_eq!(s.get(i).unwrap(), array[i]);
+        for (i, &v) in array.iter().enumerate() {
+            assert_eq!(s.get(i).unwrap(), v);
         }
     }
 
@@ -260,7 +373,7 @@ mod test {
         // only happens for big-endian systems or systems where `repr(packed)` is not supported
         let s = <SliceWrapper<Fixed>>::Raw(&[0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
         assert_eq!(s.get(0).unwrap().a, 1);
-        assert_eq!(s.get(0).unwrap().b, 2);
+        assert_eq!({ s.get(0).unwrap().b }, 2);
     }
 
     #[test]
@@ -288,7 +401,7 @@ mod test {
         ]);
         for (i, v) in s.iter().enumerate() {
             assert_eq!(v.a, (i * 2 + 1) as u8);
-            assert_eq!(v.b, (i * 2 + 2) as u64);
+            assert_eq!({ v.b }, (i * 2 + 2) as u64);
         }
     }
 
@@ -320,6 +433,55 @@ mod test {
         assert_eq!(s.size(), 6);
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_raw_primitive() {
+        let s = <SliceWrapper<u16>>::Raw(&[0x01, 0x00, 0x02, 0x00]);
+        assert_eq!(serde_json::to_string(&s).unwrap(), "[1,2]");
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_raw() {
+        use arbitrary::{Arbitrary, Unstructured};
+        let data = [7; 64];
+        let mut u = Unstructured::new(&data);
+        let s = <SliceWrapper<u32>>::arbitrary(&mut u).unwrap();
+        assert!(matches!(s, SliceWrapper::Raw(_)));
+        assert!(s.iter().all(|v| v == 0x0707_0707));
+        assert!(<SliceWrapper<bool>>::arbitrary(&mut u).unwrap().is_empty());
+    }
+
+    #[test]
+    fn eq_raw_cooked() {
+        let raw = <SliceWrapper<u16>>::Raw(&[0x01, 0x00, 0x02, 0x00]);
+        let cooked = <SliceWrapper<u16>>::Cooked(&[1, 2]);
+        assert_eq!(raw, cooked);
+        assert_eq!(cooked, raw);
+        assert_eq!(raw, raw);
+        assert_ne!(raw, <SliceWrapper<u16>>::Cooked(&[1, 2, 3]));
+        assert_ne!(raw, <SliceWrapper<u16>>::Raw(&[0x01, 0x00, 0x03, 0x00]));
+    }
+
+    #[test]
+    fn eq_native() {
+        let bytes = <SliceWrapper<u8>>::Raw(&[1, 2, 3]);
+        assert_eq!(bytes, &[1, 2, 3][..]);
+        assert_eq!(&[1, 2, 3][..], bytes);
+        assert_eq!(bytes, vec![1, 2, 3]);
+        assert_eq!(vec![1, 2, 3], bytes);
+        assert_ne!(bytes, &[1, 2][..]);
+        assert_eq!(bytes.as_slice(), &[1, 2, 3]);
+
+        let raw = <SliceWrapper<Fixed>>::Raw(&[
+            0x17, 0x4c, 0x77, 0xd8, 0x05, 0x00, 0x00, 0x00, 0x00, 0x86, 0x7e, 0xe9, 0x6c, 0x8e,
+            0x00, 0x00, 0x00, 0x00,
+        ]);
+        assert_eq!(raw, &cooked_array()[..2]);
+        assert_eq!(cooked_array()[..2], raw);
+        assert_ne!(raw, cooked_array());
+    }
+
     #[test]
     fn deref_u8_raw() {
         let s = <SliceWrapper<u8>>::Raw(&[0x00, 0x01, 0x04, 0x06]);
@@ -0,0 +1,35 @@
+//! Helpers for the `#[wasm_bindgen]` functions generated when the `wasm` feature of the crate with
+//! the generated code is enabled. Records are converted to and from plain JS objects using their
+//! serde representation, so Guids and Dates are strings and byte arrays are base64 strings.
+
+use alloc::boxed::Box;
+use alloc::string::ToString;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+pub use wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::{OwnedRecord, Record};
+
+/// Convert a JS object to the record `T` and encode it.
+pub fn encode<T>(value: JsValue) -> Result<Box<[u8]>, JsValue>
+where
+    T: OwnedRecord + DeserializeOwned,
+{
+    let record: T = serde_wasm_bindgen::from_value(value)?;
+    let buf = record
+        .encode_to_vec()
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(buf.into_boxed_slice())
+}
+
+/// Decode the record `T` and convert it to a JS object.
+pub fn decode<T>(bytes: &[u8]) -> Result<JsValue, JsValue>
+where
+    T: OwnedRecord + Serialize,
+{
+    let record =
+        <T as Record>::deserialize(bytes).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(serde_wasm_bindgen::to_value(&record)?)
+}
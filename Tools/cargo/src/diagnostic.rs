@@ -0,0 +1,120 @@
+use std::fmt;
+
+/// A compiler error which points at a location in a schema.
+#[derive(Debug)]
+pub(crate) struct Diagnostic<'a> {
+    pub file: &'a str,
+    pub line: u32,
+    pub column: u32,
+    pub message: &'a str,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Parse a line of compiler output in either the default structured log format or the
+    /// `--log-format msbuild` format. The JSON format is not understood.
+    pub fn parse(line: &'a str) -> Option<Self> {
+        Self::parse_structured(line).or_else(|| Self::parse_msbuild(line))
+    }
+
+    /// `[<time>][Compiler][Error] Issue located in '<file>' at <line>:<col>: <message>`
+    fn parse_structured(line: &'a str) -> Option<Self> {
+        let (_, rest) = line.split_once("Issue located in '")?;
+        let (file, rest) = rest.split_once("' at ")?;
+        let (position, message) = rest.split_once(": ")?;
+        let (line, column) = position.split_once(':')?;
+        Some(Self {
+            file,
+            line: line.parse().ok()?,
+            column: column.parse().ok()?,
+            message,
+        })
+    }
+
+    /// `<file>(<line>,<col>) : error BOP<code>: <message>`
+    fn parse_msbuild(line: &'a str) -> Option<Self> {
+        let (location, rest) = line.split_once(" : error ")?;
+        let (_code, message) = rest.split_once(": ")?;
+        let (file, position) = location.strip_suffix(')')?.rsplit_once('(')?;
+        let (line, column) = position.split_once(',')?;
+        Some(Self {
+            file,
+            line: line.parse().ok()?,
+            column: column.parse().ok()?,
+            message,
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> Option<(&str, u32, u32, &str)> {
+        Diagnostic::parse(line).map(|d| (d.file, d.line, d.column, d.message))
+    }
+
+    #[test]
+    fn structured() {
+        assert_eq!(
+            parse("[10/14/2026 10:22:01][Compiler][Error] Issue located in 'C:\\schemas\\a.bop' at 3:14: Unrecognized type name 'Foo'"),
+            Some(("C:\\schemas\\a.bop", 3, 14, "Unrecognized type name 'Foo'"))
+        );
+    }
+
+    #[test]
+    fn msbuild() {
+        assert_eq!(
+            parse("schemas (old)/a.bop(3,14) : error BOP100: Expected ';', but found 'x': here"),
+            Some((
+                "schemas (old)/a.bop",
+                3,
+                14,
+                "Expected ';', but found 'x': here"
+            ))
+        );
+        let d = Diagnostic::parse("a.bop(1,2) : error BOP1: bad").unwrap();
+        assert_eq!(d.to_string(), "a.bop:1:2: bad");
+    }
+
+    /// Only the first line is part of the diagnostic, the rest are passed through as they are.
+    #[test]
+    fn multi_line_message() {
+        let output = "a.bop(1,2) : error BOP1: first line\n  second line\n";
+        let parsed = output.lines().map(parse).collect::<Vec<_>>();
+        assert_eq!(parsed, [Some(("a.bop", 1, 2, "first line")), None]);
+    }
+
+    /// `--log-format json` output is passed through as it is.
+    #[test]
+    fn json() {
+        let line = r#"[{"Message": "Unrecognized type name 'Foo'", "Span": {"FileName":"a.bop","StartLine":2,"EndLine":2,"StartColumn":13,"EndColumn":16,"Lines":0}}]"#;
+        assert_eq!(parse(line), None);
+    }
+
+    #[test]
+    fn unparseable() {
+        for line in [
+            "",
+            "Compiling schemas",
+            "bebopc : fatal error BOP404: cannot open file 'a.bop'",
+            "a.bop(x,2) : error BOP1: bad position",
+            "a.bop(1,2 : error BOP1: no closing parenthesis",
+            "[Compiler][Error] Issue located in 'a.bop' at 3: no column",
+            "[Compiler][Error] Issue located in 'a.bop' at -1:2: negative",
+        ]
+        .iter()
+        {
+            assert_eq!(parse(line), None, "{}", line);
+        }
+    }
+}
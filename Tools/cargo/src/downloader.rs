@@ -1,6 +1,10 @@
 use json::JsonValue;
+use sha2::{Digest, Sha256};
+use std::env;
 use std::fs::{self, File};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 #[cfg(target_os = "windows")]
 const OS_NAME: &str = "win";
@@ -8,66 +12,173 @@ const OS_NAME: &str = "win";
 const OS_NAME: &str = "linux";
 #[cfg(target_os = "macos")]
 const OS_NAME: &str = "mac";
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+const OS_NAME: &str = env::consts::OS;
+
+#[cfg(target_arch = "x86_64")]
+const ARCH_NAME: &str = "x64";
+#[cfg(target_arch = "aarch64")]
+const ARCH_NAME: &str = "arm64";
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const ARCH_NAME: &str = env::consts::ARCH;
+
+/// Whether releases include a compiler for the platform being built on, the names above for other
+/// platforms are only used to report that there is none.
+const HAS_PREBUILT: bool = cfg!(all(
+    any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "macos"
+    ),
+    any(target_arch = "x86_64", target_arch = "aarch64")
+));
 
 /// Version of bebopc to download
 pub const BEBOPC_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Make sure bebopc of the given version is available for the current platform and return the
+/// path to it. The compiler is downloaded into `~/.cache/bebop` (or `OUT_DIR` if there is no home
+/// directory) only if a matching executable is not already there.
+///
+/// Returns a path to the executable and updates the compiler path static variable.
+pub fn ensure_compiler(version: &str) -> PathBuf {
+    let exe_path = crate::canonicalize(download_bebopc_internal(cache_dir(), version));
+    unsafe {
+        crate::COMPILER_PATH = Some(exe_path.clone());
+    }
+    exe_path
+}
+
 /// Download the executeable to the destination path.
 ///
 /// - `dest` directory in which bebopc directory structure will be placed.
 ///
 /// The bebopc directoy is structured as
-/// - `dest/<version>/<os>/`
+/// - `dest/<version>/<os>/<arch>/`
 ///     - <zipfile>
 ///     - <bin>
 ///
 /// Returns a path to the executable and updates the compiler path static variable.
 pub fn download_bebopc(dest: impl AsRef<Path>) -> PathBuf {
-    let exe_path = crate::canonicalize(download_bebopc_internal(dest));
+    let exe_path = crate::canonicalize(download_bebopc_internal(dest, BEBOPC_VERSION));
     unsafe {
         crate::COMPILER_PATH = Some(exe_path.clone());
     }
     exe_path
 }
 
-fn download_bebopc_internal(dest: impl AsRef<Path>) -> PathBuf {
-    let root_path = dest.as_ref().join(BEBOPC_VERSION).join(OS_NAME);
-    let exe_path = if cfg!(target_os = "windows") {
-        root_path.join("bebopc.exe")
-    } else {
-        root_path.join("bebopc")
-    };
+fn download_bebopc_internal(dest: impl AsRef<Path>, version: &str) -> PathBuf {
+    let root_path = dest.as_ref().join(version).join(OS_NAME).join(ARCH_NAME);
+    let exe_name = format!("bebopc{}", env::consts::EXE_SUFFIX);
+    let exe_path = root_path.join(&exe_name);
     if exe_path.exists() {
-        // executable already downloaded
-        return exe_path;
+        if is_version(&exe_path, version) {
+            // executable already downloaded
+            return exe_path;
+        }
+        println!(
+            "cargo:warning=Cached bebopc at {:?} is not version {}, downloading again",
+            exe_path, version
+        );
+        rm_rf(&root_path);
+    }
+    if !HAS_PREBUILT {
+        panic!(
+            "There is no prebuilt bebopc for {} {}, build it from source and set \
+             `bebop_tools::COMPILER_PATH` to it",
+            OS_NAME, ARCH_NAME
+        );
     }
     mkdir_p(&root_path);
-    let zip_name = format!("bebopc-{}64.zip", OS_NAME);
+    let zip_name = asset_name();
     let release_info = get_json(format!(
         "https://api.github.com/repos/rainwayapp/bebop/releases/tags/v{}",
-        BEBOPC_VERSION
+        version
     ));
-    let url = release_info["assets"]
+    let asset = release_info["assets"]
         .members()
         .find(|asset| asset["name"].as_str().unwrap() == zip_name)
-        .expect("Could not find expected asset")["browser_download_url"]
-        .as_str()
-        .unwrap()
-        .to_owned();
+        .expect("Could not find expected asset");
+    let url = asset["browser_download_url"].as_str().unwrap().to_owned();
 
+    // nothing is downloaded which can't be verified
+    let digest = asset["digest"].as_str().unwrap_or_else(|| {
+        panic!(
+            "Release v{} does not publish a checksum for {} so it can't be verified, download it \
+             yourself and set `bebop_tools::COMPILER_PATH` to it",
+            version, zip_name
+        )
+    });
     let zip_path = root_path.join(&zip_name);
     download(url, &zip_path);
+    verify_sha256(&zip_path, digest);
     let tmp_path = &root_path.join("tmp");
-    unzip(&zip_path, &tmp_path);
-    if cfg!(target_os = "windows") {
-        mv(&tmp_path.join("bebopc.exe"), &exe_path);
-    } else {
-        mv(&tmp_path.join("bebopc"), &exe_path);
-    };
-    rm_rf(&tmp_path);
+    unzip(&zip_path, tmp_path);
+    mv(tmp_path.join(&exe_name), &exe_path);
+    rm_rf(tmp_path);
+    make_executable(&exe_path);
     exe_path
 }
 
+/// Name of the release asset for this platform. The x64 builds predate the other architectures and
+/// do not include the architecture in their name.
+fn asset_name() -> String {
+    if ARCH_NAME == "x64" {
+        format!("bebopc-{}64.zip", OS_NAME)
+    } else {
+        format!("bebopc-{}-{}.zip", OS_NAME, ARCH_NAME)
+    }
+}
+
+/// Default location to cache downloaded compilers in.
+fn cache_dir() -> PathBuf {
+    if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+        PathBuf::from(home).join(".cache").join("bebop")
+    } else {
+        PathBuf::from(env::var_os("OUT_DIR").expect("No home directory and OUT_DIR is not set"))
+            .join("bebopc")
+    }
+}
+
+/// Check if the executable reports the expected version.
+fn is_version(exe: impl AsRef<Path>, version: &str) -> bool {
+    Command::new(exe.as_ref())
+        .arg("--version")
+        .output()
+        .map(|output| {
+            output.status.success()
+                && String::from_utf8_lossy(&output.stdout)
+                    .split_whitespace()
+                    .any(|v| v == version)
+        })
+        .unwrap_or(false)
+}
+
+/// Compare the sha256 hash of a file with a digest of the form `sha256:<hex>`, the file is deleted
+/// if they do not match.
+fn verify_sha256(path: impl AsRef<Path>, digest: &str) {
+    let expected = digest
+        .strip_prefix("sha256:")
+        .unwrap_or_else(|| panic!("Unsupported digest {}", digest));
+    let mut file = File::open(path.as_ref()).expect("Could not access downloaded file");
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).expect("Could not read downloaded file");
+    let actual: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if !actual.eq_ignore_ascii_case(expected) {
+        fs::remove_file(path.as_ref()).expect("Failed to remove corrupt download");
+        panic!(
+            "Checksum mismatch for {:?}, expected {} but got {}",
+            path.as_ref(),
+            expected,
+            actual
+        );
+    }
+}
+
 /// Make a directory and parents as required, equiv to `mkdir -p`
 fn mkdir_p(path: impl AsRef<Path>) {
     fs::create_dir_all(path).expect("Failed to create directory");
@@ -89,6 +200,20 @@ fn mv(from: impl AsRef<Path>, to: impl AsRef<Path>) {
     }
 }
 
+/// Mark a file as executable, equiv to `chmod +x`
+#[cfg(unix)]
+fn make_executable(path: impl AsRef<Path>) {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(path.as_ref())
+        .expect("Could not access file")
+        .permissions();
+    perms.set_mode(perms.mode() | 0o111);
+    fs::set_permissions(path, perms).expect("Failed to make file executable");
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: impl AsRef<Path>) { /* No-op */ }
+
 /// Extract a `.zip` archive.
 fn unzip(path: impl AsRef<Path>, dest: impl AsRef<Path>) {
     let file = File::open(path).expect("Could not access archive file");
@@ -123,3 +248,52 @@ fn get_json(uri: impl reqwest::IntoUrl) -> JsonValue {
     }
     json::parse(&res.text().unwrap()).expect("Failed to parse JSON response")
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::panic;
+
+    /// A file in the temporary directory which is deleted when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = env::temp_dir().join(format!("bebop-tools-{}-{}", std::process::id(), name));
+            fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    const ABC_SHA256: &str =
+        "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn checksum_match() {
+        let file = TempFile::new("match", b"abc");
+        verify_sha256(&file.0, ABC_SHA256);
+        assert!(file.0.exists());
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let file = TempFile::new("mismatch", b"abd");
+        let result = panic::catch_unwind(|| verify_sha256(&file.0, ABC_SHA256));
+        assert!(result.is_err());
+        // a corrupt download is not left to be used later
+        assert!(!file.0.exists());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported digest")]
+    fn checksum_unsupported() {
+        let file = TempFile::new("unsupported", b"abc");
+        verify_sha256(&file.0, "md5:900150983cd24fb0d6963f7d28e17f72");
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::canonicalize;
+
+/// Get the paths of the `import` statements in a schema as they are written, relative to the
+/// directory of the schema. Comments and string values are skipped.
+pub(crate) fn parse_imports(text: &str) -> Vec<&str> {
+    let mut imports = Vec::new();
+    let mut rest = text;
+    let mut at_boundary = true;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |i| &rest[i..]);
+            continue;
+        }
+        if rest.starts_with("/*") {
+            rest = rest[2..].find("*/").map_or("", |i| &rest[i + 4..]);
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            rest = rest[1..].find(c).map_or("", |i| &rest[i + 2..]);
+            at_boundary = false;
+            continue;
+        }
+        if at_boundary && rest.starts_with("import") {
+            let after = &rest["import".len()..];
+            let path = after.trim_start();
+            if let Some(quote) = path.chars().next().filter(|q| *q == '"' || *q == '\'') {
+                if let Some(end) = path[1..].find(quote) {
+                    imports.push(&path[1..end + 1]);
+                    rest = &path[end + 2..];
+                    continue;
+                }
+            }
+        }
+        at_boundary = !(c.is_alphanumeric() || c == '_');
+        rest = &rest[c.len_utf8()..];
+    }
+    imports
+}
+
+/// The schemas imported by `schema`, resolved relative to its directory. Schemas which can not be
+/// read are left out so the compiler can report them.
+fn direct_imports(schema: &Path) -> Vec<PathBuf> {
+    let text = match fs::read_to_string(schema) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+    let dir = schema.parent().unwrap();
+    parse_imports(&text)
+        .into_iter()
+        .map(|import| dir.join(import))
+        .filter(|import| import.is_file())
+        .map(canonicalize)
+        .collect()
+}
+
+/// All schemas imported by `schema` directly or indirectly, not including `schema` itself.
+pub(crate) fn transitive_imports(schema: &Path) -> Vec<PathBuf> {
+    let schema = canonicalize(schema);
+    let mut found = vec![schema.clone()];
+    let mut pending = vec![schema];
+    while let Some(next) = pending.pop() {
+        for import in direct_imports(&next) {
+            if !found.contains(&import) {
+                found.push(import.clone());
+                pending.push(import);
+            }
+        }
+    }
+    found.remove(0);
+    found
+}
+
+/// Schemas which are connected by imports, including through schemas outside of the source
+/// directory. The compiler includes imported definitions in the code it generates, so each group
+/// is compiled together to make sure every definition is only generated once.
+pub(crate) struct ImportGroups {
+    /// The sorted members of the group of each schema which imports or is imported by another.
+    groups: BTreeMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl ImportGroups {
+    pub fn new<'a>(schemas: impl IntoIterator<Item = &'a PathBuf>) -> Self {
+        let schemas = schemas.into_iter().map(canonicalize).collect::<Vec<_>>();
+
+        // union find over every schema reachable from the given ones
+        let mut parent: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+        fn root(parent: &BTreeMap<PathBuf, PathBuf>, mut node: PathBuf) -> PathBuf {
+            while let Some(next) = parent.get(&node).filter(|next| **next != node) {
+                node = next.clone();
+            }
+            node
+        }
+        let mut visited = BTreeSet::new();
+        let mut pending = schemas.clone();
+        while let Some(schema) = pending.pop() {
+            if !visited.insert(schema.clone()) {
+                continue;
+            }
+            parent
+                .entry(schema.clone())
+                .or_insert_with(|| schema.clone());
+            for import in direct_imports(&schema) {
+                parent
+                    .entry(import.clone())
+                    .or_insert_with(|| import.clone());
+                let (a, b) = (root(&parent, schema.clone()), root(&parent, import.clone()));
+                if a != b {
+                    parent.insert(a, b);
+                }
+                pending.push(import);
+            }
+        }
+
+        let mut members: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        for schema in &schemas {
+            members
+                .entry(root(&parent, schema.clone()))
+                .or_default()
+                .push(schema.clone());
+        }
+        let mut groups = BTreeMap::new();
+        for (_, mut group) in members {
+            group.sort();
+            group.dedup();
+            if group.len() < 2 {
+                continue;
+            }
+            for schema in &group {
+                groups.insert(schema.clone(), group.clone());
+            }
+        }
+        Self { groups }
+    }
+
+    /// The schemas `schema` is compiled with, sorted so the first is the one whose module the code
+    /// is written to. `None` if it is compiled on its own.
+    pub fn group(&self, schema: &Path) -> Option<&[PathBuf]> {
+        self.groups.get(&canonicalize(schema)).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    /// A directory of schemas which is deleted on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = env::temp_dir().join(format!("bebop-tools-{}-{}", std::process::id(), name));
+            for (path, text) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, text).unwrap();
+            }
+            TempDir(canonicalize(dir))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn imports_in_comments() {
+        let text = r#"
+            // import "line.bop"
+            import "a.bop"; // import "after.bop"
+            /* import "block.bop"
+               import "lines.bop" */
+            /** import "doc.bop" */ import 'b.bop'
+            /* unterminated import "c.bop"
+        "#;
+        assert_eq!(parse_imports(text), ["a.bop", "b.bop"]);
+    }
+
+    #[test]
+    fn import_in_strings() {
+        let text = r#"
+            import "a.bop"
+            const string s = "import 'str.bop'";
+            [opcode('import "op.bop"')]
+            struct S { int32 reimport; }
+            import "b.bop"
+        "#;
+        assert_eq!(parse_imports(text), ["a.bop", "b.bop"]);
+    }
+
+    #[test]
+    fn relative_parent_paths() {
+        let dir = TempDir::new(
+            "relative",
+            &[
+                ("schemas/main.bop", r#"import "../shared/a.bop""#),
+                ("shared/a.bop", r#"import "b.bop""#),
+                ("shared/b.bop", ""),
+            ],
+        );
+        assert_eq!(
+            parse_imports(&fs::read_to_string(dir.0.join("schemas/main.bop")).unwrap()),
+            ["../shared/a.bop"]
+        );
+        assert_eq!(
+            transitive_imports(&dir.0.join("schemas/main.bop")),
+            [dir.0.join("shared/a.bop"), dir.0.join("shared/b.bop")]
+        );
+    }
+
+    #[test]
+    fn import_cycle() {
+        let dir = TempDir::new(
+            "cycle",
+            &[
+                ("a.bop", r#"import "b.bop""#),
+                ("b.bop", r#"import "c.bop""#),
+                ("c.bop", r#"import "a.bop""#),
+                ("alone.bop", ""),
+            ],
+        );
+        let schemas = ["c.bop", "a.bop", "alone.bop", "b.bop"]
+            .iter()
+            .map(|name| dir.0.join(name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitive_imports(&schemas[1]),
+            [dir.0.join("b.bop"), dir.0.join("c.bop")]
+        );
+        let groups = ImportGroups::new(&schemas);
+        let group = [
+            dir.0.join("a.bop"),
+            dir.0.join("b.bop"),
+            dir.0.join("c.bop"),
+        ];
+        for name in ["a.bop", "b.bop", "c.bop"].iter() {
+            assert_eq!(groups.group(&dir.0.join(name)), Some(&group[..]));
+        }
+        assert_eq!(groups.group(&dir.0.join("alone.bop")), None);
+    }
+}
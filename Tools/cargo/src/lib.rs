@@ -1,14 +1,22 @@
-use std::collections::LinkedList;
+use diagnostic::Diagnostic;
+use parallel::Precompiled;
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::ptr::addr_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-/// Configurable compiler path. By default it will use the downloaded executeable or assume it is in PATH.
+/// Configurable compiler path. By default it will use the downloaded executeable (see
+/// `ensure_compiler`) or assume it is in the working directory.
 pub static mut COMPILER_PATH: Option<PathBuf> = None;
 pub static mut GENERATED_PREFIX: Option<String> = None;
 
+mod diagnostic;
 #[cfg(feature = "downloader")]
 mod downloader;
+mod imports;
+mod parallel;
 #[cfg(feature = "downloader")]
 pub use downloader::*;
 
@@ -18,20 +26,20 @@ fn rustfmt_path() -> &'static Path {
     // lazy static var
     static mut PATH: Option<PathBuf> = None;
 
-    if let Some(path) = unsafe { PATH.as_ref() } {
+    if let Some(path) = unsafe { (*addr_of!(PATH)).as_ref() } {
         return path
     }
 
     if let Ok(path) = std::env::var("RUSTFMT") {
         unsafe {
             PATH = Some(PathBuf::from(path));
-            PATH.as_ref().unwrap()
+            (*addr_of!(PATH)).as_ref().unwrap()
         }
     } else {
         // assume it is in PATH
         unsafe {
             PATH = Some(which::which("rustmft").unwrap_or_else(|_| "rustfmt".into()));
-            PATH.as_ref().unwrap()
+            (*addr_of!(PATH)).as_ref().unwrap()
         }
     }
 }
@@ -49,39 +57,605 @@ fn fmt_file(path: impl AsRef<Path>) {
 }
 
 #[cfg(not(feature = "format"))]
-fn fmt_file(path: impl AsRef<Path>) { /* No-op */ }
+fn fmt_file(_path: impl AsRef<Path>) { /* No-op */ }
 
-/// Build all schemas in a given directory and write them to the destination directory including a
-/// `mod.rs` file.
+/// Build all schemas in a given directory tree and write them to the destination directory. The
+/// subdirectory structure of the source is mirrored as nested modules with a `mod.rs` file in each
+/// directory.
+///
+/// Cargo is told to rerun the build script if any schema, the compiler, or anything in the
+/// destination directory changes. Generated files are only rewritten when their contents change.
+///
+/// Imports are resolved by the compiler relative to the directory of the schema containing the
+/// `import` statement, there is no other search path. Since the compiler generates code for
+/// imported definitions too, schemas which are connected by imports are compiled together and
+/// written to the module of the first of them by path, and the modules of the others re-export it.
+/// This way every definition has a single type no matter which module it is used from.
+///
+/// Schemas are compiled in parallel with up to `NUM_JOBS` compilers at once, see
+/// [`BuildConfig::jobs`]. If any fail, the error for the first of them by path is reported.
+///
+/// To write everything to a single file instead, see [`OutputLayout::SingleFile`].
 ///
 /// **WARNING: THIS DELETES DATA IN THE DESTINATION DIRECTORY** use something like `src/bebop` or `src/generated`.
 pub fn build_schema_dir(source: impl AsRef<Path>, destination: impl AsRef<Path>) {
-    if !destination.as_ref().exists() {
-        fs::create_dir_all(destination.as_ref()).unwrap();
+    BuildConfig::default().build_schema_dir(source, destination)
+}
+
+/// Build all schemas in a given directory tree and write them directly to the destination
+/// directory including a `mod.rs` file. Subdirectories are searched but not reflected in the
+/// output. Imports are handled the same as [`build_schema_dir`].
+///
+/// **WARNING: THIS DELETES DATA IN THE DESTINATION DIRECTORY** use something like `src/bebop` or `src/generated`.
+pub fn build_schema_dir_flat(source: impl AsRef<Path>, destination: impl AsRef<Path>) {
+    BuildConfig::default().build_schema_dir_flat(source, destination)
+}
+
+/// Build all schemas in a given directory tree and write each to the module `namespace` maps its
+/// schema namespace to, creating a `mod.rs` file for every module.
+///
+/// The namespace of a schema is the path of its directory relative to `source` joined with `.`, so
+/// `com/acme/payments/invoice.bop` is in `com.acme.payments` and schemas directly within `source`
+/// are in the empty namespace. `namespace` returns a module path relative to `destination` such as
+/// `payments` or `billing::payments`, an empty path is `destination` itself. Each module declares
+/// its schemas and re-exports their contents, so `namespace = |_| String::new()` makes every
+/// definition available directly from the `destination` module. Imports are handled the same as
+/// [`build_schema_dir`].
+///
+/// **WARNING: THIS DELETES DATA IN THE DESTINATION DIRECTORY** use something like `src/bebop` or `src/generated`.
+pub fn build_schema_dir_with_namespaces(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    namespace: impl Fn(&str) -> String,
+) {
+    BuildConfig::default().build_schema_dir_with_namespaces(source, destination, namespace)
+}
+
+/// Options for invoking the compiler. The free functions in this crate use the default config.
+///
+/// ```no_run
+/// bebop_tools::BuildConfig::default()
+///     .arg("--no-warn")
+///     .build_schema_dir("schemas", "src/generated");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BuildConfig {
+    /// Additional arguments passed to `bebopc` after the input and output arguments.
+    pub extra_args: Vec<String>,
+    /// How [`BuildConfig::build_schema_dir`] writes the generated code.
+    pub layout: OutputLayout,
+    /// How many items arrays hold before allocating when the `smallvec` feature of the crate with
+    /// the generated code is enabled, `bebop::DEFAULT_SMALL_ARRAY_CAPACITY` if unset.
+    pub small_array_capacity: Option<usize>,
+    /// How many compilers the directory builders run at once, the `NUM_JOBS` cargo sets for build
+    /// scripts or the available parallelism if unset.
+    pub jobs: Option<usize>,
+}
+
+/// How [`BuildConfig::build_schema_dir`] writes the generated code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputLayout {
+    /// A file for each schema and a directory with a `mod.rs` file for each subdirectory.
+    #[default]
+    FilePerSchema,
+    /// A single file at the destination path, such as `src/generated.rs`, with an inline module
+    /// for each subdirectory and schema. The module paths are the same as with `FilePerSchema`,
+    /// but there is only one module file for the compiler to load and it can be brought in with
+    /// `include!`. Nothing else is written or deleted.
+    ///
+    /// ```no_run
+    /// use bebop_tools::{BuildConfig, OutputLayout};
+    ///
+    /// BuildConfig::default()
+    ///     .layout(OutputLayout::SingleFile)
+    ///     .build_schema_dir("schemas", "src/generated.rs");
+    /// ```
+    SingleFile,
+}
+
+impl BuildConfig {
+    /// Pass an additional argument to `bebopc`.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.extra_args.push(arg.into());
+        self
     }
 
-    // clean all previously built files
-    fs::read_dir(destination.as_ref())
-        .unwrap()
-        .filter_map(|entry| {
-            let entry = entry.unwrap();
-            let name = entry.file_name().to_str().unwrap().to_string();
-            if entry.file_type().unwrap().is_file() && name != "mod.rs" {
-                Some(name)
+    /// Pass additional arguments to `bebopc`.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.extra_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set how [`BuildConfig::build_schema_dir`] writes the generated code.
+    pub fn layout(mut self, layout: OutputLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set how many items arrays hold before allocating when the `smallvec` feature of the crate
+    /// with the generated code is enabled. This sets `BEBOP_SMALL_ARRAY_CAPACITY` for the crate
+    /// being built, so it applies to all of its generated code.
+    pub fn small_array_capacity(mut self, capacity: usize) -> Self {
+        self.small_array_capacity = Some(capacity);
+        self
+    }
+
+    /// Set how many compilers the directory builders run at once, `1` compiles one schema at a
+    /// time.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Same as [`build_schema_dir`] using this config. With [`OutputLayout::SingleFile`]
+    /// `destination` is the file to write rather than a directory.
+    pub fn build_schema_dir(&self, source: impl AsRef<Path>, destination: impl AsRef<Path>) {
+        if self.layout == OutputLayout::SingleFile {
+            return self.build_schema_dir_single_file(source.as_ref(), destination.as_ref());
+        }
+
+        let (source, destination) = prepare_dirs(source.as_ref(), destination.as_ref());
+        let mut schemas = Vec::new();
+        collect_schemas(&source, &mut Vec::new(), &mut schemas);
+        let compiled = Precompiled::new(self, &schemas);
+        let module_of = |schema: &Path| nested_module(&source, schema);
+
+        // build all files and update lib.rs
+        let files = self.recurse_schema_dir(&source, &destination, true, &compiled, &module_of);
+        write_mod_file(&destination, files, true);
+    }
+
+    fn build_schema_dir_single_file(&self, source: &Path, destination: &Path) {
+        if let Some(dir) = destination
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let source = canonicalize(source);
+        println!("cargo:rerun-if-changed={}", source.to_str().unwrap());
+        println!("cargo:rerun-if-changed={}", destination.to_str().unwrap());
+        let mut schemas = Vec::new();
+        collect_schemas(&source, &mut Vec::new(), &mut schemas);
+        let compiled = Precompiled::new(self, &schemas);
+        let module_of = |schema: &Path| nested_module(&source, schema);
+
+        let code = self.inline_schema_dir(&source, &compiled, &module_of);
+        write_code(destination, &code);
+    }
+
+    /// Same as [`build_schema_dir_flat`] using this config.
+    pub fn build_schema_dir_flat(&self, source: impl AsRef<Path>, destination: impl AsRef<Path>) {
+        let (source, destination) = prepare_dirs(source.as_ref(), destination.as_ref());
+        let mut schemas = Vec::new();
+        collect_schemas(&source, &mut Vec::new(), &mut schemas);
+        let compiled = Precompiled::new(self, &schemas);
+        let module_of = |schema: &Path| vec![module_name(schema)];
+
+        // build all files and update lib.rs
+        let files = self.recurse_schema_dir(&source, &destination, false, &compiled, &module_of);
+        write_mod_file(&destination, files, false);
+    }
+
+    /// Same as [`build_schema_dir_with_namespaces`] using this config.
+    pub fn build_schema_dir_with_namespaces(
+        &self,
+        source: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+        namespace: impl Fn(&str) -> String,
+    ) {
+        let (source, destination) = prepare_dirs(source.as_ref(), destination.as_ref());
+
+        let mut schemas = Vec::new();
+        collect_schemas(&source, &mut Vec::new(), &mut schemas);
+        let compiled = Precompiled::new(self, &schemas);
+        let schema_modules = schemas
+            .iter()
+            .map(|(schema, ns)| {
+                let module = namespace(ns)
+                    .split("::")
+                    .filter(|m| !m.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                (canonicalize(schema), module)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let module_of = |schema: &Path| {
+            let mut module = schema_modules[&canonicalize(schema)].clone();
+            module.push(module_name(schema));
+            module
+        };
+
+        // every module which needs a mod file, with its child modules and schemas
+        let mut modules: BTreeMap<Vec<String>, (BTreeSet<String>, LinkedList<String>)> =
+            BTreeMap::new();
+        modules.insert(Vec::new(), Default::default());
+        for (schema, _) in schemas {
+            let module = schema_modules[&canonicalize(&schema)].clone();
+            for depth in 0..module.len() {
+                modules
+                    .entry(module[..depth].to_vec())
+                    .or_default()
+                    .0
+                    .insert(module[depth].clone());
+            }
+            let fname = module_name(&schema);
+            let dir = module
+                .iter()
+                .fold(destination.clone(), |dir, m| dir.join(m));
+            self.build_grouped(
+                &schema,
+                &dir.join(fname.clone() + ".rs"),
+                &compiled,
+                &module_of,
+            );
+            modules.entry(module).or_default().1.push_back(fname);
+        }
+
+        for (module, (children, schemas)) in modules {
+            let dir = module
+                .iter()
+                .fold(destination.clone(), |dir, m| dir.join(m));
+            fs::create_dir_all(&dir).unwrap();
+            let mut all = schemas.clone();
+            all.extend(children.iter().cloned());
+            remove_stale(&dir, &all, true);
+            let mut contents = String::new();
+            for child in children {
+                contents.push_str(&format!("pub mod {};\n", child));
+            }
+            for schema in schemas {
+                contents.push_str(&format!("pub mod {0};\npub use {0}::*;\n", schema));
+            }
+            let tmp = tmp_path("mod.rs");
+            fs::write(&tmp, contents).unwrap();
+            fmt_file(&tmp);
+            move_if_changed(tmp, dir.join("mod.rs"));
+        }
+    }
+
+    /// Same as [`build_schema`] using this config.
+    pub fn build_schema(&self, schema: impl AsRef<Path>, destination: impl AsRef<Path>) {
+        self.build_schemas(&[schema.as_ref().to_path_buf()], destination.as_ref())
+    }
+
+    /// Build `schema` to `destination` unless it is connected to other schemas by imports, in which
+    /// case the whole group is built to the module of its first schema and the modules of the rest
+    /// re-export it. `module_of` gives the module path of a schema relative to the destination
+    /// directory, ending with the module of the schema itself.
+    fn build_grouped(
+        &self,
+        schema: &Path,
+        destination: &Path,
+        compiled: &Precompiled,
+        module_of: &dyn Fn(&Path) -> Vec<String>,
+    ) {
+        let code = self.compile_grouped(schema, compiled, module_of);
+        println!("cargo:rerun-if-changed={}", destination.to_str().unwrap());
+        write_code(destination, &code);
+    }
+
+    /// The code for the module of `schema`, see [`BuildConfig::build_grouped`].
+    fn compile_grouped(
+        &self,
+        schema: &Path,
+        compiled: &Precompiled,
+        module_of: &dyn Fn(&Path) -> Vec<String>,
+    ) -> String {
+        let group = match compiled.groups.group(schema) {
+            None => return compiled.code(self, &[schema.to_path_buf()]),
+            Some(group) if group[0] == canonicalize(schema) => return compiled.code(self, group),
+            Some(group) => group,
+        };
+
+        println!("cargo:rerun-if-changed={}", schema.to_str().unwrap());
+        let module = module_of(&group[0]).join("::");
+        let supers = "super::".repeat(module_of(schema).len());
+        format!(
+            "//! Compiled together with the schemas it is connected to by imports in `{}`.\n\npub use {}{}::*;\n",
+            module, supers, module
+        )
+    }
+
+    /// Compile the schemas as a single unit and write the code to `destination`. Cargo is told to
+    /// rerun the build script if any of them or anything they import changes.
+    fn build_schemas(&self, schemas: &[PathBuf], destination: &Path) {
+        println!("cargo:rerun-if-changed={}", destination.to_str().unwrap());
+        write_code(destination, &self.compile_schemas(schemas));
+    }
+
+    /// Compile the schemas as a single unit and return the code, panicking with the compiler
+    /// output if it fails. Cargo is told to rerun the build script if any of them or anything
+    /// they import changes.
+    fn compile_schemas(&self, schemas: &[PathBuf]) -> String {
+        let result = self.generate(&compiler_path(), schemas);
+        self.finish_compile(schemas, result)
+    }
+
+    /// Report the result of compiling the schemas to cargo, see [`BuildConfig::compile_schemas`].
+    fn finish_compile(
+        &self,
+        schemas: &[PathBuf],
+        result: Result<String, (Vec<String>, String)>,
+    ) -> String {
+        if let Some(capacity) = self.small_array_capacity {
+            println!("cargo:rustc-env=BEBOP_SMALL_ARRAY_CAPACITY={}", capacity);
+        }
+        let compiler_path = compiler_path();
+        println!("cargo:rerun-if-changed={}", compiler_path.to_str().unwrap());
+        for schema in schemas {
+            println!("cargo:rerun-if-changed={}", schema.to_str().unwrap());
+            for import in imports::transitive_imports(schema) {
+                println!("cargo:rerun-if-changed={}", import.to_str().unwrap());
+            }
+        }
+        match result {
+            Ok(code) => code,
+            Err((lines, summary)) => {
+                println!(
+                    "cargo:warning=Failed to build schema {}",
+                    schemas[0].to_str().unwrap()
+                );
+                for line in lines {
+                    println!("cargo:warning={}", line);
+                }
+                panic!("Failed to build schema {}", summary);
+            }
+        }
+    }
+
+    /// Run `compiler` on the schemas and return the generated code, or the compiler output as
+    /// described in [`BuildConfig::run_compiler`]. Nothing is printed so this can be run from
+    /// any thread.
+    fn generate(
+        &self,
+        compiler: &Path,
+        schemas: &[PathBuf],
+    ) -> Result<String, (Vec<String>, String)> {
+        let tmp = tmp_path(file_stem(&schemas[0]) + ".rs");
+        self.run_compiler(compiler, schemas, &tmp)?;
+        let code = fs::read_to_string(&tmp).unwrap();
+        fs::remove_file(tmp).unwrap();
+        Ok(code)
+    }
+
+    /// Compile a single schema and return the generated code. Unlike [`BuildConfig::build_schema`]
+    /// nothing is printed for cargo and the code is not formatted, so this can be used outside of
+    /// build scripts. The error includes all of the compiler output.
+    pub fn compile_schema(&self, schema: impl AsRef<Path>) -> Result<String, String> {
+        let schema = schema.as_ref();
+        self.generate(&compiler_path(), &[schema.to_path_buf()])
+            .map_err(|(lines, summary)| {
+                format!("Failed to build schema {}\n{}", summary, lines.join("\n"))
+            })
+    }
+
+    /// Run `compiler` on the schemas writing the generated code to `output`. On failure returns
+    /// the compiler output, with diagnostics as `file:line:col: message`, and a summary naming the
+    /// schemas or the location of the first error.
+    fn run_compiler(
+        &self,
+        compiler: &Path,
+        schemas: &[PathBuf],
+        output: &Path,
+    ) -> Result<(), (Vec<String>, String)> {
+        let result = Command::new(compiler)
+            .arg("--files")
+            .args(schemas)
+            .arg("--rust")
+            .arg(output.to_str().unwrap())
+            .args(&self.extra_args)
+            .output()
+            .expect("Could not run bebopc");
+        if result.status.success() {
+            return Ok(());
+        }
+
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let mut lines = Vec::new();
+        let mut diagnostics = Vec::new();
+        let output = stdout.lines().map(|l| ("STDOUT", l));
+        for (stream, line) in output.chain(stderr.lines().map(|l| ("STDERR", l))) {
+            if let Some(diagnostic) = Diagnostic::parse(line) {
+                lines.push(diagnostic.to_string());
+                diagnostics.push(diagnostic);
+            } else {
+                lines.push(format!("{}: {}", stream, line));
+            }
+        }
+        let summary = match diagnostics.as_slice() {
+            [] => format!(
+                "{} ({}):\n{}",
+                schemas
+                    .iter()
+                    .map(|schema| schema.to_str().unwrap())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                result.status,
+                stderr.trim_end()
+            ),
+            [first] => format!("at {}", first),
+            [first, rest @ ..] => format!("at {} (and {} more)", first, rest.len()),
+        };
+        Err((lines, summary))
+    }
+
+    /// Build all schemas in `dir` and return the names of the modules written to `dest`. If `nested`,
+    /// each subdirectory becomes its own module directory, otherwise its schemas are written to `dest`.
+    fn recurse_schema_dir(
+        &self,
+        dir: &Path,
+        dest: &Path,
+        nested: bool,
+        compiled: &Precompiled,
+        module_of: &dyn Fn(&Path) -> Vec<String>,
+    ) -> LinkedList<String> {
+        let mut list = LinkedList::new();
+        let mut entries = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect::<Vec<_>>();
+        // keep the generated module files stable
+        entries.sort_by_key(|entry| entry.file_name());
+        for dir_entry in entries {
+            let file_type = dir_entry.file_type().unwrap();
+            let file_path = dir.join(dir_entry.file_name());
+            if file_type.is_dir() {
+                if dir_entry.file_name() == "ShouldFail" {
+                    // do nothing
+                } else if nested {
+                    let name = dir_entry.file_name().to_str().unwrap().to_string();
+                    let sub_dest = dest.join(&name);
+                    let modules =
+                        self.recurse_schema_dir(&file_path, &sub_dest, true, compiled, module_of);
+                    if !modules.is_empty() {
+                        write_mod_file(&sub_dest, modules, true);
+                        list.push_back(name);
+                    }
+                } else {
+                    list.append(
+                        &mut self.recurse_schema_dir(&file_path, dest, false, compiled, module_of),
+                    );
+                }
+            } else if file_type.is_file()
+                && file_path
+                    .extension()
+                    .map(|s| s.to_str().unwrap())
+                    .unwrap_or("")
+                    == "bop"
+            {
+                let fname = module_name(&file_path);
+                self.build_grouped(
+                    &file_path,
+                    &dest.join(fname.clone() + ".rs"),
+                    compiled,
+                    module_of,
+                );
+                list.push_back(fname);
             } else {
-                None
+                // do nothing
             }
-        })
-        .for_each(|file| fs::remove_file(PathBuf::from(destination.as_ref()).join(file)).unwrap());
+        }
+        list
+    }
 
-    // build all files and update lib.rs
-    let files = recurse_schema_dir(source, destination.as_ref());
+    /// Build all schemas in `dir` and return their code with an inline module for each schema and
+    /// subdirectory, in the same order [`BuildConfig::recurse_schema_dir`] writes them.
+    fn inline_schema_dir(
+        &self,
+        dir: &Path,
+        compiled: &Precompiled,
+        module_of: &dyn Fn(&Path) -> Vec<String>,
+    ) -> String {
+        let mut code = String::new();
+        let mut entries = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let file_type = entry.file_type().unwrap();
+            let file_path = entry.path();
+            if file_type.is_dir() && entry.file_name() != "ShouldFail" {
+                let modules = self.inline_schema_dir(&file_path, compiled, module_of);
+                if !modules.is_empty() {
+                    let name = entry.file_name().to_str().unwrap().to_string();
+                    code.push_str(&format!("pub mod {} {{\n{}}}\n", name, modules));
+                }
+            } else if file_type.is_file()
+                && file_path.extension().map(|e| e == "bop").unwrap_or(false)
+            {
+                code.push_str(&format!(
+                    "pub mod {} {{\n{}\n}}\n",
+                    module_name(&file_path),
+                    self.compile_grouped(&file_path, compiled, module_of)
+                ));
+            }
+        }
+        code
+    }
+}
 
-    // update the mod file
-    let mod_file_path = PathBuf::from(destination.as_ref()).join("mod.rs");
+/// The module path of a schema when subdirectories of `source` are nested modules, ending with the
+/// module of the schema itself.
+fn nested_module(source: &Path, schema: &Path) -> Vec<String> {
+    let mut module = schema
+        .parent()
+        .unwrap()
+        .strip_prefix(source)
+        .unwrap()
+        .iter()
+        .map(|m| m.to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    module.push(module_name(schema));
+    module
+}
+
+/// Find all schemas in `dir` along with their namespace, `path` is the directory names leading to
+/// `dir` from the source directory.
+fn collect_schemas(dir: &Path, path: &mut Vec<String>, schemas: &mut Vec<(PathBuf, String)>) {
+    let mut entries = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let file_type = entry.file_type().unwrap();
+        let file_path = entry.path();
+        if file_type.is_dir() && entry.file_name() != "ShouldFail" {
+            path.push(entry.file_name().to_str().unwrap().to_string());
+            collect_schemas(&file_path, path, schemas);
+            path.pop();
+        } else if file_type.is_file() && file_path.extension().map(|e| e == "bop").unwrap_or(false)
+        {
+            schemas.push((file_path, path.join(".")));
+        }
+    }
+}
+
+/// Create the destination if needed, register both directories with cargo, and return their
+/// canonical paths.
+fn prepare_dirs(source: &Path, destination: &Path) -> (PathBuf, PathBuf) {
+    if !destination.exists() {
+        fs::create_dir_all(destination).unwrap();
+    }
+    let (source, destination) = (canonicalize(source), canonicalize(destination));
+    // picks up new schemas and deleted generated files
+    println!("cargo:rerun-if-changed={}", source.to_str().unwrap());
+    println!("cargo:rerun-if-changed={}", destination.to_str().unwrap());
+    (source, destination)
+}
+
+/// Remove any files (and if `nested` any directories) in `destination` which were not just
+/// generated.
+fn remove_stale(destination: &Path, modules: &LinkedList<String>, nested: bool) {
+    for entry in fs::read_dir(destination).unwrap() {
+        let entry = entry.unwrap();
+        let file_type = entry.file_type().unwrap();
+        let path = entry.path();
+        if file_type.is_file() {
+            let is_module = path.extension().map(|e| e == "rs").unwrap_or(false)
+                && modules.contains(&file_stem(&path));
+            if entry.file_name() != "mod.rs" && !is_module {
+                fs::remove_file(path).unwrap();
+            }
+        } else if file_type.is_dir()
+            && nested
+            && !modules.contains(&entry.file_name().to_str().unwrap().to_string())
+        {
+            fs::remove_dir_all(path).unwrap();
+        }
+    }
+}
+
+/// Write a `mod.rs` file declaring all of the given modules and clean up old files.
+fn write_mod_file(destination: impl AsRef<Path>, modules: LinkedList<String>, nested: bool) {
+    remove_stale(destination.as_ref(), &modules, nested);
+    let tmp = tmp_path("mod.rs");
     fs::write(
-        &mod_file_path,
-        &files
+        &tmp,
+        modules
             .into_iter()
             .map(|mut schema_name| {
                 schema_name.insert_str(0, "pub mod ");
@@ -92,74 +666,68 @@ pub fn build_schema_dir(source: impl AsRef<Path>, destination: impl AsRef<Path>)
             .collect::<String>(),
     )
     .unwrap();
-    fmt_file(mod_file_path)
+    fmt_file(&tmp);
+    move_if_changed(tmp, PathBuf::from(destination.as_ref()).join("mod.rs"));
 }
 
 /// Build a single schema file and write it to the destination file.
 ///
 /// **WARNING: THIS OVERWRITES THE DESTINATION FILE.**
 pub fn build_schema(schema: impl AsRef<Path>, destination: impl AsRef<Path>) {
-    let (schema, destination) = (schema.as_ref(), destination.as_ref());
-    let compiler_path = compiler_path();
-    println!("cargo:rerun-if-changed={}", compiler_path.to_str().unwrap());
-    println!("cargo:rerun-if-changed={}", schema.to_str().unwrap());
-    let output = Command::new(compiler_path)
-        .arg("--files")
-        .arg(schema)
-        .arg("--rust")
-        .arg(destination.to_str().unwrap())
-        .output()
-        .expect("Could not run bebopc");
-    if !(output.status.success()) {
-        println!(
-            "cargo:warning=Failed to build schema {}",
-            schema.to_str().unwrap()
-        );
-        for line in String::from_utf8(output.stdout).unwrap().lines() {
-            println!("cargo:warning=STDOUT: {}", line);
-        }
-        for line in String::from_utf8(output.stderr).unwrap().lines() {
-            println!("cargo:warning=STDERR: {}", line);
-        }
-        panic!("Failed to build schema!");
-    }
-    fmt_file(destination);
+    BuildConfig::default().build_schema(schema, destination)
 }
 
-fn recurse_schema_dir(dir: impl AsRef<Path>, dest: impl AsRef<Path>) -> LinkedList<String> {
-    let mut list = LinkedList::new();
-    for dir_entry in fs::read_dir(&dir).unwrap() {
-        let dir_entry = dir_entry.unwrap();
-        let file_type = dir_entry.file_type().unwrap();
-        let file_path = PathBuf::from(dir.as_ref()).join(dir_entry.file_name());
-        if file_type.is_dir() {
-            if dir_entry.file_name() == "ShouldFail" {
-                // do nothing
-            } else {
-                list.append(&mut recurse_schema_dir(&file_path, dest.as_ref()));
-            }
-        } else if file_type.is_file()
-            && file_path
-                .extension()
-                .map(|s| s.to_str().unwrap())
-                .unwrap_or("")
-                == "bop"
-        {
-            let fname = format!(
-                "{}{}",
-                unsafe { GENERATED_PREFIX.as_deref().unwrap_or_else(|| "".into()) },
-                file_stem(file_path.as_path())
-            );
-            build_schema(
-                canonicalize(file_path.to_str().unwrap()),
-                canonicalize(&dest).join(fname.clone() + ".rs"),
-            );
-            list.push_back(fname);
-        } else {
-            // do nothing
+/// All schemas imported by `schema` directly or indirectly, which the code generated for it
+/// depends on. Imports which can not be read are left out.
+pub fn imported_schemas(schema: impl AsRef<Path>) -> Vec<PathBuf> {
+    imports::transitive_imports(schema.as_ref())
+}
+
+/// Format `code` and write it to `destination` if it changed.
+fn write_code(destination: &Path, code: &str) {
+    let tmp = tmp_path(destination.file_name().unwrap());
+    fs::write(&tmp, code).unwrap();
+    fmt_file(&tmp);
+    move_if_changed(tmp, destination);
+}
+
+/// Get a unique path to write intermediate output to before it is moved into place. Uses
+/// `OUT_DIR` when run from a build script.
+fn tmp_path(name: impl AsRef<Path>) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::var_os("OUT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("bebop");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(format!(
+        "{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        name.as_ref().to_str().unwrap()
+    ))
+}
+
+/// Move `from` to `to` unless `to` already has the same contents, in which case `to` is left
+/// untouched so cargo does not see it as modified.
+fn move_if_changed(from: impl AsRef<Path>, to: impl AsRef<Path>) {
+    let contents = fs::read(from.as_ref()).unwrap();
+    if fs::read(to.as_ref()).ok().as_ref() != Some(&contents) {
+        if let Some(parent) = to.as_ref().parent() {
+            fs::create_dir_all(parent).unwrap();
         }
+        fs::write(to, contents).unwrap();
     }
-    list
+    fs::remove_file(from).unwrap();
+}
+
+/// The name of the module generated for a schema.
+fn module_name(schema: &Path) -> String {
+    format!(
+        "{}{}",
+        unsafe { (*addr_of!(GENERATED_PREFIX)).as_deref().unwrap_or("") },
+        file_stem(schema)
+    )
 }
 
 fn file_stem(path: impl AsRef<Path>) -> String {
@@ -188,5 +756,16 @@ fn canonicalize(path: impl AsRef<Path>) -> PathBuf {
 }
 
 fn compiler_path() -> PathBuf {
-    (unsafe { COMPILER_PATH.clone() }).unwrap_or_else(|| canonicalize("bebopc"))
+    (unsafe { (*addr_of!(COMPILER_PATH)).clone() }).unwrap_or_else(default_compiler_path)
+}
+
+/// Download the compiler matching this crate's version if it is not already cached.
+#[cfg(feature = "downloader")]
+fn default_compiler_path() -> PathBuf {
+    ensure_compiler(BEBOPC_VERSION)
+}
+
+#[cfg(not(feature = "downloader"))]
+fn default_compiler_path() -> PathBuf {
+    canonicalize("bebopc")
 }
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::imports::ImportGroups;
+use crate::{canonicalize, compiler_path, BuildConfig};
+
+/// The compiler output for every schema in a directory, produced up front by running several
+/// compilers at once. It is used in the same order as when each schema was compiled as it was
+/// reached, so the failure reported is always the first by path no matter which finished first.
+pub(crate) struct Precompiled {
+    pub groups: ImportGroups,
+    results: BTreeMap<PathBuf, Result<String, (Vec<String>, String)>>,
+}
+
+impl Precompiled {
+    /// Compile each schema, or each group of schemas connected by imports, with up to
+    /// [`BuildConfig::jobs`] compilers running at a time.
+    pub fn new(config: &BuildConfig, schemas: &[(PathBuf, String)]) -> Self {
+        let groups = ImportGroups::new(schemas.iter().map(|(schema, _)| schema));
+        let mut units = Vec::new();
+        for (schema, _) in schemas {
+            let unit = match groups.group(schema) {
+                None => vec![schema.clone()],
+                Some(group) => group.to_vec(),
+            };
+            if !units.contains(&unit) {
+                units.push(unit);
+            }
+        }
+
+        if units.is_empty() {
+            return Self {
+                groups,
+                results: BTreeMap::new(),
+            };
+        }
+
+        // resolved once since finding it may download the compiler
+        let compiler = compiler_path();
+        let jobs = config
+            .jobs
+            .unwrap_or_else(default_jobs)
+            .clamp(1, units.len());
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(BTreeMap::new());
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| {
+                    while let Some(unit) = units.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let result = config.generate(&compiler, unit);
+                        results
+                            .lock()
+                            .unwrap()
+                            .insert(canonicalize(&unit[0]), result);
+                    }
+                });
+            }
+        });
+
+        Self {
+            groups,
+            results: results.into_inner().unwrap(),
+        }
+    }
+
+    /// The code for the schemas compiled as a unit, panicking with the compiler output if it
+    /// failed. Schemas which were not compiled up front are compiled now.
+    pub fn code(&self, config: &BuildConfig, unit: &[PathBuf]) -> String {
+        let result = match self.results.get(&canonicalize(&unit[0])) {
+            Some(result) => result.clone(),
+            None => config.generate(&compiler_path(), unit),
+        };
+        config.finish_compile(unit, result)
+    }
+}
+
+/// The number of jobs cargo is running, from `NUM_JOBS` which it sets for build scripts, or the
+/// available parallelism otherwise.
+fn default_jobs() -> usize {
+    std::env::var("NUM_JOBS")
+        .ok()
+        .and_then(|jobs| jobs.parse().ok())
+        .or_else(|| thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+}
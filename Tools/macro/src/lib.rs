@@ -0,0 +1,102 @@
+//! Embed Bebop schemas in a crate without a build script.
+
+use proc_macro::{TokenStream, TokenTree};
+use std::path::PathBuf;
+
+/// Compile a schema and expand to the generated definitions, as if the generated file had been
+/// included in the calling module.
+///
+/// The path is relative to the root of the calling crate, the same as paths given to a build
+/// script. The compiler is found the same way `bebop-tools` finds it, so it is downloaded when the
+/// `downloader` feature is enabled. The `COMPILER_PATH` environment variable can point at a
+/// specific executable instead, like setting `bebop_tools::COMPILER_PATH` in a build script, or
+/// `BEBOPC_PATH` if `COMPILER_PATH` is not set. The schema and everything it imports are tracked
+/// like `include_bytes!` so changing them recompiles the calling crate.
+///
+/// ```ignore
+/// mod jazz {
+///     bebop_macro::include_schema!("schemas/jazz.bop");
+/// }
+/// ```
+///
+/// Each schema has an `owned` module, so use a separate module for each schema included.
+///
+/// The path must be a string literal naming a schema which exists.
+///
+/// ```compile_fail
+/// bebop_macro::include_schema!(jazz);
+/// ```
+///
+/// ```compile_fail
+/// bebop_macro::include_schema!("schemas/missing.bop");
+/// ```
+#[proc_macro]
+pub fn include_schema(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(tokens) => tokens,
+        Err(err) => format!("::core::compile_error!({:?});", err)
+            .parse()
+            .unwrap(),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let root = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default());
+    let compiler = std::env::var_os("COMPILER_PATH").or_else(|| std::env::var_os("BEBOPC_PATH"));
+    if let Some(compiler) = compiler {
+        unsafe {
+            bebop_tools::COMPILER_PATH = Some(root.join(compiler));
+        }
+    }
+    let schema = root.join(parse_path(input)?);
+    let schema = schema
+        .canonicalize()
+        .map_err(|err| format!("Could not read schema {:?} ({})", schema, err))?;
+    let code = bebop_tools::BuildConfig::default().compile_schema(&schema)?;
+
+    let tracked = std::iter::once(schema.clone())
+        .chain(bebop_tools::imported_schemas(&schema))
+        .map(|path| {
+            format!(
+                "const _: &[u8] = include_bytes!({:?});\n",
+                path.to_str().unwrap()
+            )
+        })
+        .collect::<String>();
+
+    // the generated code starts with inner attributes so it is placed in its own module
+    let module = format!(
+        "__bebop_{}",
+        schema
+            .file_stem()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .replace('-', "_")
+    );
+    format!(
+        "{}mod {1} {{\n{2}\n}}\npub use {1}::*;",
+        tracked, module, code
+    )
+    .parse()
+    .map_err(|err| format!("Generated code for {:?} is invalid ({})", schema, err))
+}
+
+/// Get the value of the single string literal passed to the macro.
+fn parse_path(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+        _ => return Err("Expected the path to a schema as a string literal".into()),
+    };
+    if let Some(raw) = literal.strip_prefix('r') {
+        let raw = raw.trim_matches('#');
+        Ok(raw[1..raw.len() - 1].to_string())
+    } else if literal.starts_with('"') {
+        Ok(literal[1..literal.len() - 1]
+            .replace("\\\\", "\\")
+            .replace("\\\"", "\""))
+    } else {
+        Err("Expected the path to a schema as a string literal".into())
+    }
+}